@@ -2,6 +2,9 @@
 
 const { load, currentTarget } = require("@neon-rs/load");
 const { familySync, GLIBC } = require("detect-libc");
+const fs = require("fs");
+const path = require("path");
+const { EventEmitter } = require("events");
 
 function requireNative() {
   if (process.env.LIBSQL_JS_DEV) {
@@ -30,23 +33,71 @@ const {
   databaseClose,
   databaseSyncSync,
   databaseSyncUntilSync,
+  databaseWaitForReplicationIndexSync,
+  databaseSyncStatus,
+  databasePendingChanges,
+  databasePushSync,
+  databasePullSync,
+  databaseSetSyncListener,
+  databasePauseSync,
+  databaseResumeSync,
+  databaseSetSyncPeriod,
   databaseExecSync,
   databasePrepareSync,
   databaseDefaultSafeIntegers,
+  databaseImportFileSync,
+  databaseSchemaSync,
+  databaseMigrateSync,
   databaseLoadExtension,
   databaseMaxWriteReplicationIndex,
+  databaseSetStatementCacheSize,
+  databaseSetSlowQueryLog,
+  databaseStatementCacheStats,
   statementRaw,
   statementIsReader,
   statementGet,
   statementRun,
+  statementRunMany,
   statementRowsSync,
+  statementAllSync,
+  statementColumnar,
+  statementArrow,
+  statementArrowBatches,
+  statementExportSync,
   statementColumns,
   statementSafeIntegers,
+  statementStats,
+  statementExplain,
+  statementFinalize,
   rowsNext,
+  rowsClose,
+  arrowBatchesNext,
+  arrowBatchesClose,
+  runtimeConfigure,
+  runtimeMetrics,
+  setLogger: setNativeLogger,
 } = requireNative();
 
 const SqliteError = require("./sqlite-error");
 
+// Fall back to the symbols Node.js uses internally on versions that don't
+// define them yet.
+const disposeSymbol = Symbol.dispose ?? Symbol.for("nodejs.dispose");
+
+/**
+ * Reads the migrations in a directory, where `<id>.sql` files hold the SQL that applies a migration and optional
+ * `<id>.down.sql` files the SQL that rolls it back. Migrations are ordered by their ID.
+ */
+function readMigrations(dir) {
+  const files = fs.readdirSync(dir).filter((file) => file.endsWith(".sql")).sort();
+  const names = new Set(files);
+  return files.filter((file) => !file.endsWith(".down.sql")).map((file) => {
+    const id = file.slice(0, -".sql".length);
+    const down = names.has(`${id}.down.sql`) ? fs.readFileSync(path.join(dir, `${id}.down.sql`), "utf8") : undefined;
+    return { id, up: fs.readFileSync(path.join(dir, file), "utf8"), down };
+  });
+}
+
 function convertError(err) {
   if (err.libsqlError) {
     return new SqliteError(err.message, err.code, err.rawCode);
@@ -54,10 +105,22 @@ function convertError(err) {
   return err;
 }
 
+/**
+ * Waits for the replica to reach the replication index set by `Statement.consistency()`.
+ */
+function waitForReplica(stmt) {
+  if (stmt.minReplicationIndex != null) {
+    databaseWaitForReplicationIndexSync.call(stmt.database.db, stmt.minReplicationIndex, stmt.consistencyTimeoutMs);
+  }
+}
+
 /**
  * Database represents a connection that can prepare and execute SQL statements.
+ *
+ * Embedded replicas emit a `sync` event with the sync stats after every periodic sync, and a `syncError` event with
+ * the error if a periodic sync fails.
  */
-class Database {
+class Database extends EventEmitter {
   /**
    * Creates a new database connection. If the database file pointed to by `path` does not exists, it will be created.
    *
@@ -65,6 +128,7 @@ class Database {
    * @param {string} path - Path to the database file.
    */
   constructor(path, opts) {
+    super();
     const encryptionCipher = opts?.encryptionCipher ?? "aes256cbc";
     if (opts && opts.syncUrl) {
       var authToken = "";
@@ -78,12 +142,26 @@ class Database {
       const syncPeriod = opts?.syncPeriod ?? 0.0;
       const readYourWrites = opts?.readYourWrites ?? true;
       const offline = opts?.offline ?? false;
-      this.db = databaseOpenWithSync(path, opts.syncUrl, authToken, encryptionCipher, encryptionKey, syncPeriod, readYourWrites, offline);
+      const bootstrapFrom = opts?.bootstrapFrom ?? "";
+      this.db = databaseOpenWithSync(path, opts.syncUrl, authToken, encryptionCipher, encryptionKey, syncPeriod, readYourWrites, offline, bootstrapFrom);
+      // The listener must not keep the database alive.
+      const ref = new WeakRef(this);
+      databaseSetSyncListener.call(this.db, (event, payload) => {
+        ref.deref()?.emit(event, event === "syncError" ? convertError(payload) : payload);
+      });
     } else {
       const authToken = opts?.authToken ?? "";
       const encryptionKey = opts?.encryptionKey ?? "";
       this.db = databaseOpen(path, authToken, encryptionCipher, encryptionKey);
     }
+    if (opts?.statementCacheSize) {
+      databaseSetStatementCacheSize.call(this.db, opts.statementCacheSize);
+    }
+    if (opts?.slowQueryThresholdMs !== undefined) {
+      if (typeof opts.onSlowQuery !== "function")
+        throw new TypeError("Expected options.onSlowQuery to be a function");
+      databaseSetSlowQueryLog.call(this.db, opts.slowQueryThresholdMs, opts.onSlowQuery);
+    }
     // TODO: Use a libSQL API for this?
     this.memory = path === ":memory:";
     this.readonly = false;
@@ -100,12 +178,79 @@ class Database {
     });
   }
 
-  sync() {
-    return databaseSyncSync.call(this.db);
+  /**
+   * Synchronizes the embedded replica with the primary.
+   *
+   * @param {object} [options] - Options: the `onProgress` callback, which is called periodically while the sync runs,
+   * `changes`, whether to return the tables and rows changed by the sync, and `onConflict`, what to do when the
+   * primary rejects the local writes of an offline database.
+   */
+  sync(options) {
+    return databaseSyncSync.call(this.db, options?.onProgress, options?.changes ?? false, options?.onConflict ?? "fail");
+  }
+
+  /**
+   * Pushes the local writes of a database opened with the `offline` option to the primary without pulling.
+   *
+   * @param {object} [options] - Options: `onConflict`, what to do when the primary rejects the local writes.
+   */
+  push(options) {
+    return databasePushSync.call(this.db, options?.onConflict ?? "fail");
+  }
+
+  /**
+   * Pulls the frames of the primary into the embedded replica or offline database without pushing local writes.
+   * An offline database pulls nothing while it has local writes that have not been pushed.
+   */
+  pull() {
+    return databasePullSync.call(this.db);
+  }
+
+  /**
+   * Returns the local writes of a database opened with the `offline` option that have not been pushed.
+   */
+  pendingChanges() {
+    return databasePendingChanges.call(this.db);
+  }
+
+  /**
+   * Returns the synchronization status of the embedded replica.
+   */
+  syncStatus() {
+    return databaseSyncStatus.call(this.db);
+  }
+
+  /**
+   * Pauses the periodic sync. A sync that is in progress runs to completion.
+   */
+  pauseSync() {
+    databasePauseSync.call(this.db);
+  }
+
+  /**
+   * Resumes the periodic sync paused by `pauseSync()`.
+   */
+  resumeSync() {
+    databaseResumeSync.call(this.db);
   }
 
-  syncUntil(replicationIndex) {
-    return databaseSyncUntilSync.call(this.db, replicationIndex);
+  /**
+   * Changes the period of the periodic sync, or disables it if `seconds` is zero.
+   *
+   * @param {number} seconds - The period in seconds.
+   */
+  setSyncPeriod(seconds) {
+    databaseSetSyncPeriod.call(this.db, seconds);
+  }
+
+  /**
+   * Syncs the replica until it reaches a replication index.
+   *
+   * @param {number} replicationIndex - The replication index to wait for.
+   * @param {object} [options] - Options. Set `timeoutMs` to give up after that many milliseconds.
+   */
+  syncUntil(replicationIndex, options) {
+    return databaseSyncUntilSync.call(this.db, replicationIndex, options?.timeoutMs);
   }
 
   /**
@@ -116,7 +261,7 @@ class Database {
   prepare(sql) {
     try {
       const stmt = databasePrepareSync.call(this.db, sql);
-      return new Statement(stmt);  
+      return new Statement(stmt, this);  
     } catch (err) {
       throw convertError(err);
     }
@@ -132,12 +277,18 @@ class Database {
       throw new TypeError("Expected first argument to be a function");
 
     const db = this;
+    let replicationIndex;
     const wrapTxn = (mode) => {
       return (...bindParameters) => {
+        replicationIndex = undefined;
         db.exec("BEGIN " + mode);
         try {
           const result = fn(...bindParameters);
-          db.exec("COMMIT");
+          try {
+            replicationIndex = databaseExecSync.call(db.db, "COMMIT");
+          } catch (err) {
+            throw convertError(err);
+          }
           return result;
         } catch (err) {
           db.exec("ROLLBACK");
@@ -151,6 +302,8 @@ class Database {
       immediate: { value: wrapTxn("IMMEDIATE") },
       exclusive: { value: wrapTxn("EXCLUSIVE") },
       database: { value: this, enumerable: true },
+      // The replication index of the last commit of an embedded replica.
+      replicationIndex: { get: () => replicationIndex, enumerable: true },
     };
     Object.defineProperties(properties.default.value, properties);
     Object.defineProperties(properties.deferred.value, properties);
@@ -233,6 +386,90 @@ class Database {
     return databaseMaxWriteReplicationIndex.call(this.db)
   }
 
+  /**
+   * Returns the hit and miss counters of the prepared statement cache.
+   */
+  statementCacheStats() {
+    return databaseStatementCacheStats.call(this.db);
+  }
+
+  /**
+   * Returns the query plan of a SQL statement.
+   *
+   * @param {string} sql - The SQL statement to explain.
+   * @param {object} [options] - Options. Set `bytecode` to `true` to also return the bytecode program.
+   */
+  explain(sql, options) {
+    const stmt = this.prepare(sql);
+    try {
+      return stmt.explain(options);
+    } finally {
+      stmt.finalize();
+    }
+  }
+
+  /**
+   * Executes a SQL query and writes the resulting rows to a file.
+   *
+   * @param {string} sql - The SQL query to execute.
+   * @param bindParameters - The bind parameters for executing the query.
+   * @param {object} options - Options: the `path` of the file to write and its `format` (`"csv"` or `"ndjson"`).
+   */
+  exportQuery(sql, bindParameters, options) {
+    if (typeof options?.path !== "string")
+      throw new TypeError("Expected options.path to be a string");
+    const stmt = this.prepare(sql);
+    try {
+      return statementExportSync.call(stmt.stmt, bindParameters ?? [], options.format ?? "csv", options.path);
+    } catch (err) {
+      throw convertError(err);
+    } finally {
+      stmt.finalize();
+    }
+  }
+
+  /**
+   * Inserts the records of a file into a table.
+   *
+   * @param {string} table - The table to insert into.
+   * @param {string} path - Path to the file to import.
+   * @param {object} [options] - Options: the file `format` (`"csv"` or `"ndjson"`), the `columns` to insert into, and the `batchSize` of each INSERT statement.
+   */
+  importFile(table, path, options) {
+    try {
+      return databaseImportFileSync.call(this.db, table, path, options?.format ?? "csv", options?.columns ?? [], options?.batchSize ?? 10000);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Applies the pending schema migrations, each in its own transaction, and records them in a migrations table.
+   *
+   * @param {object} options - Options: the `dir` of the migration files or the `migrations` as `{ id, up, down }` objects, the migrations `table`, and the migration to migrate `to`.
+   */
+  migrate(options) {
+    const migrations = options?.dir !== undefined ? readMigrations(options.dir) : options?.migrations;
+    if (!Array.isArray(migrations))
+      throw new TypeError("Expected options.dir or options.migrations");
+    try {
+      return databaseMigrateSync.call(this.db, migrations, options.table ?? "_libsql_migrations", options.to ?? "");
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the tables, views and triggers of the main, temporary and attached schemas.
+   */
+  schema() {
+    try {
+      return databaseSchemaSync.call(this.db);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
   /**
    * Executes a SQL statement.
    *
@@ -261,6 +498,10 @@ class Database {
     this.open = false;
   }
 
+  [disposeSymbol]() {
+    this.close();
+  }
+
   /**
    * Toggle 64-bit integer support.
    */
@@ -278,8 +519,9 @@ class Database {
  * Statement represents a prepared SQL statement that can be executed.
  */
 class Statement {
-  constructor(stmt) {
+  constructor(stmt, database) {
     this.stmt = stmt;
+    this.database = database;
   }
 
   /**
@@ -296,11 +538,24 @@ class Statement {
     return statementIsReader.call(this.stmt);
   }
 
+  /**
+   * Makes the statement wait for the replica to reach a replication index before every execution.
+   *
+   * @param {object} options - Options. `minReplicationIndex` is the replication index to wait for, usually one
+   * returned by `maxWriteReplicationIndex()`, and `timeoutMs` limits how long to wait.
+   */
+  consistency(options) {
+    this.minReplicationIndex = options?.minReplicationIndex;
+    this.consistencyTimeoutMs = options?.timeoutMs;
+    return this;
+  }
+
   /**
    * Executes the SQL statement and returns an info object.
    */
   run(...bindParameters) {
     try {
+      waitForReplica(this);
       if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
         return statementRun.call(this.stmt, bindParameters[0]);
       } else {
@@ -311,6 +566,21 @@ class Statement {
     }
   }
 
+  /**
+   * Executes the SQL statement once for every set of bind parameters and returns an info object.
+   *
+   * @param rows - An array of bind parameters, or an object that maps parameter names to columns of values.
+   * @param {object} [options] - Options. Set `transaction` to `false` to not wrap the batch in a transaction.
+   */
+  runMany(rows, options) {
+    try {
+      waitForReplica(this);
+      return statementRunMany.call(this.stmt, rows, options?.transaction ?? true);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
   /**
    * Executes the SQL statement and returns the first row.
    *
@@ -318,6 +588,7 @@ class Statement {
    */
   get(...bindParameters) {
     try {
+      waitForReplica(this);
       if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
         return statementGet.call(this.stmt, bindParameters[0]);
       } else {
@@ -335,10 +606,15 @@ class Statement {
    */
   iterate(...bindParameters) {
     var rows = undefined;
-    if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
-      rows = statementRowsSync.call(this.stmt, bindParameters[0]);
-    } else {
-      rows = statementRowsSync.call(this.stmt, bindParameters.flat());
+    try {
+      waitForReplica(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        rows = statementRowsSync.call(this.stmt, bindParameters[0]);
+      } else {
+        rows = statementRowsSync.call(this.stmt, bindParameters.flat());
+      }
+    } catch (err) {
+      throw convertError(err);
     }
     const iter = {
       nextRows: Array(100),
@@ -360,9 +636,18 @@ class Statement {
           throw convertError(err);
         }
       },
+      return() {
+        rowsClose.call(rows);
+        this.nextRows.fill(null);
+        this.nextRowIndex = 0;
+        return { done: true };
+      },
       [Symbol.iterator]() {
         return this;
       },
+      [disposeSymbol]() {
+        this.return();
+      },
     };
     return iter;
   }
@@ -374,16 +659,97 @@ class Statement {
    */
   all(...bindParameters) {
     try {
-      const result = [];
-      for (const row of this.iterate(...bindParameters)) {
-        result.push(row);
+      waitForReplica(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        return statementAllSync.call(this.stmt, bindParameters[0]);
+      } else {
+        return statementAllSync.call(this.stmt, bindParameters.flat());
+      }
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Executes the SQL statement and returns the result set in columnar form.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  columnar(...bindParameters) {
+    try {
+      waitForReplica(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        return statementColumnar.call(this.stmt, bindParameters[0]);
+      } else {
+        return statementColumnar.call(this.stmt, bindParameters.flat());
+      }
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Executes the SQL statement and returns the result set as an Apache Arrow IPC stream.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  arrow(...bindParameters) {
+    try {
+      waitForReplica(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        return statementArrow.call(this.stmt, bindParameters[0]);
+      } else {
+        return statementArrow.call(this.stmt, bindParameters.flat());
       }
-      return result;
     } catch (err) {
       throw convertError(err);
     }
   }
 
+  /**
+   * Executes the SQL statement and returns an iterator to the result set as
+   * an Apache Arrow IPC stream, one record batch at a time.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  arrowBatches(...bindParameters) {
+    var batches = undefined;
+    try {
+      waitForReplica(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        batches = statementArrowBatches.call(this.stmt, bindParameters[0]);
+      } else {
+        batches = statementArrowBatches.call(this.stmt, bindParameters.flat());
+      }
+    } catch (err) {
+      throw convertError(err);
+    }
+    const iter = {
+      next() {
+        try {
+          const chunk = arrowBatchesNext.call(batches);
+          if (!chunk) {
+            return { done: true };
+          }
+          return { value: chunk, done: false };
+        } catch (err) {
+          throw convertError(err);
+        }
+      },
+      return() {
+        arrowBatchesClose.call(batches);
+        return { done: true };
+      },
+      [Symbol.iterator]() {
+        return this;
+      },
+      [disposeSymbol]() {
+        this.return();
+      },
+    };
+    return iter;
+  }
+
   /**
    * Returns the columns in the result set returned by this prepared statement.
    */
@@ -398,7 +764,65 @@ class Statement {
     statementSafeIntegers.call(this.stmt, toggle ?? true);
     return this;
   }
+
+  /**
+   * Returns the execution statistics of the statement.
+   */
+  stats() {
+    return statementStats.call(this.stmt);
+  }
+
+  /**
+   * Returns the query plan of the statement.
+   *
+   * @param {object} [options] - Options. Set `bytecode` to `true` to also return the bytecode program.
+   */
+  explain(options) {
+    try {
+      return statementExplain.call(this.stmt, options?.bytecode ?? false);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Finalizes the prepared statement, releasing its resources. The statement cannot be used afterwards.
+   */
+  finalize() {
+    statementFinalize.call(this.stmt);
+  }
+
+  [disposeSymbol]() {
+    this.finalize();
+  }
+}
+
+/**
+ * Configures the runtime that executes database operations. This must be called before the first database is opened.
+ *
+ * @param {object} [options] - Options: the number of `workerThreads`, the `maxBlockingThreads`, the `threadName`, and whether to use a `currentThread` runtime, which runs all tasks on one background thread.
+ */
+function configure(options) {
+  runtimeConfigure(options?.workerThreads ?? 0, options?.maxBlockingThreads ?? 0, options?.threadName ?? "", options?.currentThread ?? false);
+}
+
+/**
+ * Forwards native log records to a JavaScript logger, or restores the default logging to standard output if `options` is `null`.
+ *
+ * @param {object|null} options - Options: the minimum `level` of the records (`"trace"`, `"debug"`, `"info"`, `"warn"` or `"error"`) and the `callback` that receives them.
+ */
+function setLogger(options) {
+  if (options == null) {
+    setNativeLogger("");
+    return;
+  }
+  if (typeof options.callback !== "function")
+    throw new TypeError("Expected options.callback to be a function");
+  setNativeLogger(options.level ?? "info", options.callback);
 }
 
 module.exports = Database;
 module.exports.SqliteError = SqliteError;
+module.exports.configure = configure;
+module.exports.runtimeMetrics = runtimeMetrics;
+module.exports.setLogger = setLogger;
@@ -1,4 +1,6 @@
 import test from "ava";
+import fs from "fs";
+import net from "net";
 
 test.beforeEach(async (t) => {
   const [db, errorType] = await connect();
@@ -135,6 +137,22 @@ test.serial("Statement.all()", async (t) => {
   t.deepEqual(await stmt.all(), expected);
 });
 
+test.serial("Statement.all() [finalized while running]", async (t) => {
+  const [db] = await connect("hello.db", { statementCacheSize: 2 });
+
+  // The statement isn't returned to the cache until the query finishes, so
+  // another statement with the same SQL doesn't share it.
+  const sql = "SELECT name FROM users ORDER BY id";
+  const stmt = await db.prepare(sql);
+  const rows = stmt.all();
+  stmt.finalize();
+  t.is(db.statementCacheStats().size, 0);
+  const other = await db.prepare(sql);
+  const expected = [{ name: "Alice" }, { name: "Bob" }];
+  t.deepEqual(await Promise.all([rows, other.all()]), [expected, expected]);
+  db.close();
+});
+
 test.serial("Statement.all() [raw]", async (t) => {
   const db = t.context.db;
 
@@ -239,6 +257,8 @@ test.serial("Database.transaction()", async (t) => {
     { name: "Junior", email: "junior@example.org" },
   ]);
   t.is(db.inTransaction, false);
+  // Only the commits of embedded replicas have a replication index.
+  t.is(insertMany.replicationIndex, undefined);
 
   const stmt = await db.prepare("SELECT * FROM users WHERE id = ?");
   t.is(stmt.get(3).name, "Joey");
@@ -314,6 +334,76 @@ test.serial("Database.exec() after close()", async (t) => {
   });
 });
 
+test.serial("Statement.run() after close()", async (t) => {
+  const db = t.context.db;
+  const stmt = await db.prepare("SELECT 1");
+  await db.close();
+  await t.throwsAsync(async () => {
+    await stmt.run();
+  }, {
+    instanceOf: TypeError,
+    message: "The database connection is not open"
+  });
+});
+
+test.serial("Use after Database.close()", async (t) => {
+  const db = t.context.db;
+  const stmt = await db.prepare("SELECT * FROM users WHERE id = ?");
+  const insert = await db.prepare("INSERT INTO users (name, email) VALUES (?, ?)");
+  const it = await (await db.prepare("SELECT * FROM users")).iterate();
+  await db.close();
+
+  const uses = {
+    "Database.inTransaction": () => db.inTransaction,
+    "Database.prepare()": () => db.prepare("SELECT 1"),
+    "Database.exec()": () => db.exec("SELECT 1"),
+    "Database.interrupt()": () => db.interrupt(),
+    "Database.sync()": () => db.sync(),
+    "Database.syncUntil()": () => db.syncUntil(1),
+    "Database.maxWriteReplicationIndex()": () => db.maxWriteReplicationIndex(),
+    "Database.schema()": () => db.schema(),
+    "Database.exportQuery()": () => db.exportQuery("SELECT 1", [], { path: "closed.csv" }),
+    "Database.importFile()": () => db.importFile("users", "closed.csv"),
+    "Statement.raw()": () => stmt.raw(),
+    "Statement.reader": () => stmt.reader,
+    "Statement.run()": () => insert.run("Carol", "carol@example.net"),
+    "Statement.runMany()": () => insert.runMany([["Carol", "carol@example.net"]]),
+    "Statement.get()": () => stmt.get(1),
+    "Statement.iterate()": () => stmt.iterate(1),
+    "Statement.all()": () => stmt.all(1),
+    "Statement.columnar()": () => stmt.columnar(1),
+    "Statement.arrow()": () => stmt.arrow(1),
+    "Statement.arrowBatches()": () => stmt.arrowBatches(1),
+    "Statement.columns()": () => stmt.columns(),
+    "Statement.safeIntegers()": () => stmt.safeIntegers(),
+    "iterator.next()": () => it.next(),
+  };
+  for (const [name, use] of Object.entries(uses)) {
+    await t.throwsAsync(async () => await use(), {
+      instanceOf: TypeError,
+      message: "The database connection is not open",
+    }, name);
+  }
+});
+
+test.serial("Symbol.asyncDispose", async (t) => {
+  const asyncDispose = Symbol.asyncDispose ?? Symbol.for("nodejs.asyncDispose");
+  const db = t.context.db;
+
+  const stmt = await db.prepare("SELECT * FROM users");
+  const it = await stmt.iterate();
+  t.is(it.next().value.name, "Alice");
+  await it[asyncDispose]();
+  t.deepEqual(it.next(), { done: true });
+  const batches = await stmt.arrowBatches();
+  await batches[asyncDispose]();
+  t.deepEqual(batches.next(), { done: true });
+  await stmt[asyncDispose]();
+  await t.throwsAsync(async () => await stmt.all(), { instanceOf: TypeError });
+  await db[asyncDispose]();
+  t.false(db.open);
+});
+
 test.serial("Database.interrupt()", async (t) => {
   const db = t.context.db;
   const stmt = await db.prepare("WITH RECURSIVE infinite_loop(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM infinite_loop) SELECT * FROM infinite_loop;");
@@ -328,12 +418,47 @@ test.serial("Database.interrupt()", async (t) => {
   });
 });
 
-const connect = async (path_opt) => {
+test.serial("Statement.consistency() [unresponsive primary]", async (t) => {
+  const path = "consistency-test.db";
+  // The server accepts connections but never answers, so syncs hang until
+  // they time out.
+  const sockets = [];
+  const server = net.createServer((socket) => sockets.push(socket));
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const [db] = await connect(path, { syncUrl: `http://127.0.0.1:${server.address().port}` });
+
+  const stmt = await db.prepare("SELECT 1 AS x");
+  stmt.consistency({ minReplicationIndex: 1, timeoutMs: 200 });
+  const result = stmt.get();
+  t.true(result instanceof Promise);
+  // The wait doesn't block the event loop.
+  let ticks = 0;
+  const timer = setInterval(() => ticks++, 10);
+  const err = await t.throwsAsync(result, {
+    message: "Timed out after 200 ms waiting for replication index 1",
+  });
+  clearInterval(timer);
+  t.is(err.code, "SYNC_TIMEOUT");
+  t.true(ticks > 0);
+  await t.throwsAsync(stmt.run(), { code: "SYNC_TIMEOUT" });
+
+  stmt.consistency({});
+  t.deepEqual(stmt.get(), { x: 1 });
+
+  db.close();
+  for (const socket of sockets) socket.destroy();
+  server.close();
+  for (const file of fs.readdirSync(".")) {
+    if (file.startsWith(path)) fs.rmSync(file, { recursive: true });
+  }
+});
+
+const connect = async (path_opt, options_opt) => {
   const path = path_opt ?? "hello.db";
   const provider = process.env.PROVIDER;
   const database = process.env.LIBSQL_DATABASE ?? path;
   const x = await import("libsql/promise");
-  const options = {};
+  const options = options_opt ?? {};
   const db = new x.default(database, options);
   return [db, x.SqliteError];
 };
@@ -1,4 +1,7 @@
 import test from "ava";
+import fs from "fs";
+import net from "net";
+import { tableFromIPC } from "apache-arrow";
 
 test.serial("Statement.run() returning duration", async (t) => {
   const db = t.context.db;
@@ -18,10 +21,953 @@ test.serial("Statement.get() returning duration", async (t) => {
   t.log(info._metadata?.duration)
 });
 
-const connect = async (path_opt) => {
+test.serial("Statement.stats()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT * FROM users WHERE id >= ?");
+  t.is(stmt.stats().executions, 0);
+  stmt.get(1);
+  stmt.all(1);
+  for (const row of stmt.iterate(2)) {
+    t.is(row.id, 2);
+  }
+  const stats = stmt.stats();
+  t.is(stats.executions, 3);
+  t.is(stats.rowsReturned, 4);
+  t.true(stats.minTime <= stats.maxTime);
+  t.true(stats.maxTime <= stats.totalTime);
+});
+
+test.serial("Statement.stats() [status counters]", async (t) => {
+  const [db] = await connect(":memory:");
+  db.exec("CREATE TABLE t (x INTEGER, y TEXT)");
+  db.exec("INSERT INTO t VALUES (3, 'c'), (1, 'a'), (2, 'b')");
+
+  const stmt = db.prepare("SELECT y FROM t ORDER BY x");
+  t.is(stmt.stats().vmSteps, 0);
+  t.deepEqual(stmt.raw().all().flat(), ["a", "b", "c"]);
+  const stats = stmt.stats();
+  // libSQL counts the rows read from the table and from the sorter.
+  t.is(stats.rowsRead, 6);
+  t.is(stats.fullScanSteps, 2);
+  t.is(stats.sorts, 1);
+  t.is(stats.autoIndexes, 0);
+  t.true(stats.vmSteps > 0);
+  stmt.all();
+  t.is(stmt.stats().rowsRead, 12);
+  t.is(stmt.stats().sorts, 2);
+
+  // A statement checked out of the cache starts from zero.
+  const [cached] = await connect(":memory:", { statementCacheSize: 1 });
+  cached.exec("CREATE TABLE t (x)");
+  const a = cached.prepare("SELECT * FROM t ORDER BY x");
+  a.all();
+  a.finalize();
+  t.is(cached.prepare("SELECT * FROM t ORDER BY x").stats().sorts, 0);
+  cached.close();
+  db.close();
+});
+
+test.serial("Database slow query log", async (t) => {
+  const events = [];
+  const [db] = await connect(":memory:", {
+    slowQueryThresholdMs: 0,
+    onSlowQuery: (event) => events.push(event),
+  });
+  db.exec("CREATE TABLE t (x TEXT)");
+  db.prepare("INSERT INTO t VALUES (?)").run("hello");
+  db.prepare("SELECT * FROM t WHERE x = :x").all({ x: "hello" });
+  t.throws(() => db.prepare("SELECT abs(-9223372036854775807 - 1)").get(), {
+    message: /integer overflow/,
+  });
+  t.throws(() => db.exec("INSERT INTO missing VALUES (1)"), {
+    message: /no such table/,
+  });
+  await new Promise((resolve) => setTimeout(resolve, 100));
+
+  t.is(events.length, 5);
+  t.is(events[0].sql, "CREATE TABLE t (x TEXT)");
+  t.is(events[1].parameters, "text(5)");
+  t.is(events[2].parameters, ":x=text(5)");
+  t.is(events[2].mode, "local");
+  t.true(events[2].durationMs > 0);
+  t.is(events[3].sql, "SELECT abs(-9223372036854775807 - 1)");
+  t.is(events[4].sql, "INSERT INTO missing VALUES (1)");
+});
+
+test.serial("Statement.explain()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT * FROM users WHERE id = ?");
+  const { plan, bytecode } = stmt.explain();
+  t.is(plan.length, 1);
+  t.regex(plan[0].detail, /^SEARCH users USING INTEGER PRIMARY KEY/);
+  t.is(bytecode, undefined);
+
+  const result = db.explain("SELECT * FROM users WHERE name = ?", { bytecode: true });
+  t.regex(result.plan[0].detail, /^SCAN users/);
+  t.is(result.bytecode[0].opcode, "Init");
+});
+
+test.serial("Database.explain() finalizes its statement", async (t) => {
+  const [db] = await connect("hello.db", { statementCacheSize: 2 });
+
+  db.explain("SELECT * FROM users");
+  db.explain("SELECT * FROM users");
+  t.deepEqual(db.statementCacheStats(), { hits: 1, misses: 1, size: 1, capacity: 2 });
+  db.close();
+});
+
+test.serial("Database.schema()", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      DROP TABLE IF EXISTS posts;
+      CREATE TABLE posts (
+        id INTEGER PRIMARY KEY,
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        title TEXT DEFAULT 'untitled',
+        slug TEXT GENERATED ALWAYS AS (lower(title)) VIRTUAL
+      );
+      CREATE INDEX posts_user_id ON posts (user_id);
+  `);
+  const main = db.schema().find((schema) => schema.name === "main");
+  const posts = main.tables.find((table) => table.name === "posts");
+  t.deepEqual(
+    posts.columns.map((column) => [column.name, column.type, column.notNull, column.default, column.primaryKey, column.generated]),
+    [
+      ["id", "INTEGER", false, null, 1, null],
+      ["user_id", "INTEGER", true, null, 0, null],
+      ["title", "TEXT", false, "'untitled'", 0, null],
+      ["slug", "TEXT", false, null, 0, "virtual"],
+    ]
+  );
+  t.deepEqual(posts.indexes, [
+    { name: "posts_user_id", unique: false, origin: "c", partial: false, columns: ["user_id"] },
+  ]);
+  t.deepEqual(posts.foreignKeys, [
+    { table: "users", from: ["user_id"], to: ["id"], onUpdate: "NO ACTION", onDelete: "CASCADE" },
+  ]);
+});
+
+test.serial("Database.migrate()", async (t) => {
+  const [db] = await connect(":memory:");
+
+  const migrations = [
+    { id: "001_posts", up: "CREATE TABLE posts (id INTEGER PRIMARY KEY)", down: "DROP TABLE posts" },
+    { id: "002_title", up: "ALTER TABLE posts ADD COLUMN title TEXT", down: "ALTER TABLE posts DROP COLUMN title" },
+  ];
+  t.deepEqual(db.migrate({ migrations, to: "001_posts" }), { applied: ["001_posts"], rolledBack: [] });
+  t.deepEqual(db.migrate({ migrations }), { applied: ["002_title"], rolledBack: [] });
+  t.deepEqual(db.migrate({ migrations }), { applied: [], rolledBack: [] });
+  db.prepare("INSERT INTO posts (title) VALUES (?)").run("hello");
+
+  const edited = [migrations[0], { ...migrations[1], up: "ALTER TABLE posts ADD COLUMN body TEXT" }];
+  t.throws(() => db.migrate({ migrations: edited }), {
+    message: "Migration 002_title has been edited after it was applied",
+  });
+
+  const failing = [...migrations, { id: "003_fail", up: "CREATE TABLE t (x); SELECT * FROM missing" }];
+  t.throws(() => db.migrate({ migrations: failing }), { message: /^Migration 003_fail failed/ });
+  t.false(db.schema()[0].tables.some((table) => table.name === "t"));
+
+  t.deepEqual(db.migrate({ migrations, to: "001_posts" }), { applied: [], rolledBack: ["002_title"] });
+});
+
+test.serial("Database.syncStatus()", async (t) => {
+  const db = t.context.db;
+
+  t.deepEqual(db.syncStatus(), {
+    syncing: false,
+    lastSyncTime: null,
+    lastError: null,
+    replicationIndex: null,
+    framesSynced: 0,
+    periodicSync: false,
+  });
+});
+
+test.serial("Database.setSyncPeriod() [local database]", async (t) => {
+  const db = t.context.db;
+
+  t.throws(() => db.setSyncPeriod(1), {
+    message: "Periodic sync is only supported by embedded replicas",
+  });
+  t.throws(() => db.pauseSync(), {
+    message: "Periodic sync is only supported by embedded replicas",
+  });
+  t.throws(() => db.resumeSync(), {
+    message: "Periodic sync is only supported by embedded replicas",
+  });
+  t.false(db.syncStatus().periodicSync);
+});
+
+test.serial("Database sync events [unreachable primary]", async (t) => {
+  t.timeout(30000);
+  const path = "sync-events-test.db";
+  const [db] = await connect(path, { syncUrl: "http://localhost:1", syncPeriod: 0.05 });
+  t.true(db.syncStatus().periodicSync);
+
+  const synced = [];
+  db.on("sync", (stats) => synced.push(stats));
+  const err = await new Promise((resolve) => db.once("syncError", resolve));
+  t.true(err instanceof Error);
+  t.not(db.syncStatus().lastError, null);
+  t.deepEqual(synced, []);
+
+  db.pauseSync();
+  t.false(db.syncStatus().periodicSync);
+  db.resumeSync();
+  t.true(db.syncStatus().periodicSync);
+  db.setSyncPeriod(0);
+  t.false(db.syncStatus().periodicSync);
+
+  db.close();
+  for (const file of fs.readdirSync(".")) {
+    if (file.startsWith(path)) fs.rmSync(file, { recursive: true });
+  }
+});
+
+test.serial("Database.push() and Database.pull() [local database]", async (t) => {
+  const db = t.context.db;
+
+  t.throws(() => db.push(), {
+    message: "push() requires a database opened with the offline option",
+  });
+  t.throws(() => db.pull(), {
+    message: "pull() requires an embedded replica or a database opened with the offline option",
+  });
+});
+
+test.serial("Database.push() and Database.pull() [offline database]", async (t) => {
+  const path = "push-pull-test.db";
+  const [local] = await connect(path);
+  local.exec("CREATE TABLE t (id INTEGER)");
+  local.close();
+  // The primary is unreachable, so these only pass without contacting it.
+  const [db] = await connect(path, { syncUrl: "http://localhost:1", offline: true });
+
+  const pushed = db.push();
+  t.is(pushed.pushed, 0);
+  t.is(pushed.pendingFrames, 0);
+
+  db.exec("INSERT INTO t VALUES (1)");
+  const { frames } = db.pendingChanges();
+  t.true(frames > 0);
+  t.deepEqual(db.pull(), { pulled: 0, frameNo: null, pendingFrames: frames });
+  // Nothing was pushed.
+  t.is(db.pendingChanges().frames, frames);
+
+  db.close();
+  for (const file of fs.readdirSync(".")) {
+    if (file.startsWith(path)) fs.rmSync(file, { recursive: true });
+  }
+});
+
+test.serial("Database.sync() [onConflict]", async (t) => {
+  const db = t.context.db;
+
+  t.throws(() => db.sync({ onConflict: "pull-then-retry" }), {
+    instanceOf: TypeError,
+    message: /^The pull-then-retry conflict policy is not supported/,
+  });
+  t.throws(() => db.sync({ onConflict: "retry" }), {
+    instanceOf: TypeError,
+    message: "Unsupported conflict policy: retry",
+  });
+  t.throws(() => db.sync({ onConflict: "overwrite" }), {
+    instanceOf: TypeError,
+    message: "Unsupported conflict policy: overwrite",
+  });
+});
+
+test.serial("Database.pendingChanges() [local database]", async (t) => {
+  const db = t.context.db;
+
+  t.deepEqual(db.pendingChanges(), {
+    frames: 0,
+    transactions: 0,
+    bytes: 0,
+    oldestUnpushedTime: null,
+  });
+});
+
+test.serial("Database.pendingChanges() [offline database]", async (t) => {
+  const path = "pending-changes-test.db";
+  const csv = "pending-changes-test.csv";
+  const options = { syncUrl: "http://localhost:1", offline: true };
+  const removeFiles = () => {
+    for (const file of fs.readdirSync(".")) {
+      if (file.startsWith(path)) fs.rmSync(file, { recursive: true });
+    }
+  };
+  fs.writeFileSync(csv, "id,name\n1,Alice\n2,Bob\n");
+  const writes = {
+    exec: (db) => db.exec("INSERT INTO t VALUES (1, 'Alice')"),
+    run: (db) => db.prepare("INSERT INTO t VALUES (?, ?)").run(1, "Alice"),
+    runMany: (db) => db.prepare("INSERT INTO t VALUES (?, ?)").runMany([[1, "Alice"], [2, "Bob"]]),
+    importFile: (db) => db.importFile("t", csv, { format: "csv" }),
+    migrate: (db) => db.migrate({ migrations: [{ id: "001_u", up: "CREATE TABLE u (id INTEGER)" }] }),
+    // Creating the migrations table changes no rows but is a write too.
+    "migrate without migrations": (db) => db.migrate({ migrations: [] }),
+  };
+
+  for (const [name, write] of Object.entries(writes)) {
+    // Closing the local database checkpoints the table into the database
+    // file, so there is nothing to push when it's reopened offline.
+    const [local] = await connect(path);
+    local.exec("CREATE TABLE t (id INTEGER, name TEXT)");
+    local.close();
+    const [db] = await connect(path, options);
+    t.is(db.pendingChanges().oldestUnpushedTime, null, name);
+
+    write(db);
+    const written = db.pendingChanges();
+    t.true(written.frames > 0, name);
+    t.true(written.transactions > 0, name);
+    t.not(written.oldestUnpushedTime, null, name);
+
+    // Later writes don't move the time of the oldest one.
+    await new Promise((resolve) => setTimeout(resolve, 5));
+    db.exec("INSERT INTO t VALUES (3, 'Carol')");
+    const later = db.pendingChanges();
+    t.true(later.frames > written.frames, name);
+    t.is(later.oldestUnpushedTime, written.oldestUnpushedTime, name);
+    db.close();
+
+    // The time survives reopening the database.
+    const [reopened] = await connect(path, options);
+    t.is(reopened.pendingChanges().oldestUnpushedTime, written.oldestUnpushedTime, name);
+    reopened.close();
+    removeFiles();
+  }
+  fs.rmSync(csv);
+});
+
+test.serial("new Database() [bootstrapFrom]", async (t) => {
+  const path = "bootstrap-test.db";
+  await t.throwsAsync(
+    connect(path, { syncUrl: "http://localhost:1", bootstrapFrom: "missing-snapshot.db" }),
+    { message: /^Failed to bootstrap the replica from missing-snapshot.db/ },
+  );
+  t.false(fs.existsSync(path));
+});
+
+test.serial("Statement.consistency()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT name FROM users WHERE id = ?");
+  t.is(stmt.consistency({}), stmt);
+  t.deepEqual(stmt.get(1), { name: "Alice" });
+  stmt.consistency({ minReplicationIndex: 1, timeoutMs: -1 });
+  t.throws(() => stmt.get(1), {
+    instanceOf: RangeError,
+    message: "timeoutMs must be a non-negative number",
+  });
+  t.throws(() => db.syncUntil(1, { timeoutMs: -1 }), { instanceOf: RangeError });
+  stmt.consistency({});
+  t.deepEqual(stmt.get(1), { name: "Alice" });
+});
+
+test.serial("Database.syncUntil() [unresponsive primary]", async (t) => {
+  const path = "sync-until-test.db";
+  // The server accepts connections but never answers, so syncs hang until
+  // they time out.
+  const sockets = [];
+  const server = net.createServer((socket) => sockets.push(socket));
+  await new Promise((resolve) => server.listen(0, "127.0.0.1", resolve));
+  const [db] = await connect(path, { syncUrl: `http://127.0.0.1:${server.address().port}` });
+
+  const err = t.throws(() => db.syncUntil(1, { timeoutMs: 100 }), {
+    message: "Timed out after 100 ms waiting for replication index 1",
+  });
+  t.is(err.code, "SYNC_TIMEOUT");
+  // The timeout only stops the wait; the sync keeps running until the
+  // primary answers or the connection fails.
+  const status = db.syncStatus();
+  t.true(status.syncing);
+  t.is(status.lastError, err.message);
+  t.is(status.replicationIndex, null);
+
+  const stmt = db.prepare("SELECT 1 AS x");
+  stmt.consistency({ minReplicationIndex: 1, timeoutMs: 100 });
+  t.throws(() => stmt.get(), { code: "SYNC_TIMEOUT" });
+  t.throws(() => [...stmt.iterate()], { code: "SYNC_TIMEOUT" });
+  stmt.consistency({});
+  t.deepEqual(stmt.get(), { x: 1 });
+
+  db.close();
+  for (const socket of sockets) socket.destroy();
+  server.close();
+  for (const file of fs.readdirSync(".")) {
+    if (file.startsWith(path)) fs.rmSync(file, { recursive: true });
+  }
+});
+
+test.serial("Statement.run() [replicationIndex]", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("INSERT INTO users(name, email) VALUES (?, ?)");
+  const info = stmt.run("Carol", "carol@example.net");
+  t.is(info.changes, 1);
+  t.false("replicationIndex" in info);
+
+  // Only embedded replicas delegate writes to the primary.
+  const batch = stmt.runMany([["Dave", "dave@example.net"]]);
+  t.false("replicationIndex" in batch);
+  const insert = db.transaction((name, email) => stmt.run(name, email));
+  t.is(insert.replicationIndex, undefined);
+  insert("Erin", "erin@example.net");
+  t.is(insert.replicationIndex, undefined);
+  t.is(insert.immediate.replicationIndex, undefined);
+});
+
+test.serial("Statement.runMany() [rows]", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("INSERT INTO users(name, email) VALUES (?, ?)");
+  const info = stmt.runMany([
+    ["Carol", "carol@example.net"],
+    ["Dave", "dave@example.net"],
+  ]);
+  t.is(info.changes, 2);
+  t.is(info.lastInsertRowid, 4);
+  t.is(db.prepare("SELECT name FROM users WHERE id = 4").get().name, "Dave");
+});
+
+test.serial("Statement.runMany() [columnar]", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      DROP TABLE IF EXISTS t;
+      CREATE TABLE t (x INTEGER, y REAL);
+  `);
+  const stmt = db.prepare("INSERT INTO t (x, y) VALUES (:x, :y)");
+  const info = stmt.runMany({ x: [1, 2, 3], y: new Float64Array([0.5, 1.5, 2.5]) });
+  t.is(info.changes, 3);
+  t.deepEqual(db.prepare("SELECT SUM(x), SUM(y) FROM t").raw().get(), [6, 4.5]);
+});
+
+test.serial("Statement.runMany() rolls back on error", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("INSERT INTO users(id, name, email) VALUES (?, ?, ?)");
+  t.throws(() => {
+    stmt.runMany([
+      [3, "Carol", "carol@example.net"],
+      [1, "Alice", "alice@example.org"],
+    ]);
+  }, {
+    instanceOf: t.context.errorType,
+    message: "UNIQUE constraint failed: users.id"
+  });
+  t.is(db.prepare("SELECT * FROM users WHERE id = 3").get(), undefined);
+  t.is(db.inTransaction, false);
+});
+
+test.serial("Statement.runMany() rolls back when the commit fails", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      PRAGMA foreign_keys = ON;
+      DROP TABLE IF EXISTS posts;
+      CREATE TABLE posts (user_id INTEGER REFERENCES users(id) DEFERRABLE INITIALLY DEFERRED);
+  `);
+  // Deferred foreign keys are checked by the commit, which leaves the
+  // transaction open when it fails.
+  const stmt = db.prepare("INSERT INTO posts (user_id) VALUES (?)");
+  t.throws(() => stmt.runMany([[1], [42]]), {
+    instanceOf: t.context.errorType,
+    message: "FOREIGN KEY constraint failed",
+  });
+  t.is(db.inTransaction, false);
+  t.deepEqual(db.prepare("SELECT COUNT(*) FROM posts").raw().get(), [0]);
+  db.exec("DROP TABLE posts; PRAGMA foreign_keys = OFF");
+});
+
+test.serial("Statement.runMany() binds values like Statement.run()", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      DROP TABLE IF EXISTS t;
+      CREATE TABLE t (x);
+  `);
+  db.prepare("INSERT INTO t (x) VALUES (?)").run(1);
+  db.prepare("INSERT INTO t (x) VALUES (?)").runMany([[2], [2.5]]);
+  const stmt = db.prepare("INSERT INTO t (x) VALUES (:x)");
+  // Getters may use the statement while the batch is converted.
+  stmt.runMany({
+    get x() {
+      t.is(stmt.columns().length, 0);
+      return [3];
+    },
+  });
+  t.deepEqual(db.prepare("SELECT typeof(x) FROM t").raw().all().flat(), ["real", "real", "real", "real"]);
+
+  // Integer typed arrays bind as integers.
+  db.exec("DELETE FROM t");
+  stmt.runMany({ x: new Int32Array([1, -2]) });
+  stmt.runMany({ x: new BigInt64Array([3n]) });
+  stmt.runMany({ x: new Float64Array([4]) });
+  t.deepEqual(db.prepare("SELECT x, typeof(x) FROM t").raw().all(), [
+    [1, "integer"],
+    [-2, "integer"],
+    [3, "integer"],
+    [4, "real"],
+  ]);
+});
+
+test.serial("Statement.columnar()", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      DROP TABLE IF EXISTS t;
+      CREATE TABLE t (x INTEGER, y REAL, z TEXT);
+      INSERT INTO t VALUES (1, 0.5, 'a'), (NULL, 1.5, NULL), (3, NULL, 'c');
+  `);
+  const result = db.prepare("SELECT x, y, z FROM t").columnar();
+  t.is(result.length, 3);
+  t.deepEqual(result.columns.x, new Float64Array([1, 0, 3]));
+  t.deepEqual(result.columns.y, new Float64Array([0.5, 1.5, 0]));
+  t.deepEqual(result.columns.z, ["a", null, "c"]);
+  t.deepEqual(result.nulls.x, new Uint8Array([0b010]));
+  t.deepEqual(result.nulls.y, new Uint8Array([0b100]));
+
+  const safe = db.prepare("SELECT x FROM t WHERE x IS NOT NULL").safeIntegers().columnar();
+  t.deepEqual(safe.columns.x, new BigInt64Array([1n, 3n]));
+});
+
+test.serial("Statement.columnar() [duplicate column names]", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT a.id, b.id FROM users a JOIN users b ON a.id = b.id");
+  t.throws(() => stmt.columnar(), {
+    message: "Duplicate column name in columnar result: id; use aliases to make column names unique",
+  });
+  const result = db.prepare("SELECT a.id AS a_id, b.id AS b_id FROM users a JOIN users b ON a.id = b.id").columnar();
+  t.deepEqual(Object.keys(result.columns), ["a_id", "b_id"]);
+});
+
+test.serial("Statement.arrow()", async (t) => {
+  const db = t.context.db;
+
+  const buf = db.prepare("SELECT * FROM users").arrow();
+  t.true(Buffer.isBuffer(buf));
+  const table = tableFromIPC(buf);
+  t.deepEqual(
+    table.schema.fields.map((field) => [field.name, String(field.type)]),
+    [["id", "Int64"], ["name", "Utf8"], ["email", "Utf8"]]
+  );
+  t.deepEqual(table.getChild("id").toArray(), new BigInt64Array([1n, 2n]));
+  t.deepEqual(table.getChild("name").toArray(), ["Alice", "Bob"]);
+  t.deepEqual(table.getChild("email").toArray(), ["alice@example.org", "bob@example.com"]);
+
+  const empty = tableFromIPC(db.prepare("SELECT * FROM users WHERE id > ?").arrow(2));
+  t.is(empty.numRows, 0);
+  t.is(empty.schema.fields.length, 3);
+});
+
+test.serial("Statement.arrow() [column types]", async (t) => {
+  const db = t.context.db;
+
+  db.exec("CREATE TABLE IF NOT EXISTS values_t (i INTEGER, r REAL, b BLOB, x)");
+  db.exec("DELETE FROM values_t");
+  db.prepare("INSERT INTO values_t VALUES (?, ?, ?, ?)").run(1, 1.5, Buffer.from([1, 2]), "a");
+  db.prepare("INSERT INTO values_t VALUES (?, ?, ?, ?)").run(null, 2, null, 3);
+  const table = tableFromIPC(db.prepare("SELECT * FROM values_t").arrow());
+  t.deepEqual(
+    table.schema.fields.map((field) => String(field.type)),
+    ["Int64", "Float64", "Binary", "Utf8"]
+  );
+  t.is(table.getChild("i").get(0), 1n);
+  t.is(table.getChild("i").get(1), null);
+  t.deepEqual(table.getChild("r").toArray(), new Float64Array([1.5, 2]));
+  t.deepEqual(Buffer.from(table.getChild("b").get(0)), Buffer.from([1, 2]));
+  t.deepEqual(table.getChild("x").toArray(), ["a", "3"]);
+
+  db.exec("INSERT INTO values_t (i) VALUES ('not a number')");
+  t.throws(() => db.prepare("SELECT i FROM values_t").arrow(), {
+    message: /Cannot encode text value of column "i" as Int64/,
+  });
+  db.exec("DROP TABLE values_t");
+});
+
+test.serial("Statement.arrowBatches()", async (t) => {
+  const db = t.context.db;
+
+  db.exec("CREATE TABLE IF NOT EXISTS numbers (n INTEGER)");
+  db.exec("DELETE FROM numbers");
+  db.exec("WITH RECURSIVE s(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM s WHERE n < 10000) INSERT INTO numbers SELECT n FROM s");
+  const stmt = db.prepare("SELECT n FROM numbers ORDER BY n");
+  const chunks = [...stmt.arrowBatches()];
+  // One batch of 8192 rows, one of the remaining 1808, then the end of the stream.
+  t.is(chunks.length, 2);
+  t.true(chunks.every((chunk) => Buffer.isBuffer(chunk)));
+  const table = tableFromIPC(Buffer.concat(chunks));
+  t.is(table.numRows, 10000);
+  t.is(table.batches.length, 2);
+  t.is(table.getChild("n").get(9999), 10000n);
+  t.deepEqual(Buffer.concat(chunks), stmt.arrow());
+
+  // Closing the iterator early ends the query.
+  for (const chunk of stmt.arrowBatches()) {
+    t.is(tableFromIPC(chunk).numRows, 8192);
+    break;
+  }
+  t.is(stmt.stats().executions, 3);
+  db.exec("DROP TABLE numbers");
+});
+
+test.serial("Database.exportQuery() and Database.importFile() [csv]", async (t) => {
+  const db = t.context.db;
+
+  const path = "users-export.csv";
+  t.is(db.exportQuery("SELECT * FROM users WHERE id > ?", [0], { format: "csv", path }), 2);
+  t.is(fs.readFileSync(path, "utf8"), "id,name,email\n1,Alice,alice@example.org\n2,Bob,bob@example.com\n");
+
+  db.exec("DELETE FROM users");
+  t.is(db.importFile("users", path, { format: "csv" }), 2);
+  t.deepEqual(db.prepare("SELECT * FROM users").all(), [
+    { id: 1, name: "Alice", email: "alice@example.org" },
+    { id: 2, name: "Bob", email: "bob@example.com" },
+  ]);
+  fs.unlinkSync(path);
+});
+
+test.serial("Database.exportQuery() and Database.importFile() [ndjson]", async (t) => {
+  const db = t.context.db;
+
+  const path = "users-export.ndjson";
+  t.is(db.exportQuery("SELECT * FROM users", [], { format: "ndjson", path }), 2);
+  const lines = fs.readFileSync(path, "utf8").trim().split("\n").map((line) => JSON.parse(line));
+  t.deepEqual(lines, [
+    { id: 1, name: "Alice", email: "alice@example.org" },
+    { id: 2, name: "Bob", email: "bob@example.com" },
+  ]);
+
+  db.exec("DELETE FROM users");
+  t.is(db.importFile("users", path, { format: "ndjson", columns: ["name", "email"], batchSize: 1 }), 2);
+  t.deepEqual(db.prepare("SELECT name FROM users").raw().all(), [["Alice"], ["Bob"]]);
+  fs.unlinkSync(path);
+});
+
+test.serial("Database.exportQuery() and Database.importFile() [round trip]", async (t) => {
+  const db = t.context.db;
+
+  db.exec("DROP TABLE IF EXISTS things");
+  db.exec("CREATE TABLE things (a TEXT, b BLOB, c REAL)");
+  const insert = db.prepare("INSERT INTO things VALUES (?, ?, ?)");
+  insert.run(null, Buffer.from([0, 1, 255]), 1.5);
+  insert.run("", Buffer.alloc(0), null);
+  insert.run('comma, "quote"\nnewline', null, -2);
+  const rows = db.prepare("SELECT * FROM things").all();
+
+  for (const format of ["csv", "ndjson"]) {
+    const path = `things-export.${format}`;
+    t.is(db.exportQuery("SELECT * FROM things", [], { format, path }), 3);
+    db.exec("DELETE FROM things");
+    t.is(db.importFile("things", path, { format }), 3);
+    t.deepEqual(db.prepare("SELECT * FROM things").all(), rows, format);
+    fs.unlinkSync(path);
+  }
+  db.exec("DROP TABLE things");
+});
+
+test.serial("Database.exportQuery() and Database.importFile() [untyped columns]", async (t) => {
+  const db = t.context.db;
+
+  db.exec("DROP TABLE IF EXISTS things");
+  db.exec("CREATE TABLE things (x)");
+  db.exec("INSERT INTO things VALUES (1), (1.0), (-2.5), ('x'), ('007'), (NULL)");
+  const rows = db.prepare("SELECT x, typeof(x) AS type FROM things").all();
+
+  const path = "things-export.csv";
+  t.is(db.exportQuery("SELECT * FROM things", [], { path }), 6);
+  db.exec("DELETE FROM things");
+  t.is(db.importFile("things", path), 6);
+  t.deepEqual(db.prepare("SELECT x, typeof(x) AS type FROM things").all(), rows);
+  fs.unlinkSync(path);
+  db.exec("DROP TABLE things");
+});
+
+test.serial("Database.importFile() [csv columns]", async (t) => {
+  const db = t.context.db;
+
+  const path = "users-import.csv";
+  fs.writeFileSync(path, "email,ignored,name\ncarol@example.net,x,Carol\n");
+  db.exec("DELETE FROM users");
+  t.is(db.importFile("users", path, { columns: ["name", "email"] }), 1);
+  t.deepEqual(db.prepare("SELECT name, email FROM users").all(), [{ name: "Carol", email: "carol@example.net" }]);
+  t.throws(() => db.importFile("users", path, { columns: ["name", "phone"] }), {
+    message: "Column phone not found in the CSV header",
+  });
+  fs.unlinkSync(path);
+});
+
+test.serial("Database.importFile() inserts all rows or none", async (t) => {
+  const db = t.context.db;
+
+  const path = "users-import.csv";
+  fs.writeFileSync(path, "id,name,email\n3,Carol,carol@example.net\n4,Dave,dave@example.net\n1,Alice,alice@example.org\n");
+  t.throws(() => db.importFile("users", path, { batchSize: 1 }), { message: /UNIQUE constraint failed/ });
+  t.is(db.prepare("SELECT COUNT(*) AS n FROM users").get().n, 2);
+  t.false(db.inTransaction);
+
+  // Inside a transaction, a failed import doesn't roll back the transaction.
+  db.exec("BEGIN");
+  db.exec("INSERT INTO users (id, name, email) VALUES (5, 'Eve', 'eve@example.net')");
+  t.throws(() => db.importFile("users", path, { batchSize: 1 }), { message: /UNIQUE constraint failed/ });
+  t.true(db.inTransaction);
+  db.exec("COMMIT");
+  t.deepEqual(db.prepare("SELECT id FROM users").raw().all(), [[1], [2], [5]]);
+  fs.unlinkSync(path);
+});
+
+test.serial("Database.exportQuery() finalizes its statement", async (t) => {
+  const [db] = await connect("hello.db", { statementCacheSize: 2 });
+
+  const path = "users-export.csv";
+  db.exportQuery("SELECT * FROM users", [], { path });
+  db.exportQuery("SELECT * FROM users", [], { path });
+  t.deepEqual(db.statementCacheStats(), { hits: 1, misses: 1, size: 1, capacity: 2 });
+  fs.unlinkSync(path);
+  db.close();
+});
+
+test.serial("Database.statementCacheStats()", async (t) => {
+  const [db] = await connect("hello.db", { statementCacheSize: 2 });
+
+  const sql = "SELECT name FROM users WHERE id = ?";
+  const a = db.prepare(sql);
+  t.is(a.get(1).name, "Alice");
+  a.finalize();
+  t.deepEqual(db.statementCacheStats(), { hits: 0, misses: 1, size: 1, capacity: 2 });
+  const b = db.prepare(sql);
+  t.is(b.get(2).name, "Bob");
+  t.deepEqual(db.statementCacheStats(), { hits: 1, misses: 1, size: 0, capacity: 2 });
+
+  // Live statements with the same SQL don't share a prepared statement.
+  const c = db.prepare(sql);
+  const it = b.iterate(1);
+  t.is(c.get(2).name, "Bob");
+  t.is(it.next().value.name, "Alice");
+  t.is(c.get(1).name, "Alice");
+  t.true(it.next().done);
+  t.is(b.get(2).name, "Bob");
+  t.deepEqual(db.statementCacheStats(), { hits: 1, misses: 2, size: 0, capacity: 2 });
+
+  // A statement that is still being iterated is not returned to the cache.
+  const it2 = c.iterate(1);
+  c.finalize();
+  t.is(db.statementCacheStats().size, 0);
+  it2.return();
+  b.finalize();
+  t.is(db.statementCacheStats().size, 1);
+
+  db.prepare("SELECT 1").finalize();
+  db.prepare("SELECT 2").finalize();
+  t.is(db.statementCacheStats().size, 2);
+  db.close();
+});
+
+test.serial("Statement.finalize()", async (t) => {
+  const db = t.context.db;
+
+  const stmt = db.prepare("SELECT * FROM users");
+  stmt.finalize();
+  t.throws(() => stmt.all(), {
+    instanceOf: TypeError,
+    message: "The statement has been finalized",
+  });
+  t.is(db.prepare("SELECT COUNT(*) AS n FROM users").get().n, 2);
+});
+
+test.serial("Database.close() finalizes statements and iterators", async (t) => {
+  const path = "close-test.db";
+  const [db] = await connect(path, { statementCacheSize: 1 });
+  db.exec("DROP TABLE IF EXISTS t; CREATE TABLE t (x)");
+  db.exec("INSERT INTO t VALUES (1), (2)");
+  db.prepare("SELECT x FROM t WHERE x = 1").finalize();
+  const stmt = db.prepare("SELECT x FROM t");
+  const it = stmt.iterate();
+  const file = fs.realpathSync(path);
+  if (process.platform === "linux") {
+    t.true(openFiles(file) > 0);
+  }
+  db.close();
+  t.throws(() => it.next(), {
+    instanceOf: TypeError,
+    message: "The database connection is not open",
+  });
+  t.throws(() => stmt.all(), {
+    instanceOf: TypeError,
+    message: "The database connection is not open",
+  });
+  if (process.platform === "linux") {
+    // Every handle, including the cached statement's, is released, which
+    // closes the database file and its WAL.
+    t.is(openFiles(file), 0);
+  }
+  fs.unlinkSync(path);
+});
+
+// Returns the number of file descriptors of this process that refer to the
+// database file at `file` or its WAL and shared memory files.
+const openFiles = (file) =>
+  fs.readdirSync("/proc/self/fd").filter((fd) => {
+    try {
+      return fs.readlinkSync(`/proc/self/fd/${fd}`).startsWith(file);
+    } catch {
+      return false;
+    }
+  }).length;
+
+test.serial("Use after Database.close()", async (t) => {
+  const db = t.context.db;
+  const stmt = db.prepare("SELECT * FROM users WHERE id = ?");
+  const insert = db.prepare("INSERT INTO users (name, email) VALUES (?, ?)");
+  const it = db.prepare("SELECT * FROM users").iterate();
+  db.close();
+
+  const uses = {
+    "Database.inTransaction": () => db.inTransaction,
+    "Database.prepare()": () => db.prepare("SELECT 1"),
+    "Database.exec()": () => db.exec("SELECT 1"),
+    "Database.interrupt()": () => db.interrupt(),
+    "Database.sync()": () => db.sync(),
+    "Database.syncUntil()": () => db.syncUntil(1),
+    "Database.loadExtension()": () => db.loadExtension("ext"),
+    "Database.maxWriteReplicationIndex()": () => db.maxWriteReplicationIndex(),
+    "Database.exportQuery()": () => db.exportQuery("SELECT 1", [], { path: "closed.csv" }),
+    "Database.importFile()": () => db.importFile("users", "closed.csv"),
+    "Statement.raw()": () => stmt.raw(),
+    "Statement.reader": () => stmt.reader,
+    "Statement.run()": () => insert.run("Carol", "carol@example.net"),
+    "Statement.runMany()": () => insert.runMany([["Carol", "carol@example.net"]]),
+    "Statement.get()": () => stmt.get(1),
+    "Statement.iterate()": () => stmt.iterate(1),
+    "Statement.all()": () => stmt.all(1),
+    "Statement.columnar()": () => stmt.columnar(1),
+    "Statement.arrow()": () => stmt.arrow(1),
+    "Statement.arrowBatches()": () => stmt.arrowBatches(1),
+    "Statement.columns()": () => stmt.columns(),
+    "Statement.safeIntegers()": () => stmt.safeIntegers(),
+    "iterator.next()": () => it.next(),
+  };
+  for (const [name, use] of Object.entries(uses)) {
+    t.throws(use, {
+      instanceOf: TypeError,
+      message: "The database connection is not open",
+    }, name);
+  }
+});
+
+test.serial("Statement.run() binds booleans as integers", async (t) => {
+  const db = t.context.db;
+
+  db.exec("CREATE TABLE IF NOT EXISTS flags (flag)");
+  db.exec("DELETE FROM flags");
+  db.prepare("INSERT INTO flags VALUES (?)").run(true);
+  db.prepare("INSERT INTO flags VALUES (?)").runMany([[false]]);
+  t.deepEqual(db.prepare("SELECT flag, typeof(flag) AS type FROM flags").all(), [
+    { flag: 1, type: "integer" },
+    { flag: 0, type: "integer" },
+  ]);
+  t.is(db.prepare("SELECT ? AS flag").get(true).flag, 1);
+  db.exec("DROP TABLE flags");
+});
+
+test.serial("Statement.get() with object bind parameters requires named parameters", async (t) => {
+  const db = t.context.db;
+  const stmt = db.prepare("SELECT * FROM users WHERE id = ?");
+  t.throws(() => stmt.get({ id: 1 }), {
+    instanceOf: TypeError,
+    message: "Object bind parameters require named parameters",
+  });
+});
+
+test.serial("Iterator return() releases rows", async (t) => {
+  const db = t.context.db;
+  const [other] = await connect();
+  // In rollback journal mode, a read transaction keeps other connections
+  // from committing writes.
+  db.exec("PRAGMA journal_mode = DELETE");
+
+  const it = db.prepare("SELECT * FROM users").iterate();
+  t.is(it.next().value.name, "Alice");
+  t.throws(() => other.exec("DELETE FROM users"), { code: "SQLITE_BUSY" });
+  t.deepEqual(it.return(), { done: true });
+  t.deepEqual(it.next(), { done: true });
+
+  let names = [];
+  for (const row of db.prepare("SELECT * FROM users").iterate()) {
+    names.push(row.name);
+    break;
+  }
+  t.deepEqual(names, ["Alice"]);
+  // The abandoned iterators no longer hold a read transaction.
+  other.exec("DELETE FROM users");
+  t.is(db.prepare("SELECT COUNT(*) AS n FROM users").get().n, 0);
+  other.close();
+});
+
+test.serial("Symbol.dispose", async (t) => {
+  const dispose = Symbol.dispose ?? Symbol.for("nodejs.dispose");
+  const [db] = await connect();
+
+  const stmt = db.prepare("SELECT * FROM users");
+  const it = stmt.iterate();
+  it[dispose]();
+  t.deepEqual(it.next(), { done: true });
+  stmt[dispose]();
+  t.throws(() => stmt.all(), { instanceOf: TypeError });
+  db[dispose]();
+  t.false(db.open);
+  t.throws(() => db.exec("SELECT 1"), {
+    instanceOf: TypeError,
+    message: "The database connection is not open",
+  });
+});
+
+test.serial("configure() and runtimeMetrics()", async (t) => {
+  const x = await import("libsql");
+
+  // The runtime was started when the test database was opened.
+  t.throws(() => x.default.configure({ workerThreads: 1 }), {
+    message: "configure() must be called before the first database is opened",
+  });
+  const metrics = x.default.runtimeMetrics();
+  t.true(metrics.workers >= 1);
+  t.true(metrics.aliveTasks >= 0);
+  t.true(metrics.globalQueueDepth >= 0);
+});
+
+test.serial("setLogger()", async (t) => {
+  const x = await import("libsql");
+  const db = t.context.db;
+
+  const records = [];
+  x.default.setLogger({ level: "trace", callback: (record) => records.push(record) });
+  db.exec("SELECT 1");
+  await new Promise((resolve) => setTimeout(resolve, 100));
+  x.default.setLogger(null);
+
+  const record = records.find((record) => record.message.startsWith("Executing SQL statement"));
+  t.is(record.level, "trace");
+  t.is(record.target, "libsql_js::database");
+  t.is(record.message, "Executing SQL statement (sync): SELECT 1");
+  t.is(typeof record.time, "number");
+
+  const count = records.length;
+  db.exec("SELECT 1");
+  await new Promise((resolve) => setTimeout(resolve, 100));
+  t.is(records.length, count);
+});
+
+const connect = async (path_opt, options) => {
   const path = path_opt ?? "hello.db";
   const x = await import("libsql");
-  const db = new x.default(process.env.LIBSQL_DATABASE ?? path, {});
+  const db = new x.default(process.env.LIBSQL_DATABASE ?? path, options ?? {});
   return [db, x.SqliteError, "libsql"];
 };
 
@@ -167,6 +167,19 @@ test.serial("Statement.all() [raw]", async (t) => {
   t.deepEqual(stmt.raw().all(), expected);
 });
 
+test.serial("Statement.all() [blob]", async (t) => {
+  const db = t.context.db;
+
+  db.exec(`
+      DROP TABLE IF EXISTS t;
+      CREATE TABLE t (value BLOB);
+  `);
+  db.prepare("INSERT INTO t (value) VALUES (?)").run([Buffer.from("hello")]);
+
+  const stmt = db.prepare("SELECT value FROM t");
+  t.deepEqual(stmt.all(), [{ value: Buffer.from("hello") }]);
+});
+
 test.serial("Statement.all() [default safe integers]", async (t) => {
   const db = t.context.db;
   db.defaultSafeIntegers();
@@ -408,6 +421,18 @@ test.serial("Database.exec() after close()", async (t) => {
   });
 });
 
+test.serial("Statement.run() after close()", async (t) => {
+  const db = t.context.db;
+  const stmt = db.prepare("SELECT 1");
+  db.close();
+  t.throws(() => {
+    stmt.run();
+  }, {
+    instanceOf: TypeError,
+    message: "The database connection is not open"
+  });
+});
+
 const connect = async (path_opt) => {
   const path = path_opt ?? "hello.db";
   const provider = process.env.PROVIDER;
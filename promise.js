@@ -2,6 +2,9 @@
 
 const { load, currentTarget } = require("@neon-rs/load");
 const { familySync, GLIBC } = require("detect-libc");
+const fs = require("fs");
+const path = require("path");
+const { EventEmitter } = require("events");
 
 // Static requires for bundlers.
 if (0) {
@@ -10,6 +13,25 @@ if (0) {
 
 const SqliteError = require("./sqlite-error");
 
+// Fall back to the symbols Node.js uses internally on versions that don't
+// define them yet.
+const disposeSymbol = Symbol.dispose ?? Symbol.for("nodejs.dispose");
+const asyncDisposeSymbol = Symbol.asyncDispose ?? Symbol.for("nodejs.asyncDispose");
+
+/**
+ * Reads the migrations in a directory, where `<id>.sql` files hold the SQL that applies a migration and optional
+ * `<id>.down.sql` files the SQL that rolls it back. Migrations are ordered by their ID.
+ */
+async function readMigrations(dir) {
+  const files = (await fs.promises.readdir(dir)).filter((file) => file.endsWith(".sql")).sort();
+  const names = new Set(files);
+  return Promise.all(files.filter((file) => !file.endsWith(".down.sql")).map(async (file) => {
+    const id = file.slice(0, -".sql".length);
+    const down = names.has(`${id}.down.sql`) ? await fs.promises.readFile(path.join(dir, `${id}.down.sql`), "utf8") : undefined;
+    return { id, up: await fs.promises.readFile(path.join(dir, file), "utf8"), down };
+  }));
+}
+
 function convertError(err) {
   if (err.libsqlError) {
     return new SqliteError(err.message, err.code, err.rawCode);
@@ -17,6 +39,32 @@ function convertError(err) {
   return err;
 }
 
+/**
+ * Waits for the replica to reach the replication index set by `Statement.consistency()`.
+ */
+async function waitForReplicaAsync(stmt) {
+  if (stmt.minReplicationIndex != null) {
+    await databaseWaitForReplicationIndexAsync.call(stmt.database.db, stmt.minReplicationIndex, stmt.consistencyTimeoutMs);
+  }
+}
+
+/**
+ * Calls `fn` once the replica has reached the replication index set by `Statement.consistency()`.
+ *
+ * The wait doesn't block the event loop, so the result is a promise if the statement has a replication
+ * index to wait for. Otherwise, `fn` is called right away.
+ */
+function afterReplica(stmt, fn) {
+  if (stmt.minReplicationIndex == null) {
+    return fn();
+  }
+  return waitForReplicaAsync(stmt)
+    .then(fn)
+    .catch((err) => {
+      throw convertError(err);
+    });
+}
+
 function requireNative() {
   if (process.env.LIBSQL_JS_DEV) {
     return load(__dirname)
@@ -44,24 +92,57 @@ const {
   databaseClose,
   databaseSyncAsync,
   databaseSyncUntilAsync,
+  databaseWaitForReplicationIndexAsync,
+  databaseSyncStatus,
+  databasePendingChanges,
+  databasePushAsync,
+  databasePullAsync,
+  databaseSetSyncListener,
+  databasePauseSync,
+  databaseResumeSync,
+  databaseSetSyncPeriod,
   databaseExecAsync,
   databasePrepareAsync,
   databaseMaxWriteReplicationIndex,
+  databaseSetStatementCacheSize,
+  databaseSetSlowQueryLog,
+  databaseStatementCacheStats,
   databaseDefaultSafeIntegers,
+  databaseImportFileAsync,
+  databaseSchemaAsync,
+  databaseMigrateAsync,
   statementRaw,
   statementIsReader,
   statementGet,
   statementRun,
+  statementRunMany,
   statementRowsAsync,
+  statementAllAsync,
+  statementColumnar,
+  statementArrow,
+  statementArrowBatches,
+  statementExportAsync,
   statementColumns,
   statementSafeIntegers,
+  statementStats,
+  statementExplain,
+  statementFinalize,
   rowsNext,
+  rowsClose,
+  arrowBatchesNext,
+  arrowBatchesClose,
+  runtimeConfigure,
+  runtimeMetrics,
+  setLogger: setNativeLogger,
 } = requireNative();
 
 /**
  * Database represents a connection that can prepare and execute SQL statements.
+ *
+ * Embedded replicas emit a `sync` event with the sync stats after every periodic sync, and a `syncError` event with
+ * the error if a periodic sync fails.
  */
-class Database {
+class Database extends EventEmitter {
   /**
    * Creates a new database connection. If the database file pointed to by `path` does not exists, it will be created.
    *
@@ -69,6 +150,7 @@ class Database {
    * @param {string} path - Path to the database file.
    */
   constructor(path, opts) {
+    super();
     const encryptionCipher = opts?.encryptionCipher ?? "aes256cbc";
     if (opts && opts.syncUrl) {
       var authToken = "";
@@ -80,13 +162,28 @@ class Database {
       }
       const encryptionKey = opts?.encryptionKey ?? "";
       const syncPeriod = opts?.syncPeriod ?? 0.0;
+      const readYourWrites = opts?.readYourWrites ?? true;
       const offline = opts?.offline ?? false;
-      this.db = databaseOpenWithSync(path, opts.syncUrl, authToken, encryptionCipher, encryptionKey, syncPeriod, offline);
+      const bootstrapFrom = opts?.bootstrapFrom ?? "";
+      this.db = databaseOpenWithSync(path, opts.syncUrl, authToken, encryptionCipher, encryptionKey, syncPeriod, readYourWrites, offline, bootstrapFrom);
+      // The listener must not keep the database alive.
+      const ref = new WeakRef(this);
+      databaseSetSyncListener.call(this.db, (event, payload) => {
+        ref.deref()?.emit(event, event === "syncError" ? convertError(payload) : payload);
+      });
     } else {
       const authToken = opts?.authToken ?? "";
       const encryptionKey = opts?.encryptionKey ?? "";
       this.db = databaseOpen(path, authToken, encryptionCipher, encryptionKey);
     }
+    if (opts?.statementCacheSize) {
+      databaseSetStatementCacheSize.call(this.db, opts.statementCacheSize);
+    }
+    if (opts?.slowQueryThresholdMs !== undefined) {
+      if (typeof opts.onSlowQuery !== "function")
+        throw new TypeError("Expected options.onSlowQuery to be a function");
+      databaseSetSlowQueryLog.call(this.db, opts.slowQueryThresholdMs, opts.onSlowQuery);
+    }
     // TODO: Use a libSQL API for this?
     this.memory = path === ":memory:";
     this.readonly = false;
@@ -103,12 +200,79 @@ class Database {
     });
   }
 
-  sync() {
-    return databaseSyncAsync.call(this.db);
+  /**
+   * Synchronizes the embedded replica with the primary.
+   *
+   * @param {object} [options] - Options: the `onProgress` callback, which is called periodically while the sync runs,
+   * `changes`, whether to return the tables and rows changed by the sync, and `onConflict`, what to do when the
+   * primary rejects the local writes of an offline database.
+   */
+  sync(options) {
+    return databaseSyncAsync.call(this.db, options?.onProgress, options?.changes ?? false, options?.onConflict ?? "fail");
+  }
+
+  /**
+   * Pushes the local writes of a database opened with the `offline` option to the primary without pulling.
+   *
+   * @param {object} [options] - Options: `onConflict`, what to do when the primary rejects the local writes.
+   */
+  push(options) {
+    return databasePushAsync.call(this.db, options?.onConflict ?? "fail");
   }
 
-  syncUntil(replicationIndex) {
-    return databaseSyncUntilAsync.call(this.db, replicationIndex);
+  /**
+   * Pulls the frames of the primary into the embedded replica or offline database without pushing local writes.
+   * An offline database pulls nothing while it has local writes that have not been pushed.
+   */
+  pull() {
+    return databasePullAsync.call(this.db);
+  }
+
+  /**
+   * Returns the local writes of a database opened with the `offline` option that have not been pushed.
+   */
+  pendingChanges() {
+    return databasePendingChanges.call(this.db);
+  }
+
+  /**
+   * Returns the synchronization status of the embedded replica.
+   */
+  syncStatus() {
+    return databaseSyncStatus.call(this.db);
+  }
+
+  /**
+   * Pauses the periodic sync. A sync that is in progress runs to completion.
+   */
+  pauseSync() {
+    databasePauseSync.call(this.db);
+  }
+
+  /**
+   * Resumes the periodic sync paused by `pauseSync()`.
+   */
+  resumeSync() {
+    databaseResumeSync.call(this.db);
+  }
+
+  /**
+   * Changes the period of the periodic sync, or disables it if `seconds` is zero.
+   *
+   * @param {number} seconds - The period in seconds.
+   */
+  setSyncPeriod(seconds) {
+    databaseSetSyncPeriod.call(this.db, seconds);
+  }
+
+  /**
+   * Syncs the replica until it reaches a replication index.
+   *
+   * @param {number} replicationIndex - The replication index to wait for.
+   * @param {object} [options] - Options. Set `timeoutMs` to give up after that many milliseconds.
+   */
+  syncUntil(replicationIndex, options) {
+    return databaseSyncUntilAsync.call(this.db, replicationIndex, options?.timeoutMs);
   }
 
   /**
@@ -118,7 +282,7 @@ class Database {
    */
   prepare(sql) {
     return databasePrepareAsync.call(this.db, sql).then((stmt) => {
-      return new Statement(stmt);
+      return new Statement(stmt, this);
     }).catch((err) => {
       throw convertError(err);
     });
@@ -134,12 +298,16 @@ class Database {
       throw new TypeError("Expected first argument to be a function");
 
     const db = this;
+    let replicationIndex;
     const wrapTxn = (mode) => {
       return async (...bindParameters) => {
+        replicationIndex = undefined;
         await db.exec("BEGIN " + mode);
         try {
           const result = fn(...bindParameters);
-          await db.exec("COMMIT");
+          replicationIndex = await databaseExecAsync.call(db.db, "COMMIT").catch((err) => {
+            throw convertError(err);
+          });
           return result;
         } catch (err) {
           await db.exec("ROLLBACK");
@@ -153,6 +321,8 @@ class Database {
       immediate: { value: wrapTxn("IMMEDIATE") },
       exclusive: { value: wrapTxn("EXCLUSIVE") },
       database: { value: this, enumerable: true },
+      // The replication index of the last commit of an embedded replica.
+      replicationIndex: { get: () => replicationIndex, enumerable: true },
     };
     Object.defineProperties(properties.default.value, properties);
     Object.defineProperties(properties.deferred.value, properties);
@@ -236,6 +406,86 @@ class Database {
     return databaseMaxWriteReplicationIndex.call(this.db)
   }
 
+  /**
+   * Returns the hit and miss counters of the prepared statement cache.
+   */
+  statementCacheStats() {
+    return databaseStatementCacheStats.call(this.db);
+  }
+
+  /**
+   * Returns the query plan of a SQL statement.
+   *
+   * @param {string} sql - The SQL statement to explain.
+   * @param {object} [options] - Options. Set `bytecode` to `true` to also return the bytecode program.
+   */
+  async explain(sql, options) {
+    const stmt = await this.prepare(sql);
+    try {
+      return stmt.explain(options);
+    } finally {
+      stmt.finalize();
+    }
+  }
+
+  /**
+   * Executes a SQL query and writes the resulting rows to a file.
+   *
+   * @param {string} sql - The SQL query to execute.
+   * @param bindParameters - The bind parameters for executing the query.
+   * @param {object} options - Options: the `path` of the file to write and its `format` (`"csv"` or `"ndjson"`).
+   */
+  async exportQuery(sql, bindParameters, options) {
+    if (typeof options?.path !== "string")
+      throw new TypeError("Expected options.path to be a string");
+    const stmt = await this.prepare(sql);
+    try {
+      return await statementExportAsync.call(stmt.stmt, bindParameters ?? [], options.format ?? "csv", options.path);
+    } catch (err) {
+      throw convertError(err);
+    } finally {
+      stmt.finalize();
+    }
+  }
+
+  /**
+   * Inserts the records of a file into a table.
+   *
+   * @param {string} table - The table to insert into.
+   * @param {string} path - Path to the file to import.
+   * @param {object} [options] - Options: the file `format` (`"csv"` or `"ndjson"`), the `columns` to insert into, and the `batchSize` of each INSERT statement.
+   */
+  importFile(table, path, options) {
+    return databaseImportFileAsync.call(this.db, table, path, options?.format ?? "csv", options?.columns ?? [], options?.batchSize ?? 10000).catch((err) => {
+      throw convertError(err);
+    });
+  }
+
+  /**
+   * Applies the pending schema migrations, each in its own transaction, and records them in a migrations table.
+   *
+   * @param {object} options - Options: the `dir` of the migration files or the `migrations` as `{ id, up, down }` objects, the migrations `table`, and the migration to migrate `to`.
+   */
+  async migrate(options) {
+    const migrations = options?.dir !== undefined ? await readMigrations(options.dir) : options?.migrations;
+    if (!Array.isArray(migrations))
+      throw new TypeError("Expected options.dir or options.migrations");
+    try {
+      return await databaseMigrateAsync.call(this.db, migrations, options.table ?? "_libsql_migrations", options.to ?? "");
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Returns the tables, views and triggers of the main, temporary and attached schemas.
+   */
+  schema() {
+    return databaseSchemaAsync.call(this.db).catch((err) => {
+      throw convertError(err);
+    });
+  }
+
   /**
    * Executes a SQL statement.
    *
@@ -259,6 +509,15 @@ class Database {
    */
   close() {
     databaseClose.call(this.db);
+    this.open = false;
+  }
+
+  [disposeSymbol]() {
+    this.close();
+  }
+
+  async [asyncDisposeSymbol]() {
+    this.close();
   }
 
   /**
@@ -278,8 +537,9 @@ class Database {
  * Statement represents a prepared SQL statement that can be executed.
  */
 class Statement {
-  constructor(stmt) {
+  constructor(stmt, database) {
     this.stmt = stmt;
+    this.database = database;
   }
 
   /**
@@ -296,16 +556,46 @@ class Statement {
     return statementIsReader.call(this.stmt);
   }
 
+  /**
+   * Makes the statement wait for the replica to reach a replication index before every execution.
+   *
+   * @param {object} options - Options. `minReplicationIndex` is the replication index to wait for, usually one
+   * returned by `maxWriteReplicationIndex()`, and `timeoutMs` limits how long to wait.
+   */
+  consistency(options) {
+    this.minReplicationIndex = options?.minReplicationIndex;
+    this.consistencyTimeoutMs = options?.timeoutMs;
+    return this;
+  }
+
   /**
    * Executes the SQL statement and returns an info object.
    */
   run(...bindParameters) {
     try {
-      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
-        return statementRun.call(this.stmt, bindParameters[0]);
-      } else {
-        return statementRun.call(this.stmt, bindParameters.flat());
-      }
+      return afterReplica(this, () => {
+        if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+          return statementRun.call(this.stmt, bindParameters[0]);
+        } else {
+          return statementRun.call(this.stmt, bindParameters.flat());
+        }
+      });
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Executes the SQL statement once for every set of bind parameters and returns an info object.
+   *
+   * @param rows - An array of bind parameters, or an object that maps parameter names to columns of values.
+   * @param {object} [options] - Options. Set `transaction` to `false` to not wrap the batch in a transaction.
+   */
+  runMany(rows, options) {
+    try {
+      return afterReplica(this, () => {
+        return statementRunMany.call(this.stmt, rows, options?.transaction ?? true);
+      });
     } catch (err) {
       throw convertError(err);
     }
@@ -318,11 +608,13 @@ class Statement {
    */
   get(...bindParameters) {
     try {
-      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
-        return statementGet.call(this.stmt, bindParameters[0]);
-      } else {
-        return statementGet.call(this.stmt, bindParameters.flat());
-      }
+      return afterReplica(this, () => {
+        if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+          return statementGet.call(this.stmt, bindParameters[0]);
+        } else {
+          return statementGet.call(this.stmt, bindParameters.flat());
+        }
+      });
     } catch (e) {
       throw convertError(e);
     }
@@ -335,10 +627,15 @@ class Statement {
    */
   async iterate(...bindParameters) {
     var rows = undefined;
-    if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
-      rows = await statementRowsAsync.call(this.stmt, bindParameters[0]);
-    } else {
-      rows = await statementRowsAsync.call(this.stmt, bindParameters.flat());
+    try {
+      await waitForReplicaAsync(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        rows = await statementRowsAsync.call(this.stmt, bindParameters[0]);
+      } else {
+        rows = await statementRowsAsync.call(this.stmt, bindParameters.flat());
+      }
+    } catch (err) {
+      throw convertError(err);
     }
     const iter = {
       nextRows: Array(100),
@@ -361,9 +658,21 @@ class Statement {
           throw convertError(e);
         }
       },
+      return() {
+        rowsClose.call(rows);
+        this.nextRows.fill(null);
+        this.nextRowIndex = 0;
+        return { done: true };
+      },
       [Symbol.iterator]() {
         return this;
       },
+      [disposeSymbol]() {
+        this.return();
+      },
+      async [asyncDisposeSymbol]() {
+        this.return();
+      },
     };
     return iter;
   }
@@ -375,17 +684,102 @@ class Statement {
    */
   async all(...bindParameters) {
     try {
-      const result = [];
-      const it = await this.iterate(...bindParameters);
-      for (const row of it) {
-        result.push(row);
+      await waitForReplicaAsync(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        return await statementAllAsync.call(this.stmt, bindParameters[0]);
+      } else {
+        return await statementAllAsync.call(this.stmt, bindParameters.flat());
       }
-      return result;
     } catch (e) {
       throw convertError(e);
     }
   }
 
+  /**
+   * Executes the SQL statement and returns the result set in columnar form.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  columnar(...bindParameters) {
+    try {
+      return afterReplica(this, () => {
+        if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+          return statementColumnar.call(this.stmt, bindParameters[0]);
+        } else {
+          return statementColumnar.call(this.stmt, bindParameters.flat());
+        }
+      });
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Executes the SQL statement and returns the result set as an Apache Arrow IPC stream.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  arrow(...bindParameters) {
+    try {
+      return afterReplica(this, () => {
+        if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+          return statementArrow.call(this.stmt, bindParameters[0]);
+        } else {
+          return statementArrow.call(this.stmt, bindParameters.flat());
+        }
+      });
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Executes the SQL statement and returns an iterator to the result set as
+   * an Apache Arrow IPC stream, one record batch at a time.
+   *
+   * @param bindParameters - The bind parameters for executing the statement.
+   */
+  async arrowBatches(...bindParameters) {
+    var batches = undefined;
+    try {
+      await waitForReplicaAsync(this);
+      if (bindParameters.length == 1 && typeof bindParameters[0] === "object") {
+        batches = statementArrowBatches.call(this.stmt, bindParameters[0]);
+      } else {
+        batches = statementArrowBatches.call(this.stmt, bindParameters.flat());
+      }
+    } catch (err) {
+      throw convertError(err);
+    }
+    const iter = {
+      next() {
+        try {
+          const chunk = arrowBatchesNext.call(batches);
+          if (!chunk) {
+            return { done: true };
+          }
+          return { value: chunk, done: false };
+        } catch (err) {
+          throw convertError(err);
+        }
+      },
+      return() {
+        arrowBatchesClose.call(batches);
+        return { done: true };
+      },
+      [Symbol.iterator]() {
+        return this;
+      },
+      [disposeSymbol]() {
+        this.return();
+      },
+      async [asyncDisposeSymbol]() {
+        this.return();
+      },
+    };
+    return iter;
+  }
+
   /**
    * Returns the columns in the result set returned by this prepared statement.
    */
@@ -401,7 +795,69 @@ class Statement {
     return this;
   }
 
+  /**
+   * Returns the execution statistics of the statement.
+   */
+  stats() {
+    return statementStats.call(this.stmt);
+  }
+
+  /**
+   * Returns the query plan of the statement.
+   *
+   * @param {object} [options] - Options. Set `bytecode` to `true` to also return the bytecode program.
+   */
+  explain(options) {
+    try {
+      return statementExplain.call(this.stmt, options?.bytecode ?? false);
+    } catch (err) {
+      throw convertError(err);
+    }
+  }
+
+  /**
+   * Finalizes the prepared statement, releasing its resources. The statement cannot be used afterwards.
+   */
+  finalize() {
+    statementFinalize.call(this.stmt);
+  }
+
+  [disposeSymbol]() {
+    this.finalize();
+  }
+
+  async [asyncDisposeSymbol]() {
+    this.finalize();
+  }
+
+}
+
+/**
+ * Configures the runtime that executes database operations. This must be called before the first database is opened.
+ *
+ * @param {object} [options] - Options: the number of `workerThreads`, the `maxBlockingThreads`, the `threadName`, and whether to use a `currentThread` runtime, which runs all tasks on one background thread.
+ */
+function configure(options) {
+  runtimeConfigure(options?.workerThreads ?? 0, options?.maxBlockingThreads ?? 0, options?.threadName ?? "", options?.currentThread ?? false);
+}
+
+/**
+ * Forwards native log records to a JavaScript logger, or restores the default logging to standard output if `options` is `null`.
+ *
+ * @param {object|null} options - Options: the minimum `level` of the records (`"trace"`, `"debug"`, `"info"`, `"warn"` or `"error"`) and the `callback` that receives them.
+ */
+function setLogger(options) {
+  if (options == null) {
+    setNativeLogger("");
+    return;
+  }
+  if (typeof options.callback !== "function")
+    throw new TypeError("Expected options.callback to be a function");
+  setNativeLogger(options.level ?? "info", options.callback);
 }
 
 module.exports = Database;
 module.exports.SqliteError = SqliteError;
+module.exports.configure = configure;
+module.exports.runtimeMetrics = runtimeMetrics;
+module.exports.setLogger = setLogger;
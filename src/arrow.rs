@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BinaryArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+
+/// Number of rows encoded into each Arrow record batch.
+pub(crate) const BATCH_SIZE: usize = 8192;
+
+/// Encodes query results into an Arrow IPC stream one record batch at a time.
+///
+/// The schema is derived from the declared column types, following SQLite's
+/// type affinity rules, and falls back to the value types observed in the
+/// first batch for columns without a declared type. Integers are widened to
+/// floats and numbers to strings where the column type needs it; any other
+/// value that doesn't match the column type is an error.
+pub(crate) struct ArrowStream {
+    names: Vec<String>,
+    decl_types: Vec<Option<String>>,
+    writer: Option<(SchemaRef, StreamWriter<Vec<u8>>)>,
+}
+
+impl ArrowStream {
+    pub fn new(names: Vec<String>, decl_types: Vec<Option<String>>) -> Self {
+        ArrowStream {
+            names,
+            decl_types,
+            writer: None,
+        }
+    }
+
+    pub fn write_batch(&mut self, rows: &[Vec<libsql::Value>]) -> Result<(), ArrowError> {
+        if self.writer.is_none() {
+            let fields = self
+                .names
+                .iter()
+                .zip(self.decl_types.iter())
+                .enumerate()
+                .map(|(idx, (name, decl_type))| {
+                    let data_type = column_type(decl_type.as_deref(), rows, idx);
+                    Field::new(name, data_type, true)
+                })
+                .collect::<Vec<_>>();
+            let schema = Arc::new(Schema::new(fields));
+            let writer = StreamWriter::try_new(Vec::new(), &schema)?;
+            self.writer = Some((schema, writer));
+        }
+        let (schema, writer) = self.writer.as_mut().unwrap();
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let columns = schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| convert_column(field, rows, idx))
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+        writer.write(&batch)
+    }
+
+    /// Takes the bytes of the stream encoded since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        match self.writer.as_mut() {
+            Some((_, writer)) => std::mem::take(writer.get_mut()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Ends the stream and takes the bytes encoded since the last call to
+    /// `take_output()`.
+    pub fn finish(&mut self) -> Result<Vec<u8>, ArrowError> {
+        // Make sure the schema is written even if the result set is empty.
+        self.write_batch(&[])?;
+        let (_, writer) = self.writer.as_mut().unwrap();
+        writer.finish()?;
+        Ok(self.take_output())
+    }
+}
+
+fn column_type(decl_type: Option<&str>, rows: &[Vec<libsql::Value>], idx: usize) -> DataType {
+    if let Some(decl_type) = decl_type {
+        let decl_type = decl_type.to_uppercase();
+        if decl_type.contains("INT") {
+            return DataType::Int64;
+        }
+        if decl_type.contains("CHAR") || decl_type.contains("CLOB") || decl_type.contains("TEXT") {
+            return DataType::Utf8;
+        }
+        if decl_type.contains("REAL") || decl_type.contains("FLOA") || decl_type.contains("DOUB") {
+            return DataType::Float64;
+        }
+    }
+    let (mut integers, mut reals, mut texts, mut blobs) = (0, 0, 0, 0);
+    for row in rows {
+        match row[idx] {
+            libsql::Value::Null => {}
+            libsql::Value::Integer(_) => integers += 1,
+            libsql::Value::Real(_) => reals += 1,
+            libsql::Value::Text(_) => texts += 1,
+            libsql::Value::Blob(_) => blobs += 1,
+        }
+    }
+    match (integers > 0, reals > 0, texts > 0, blobs > 0) {
+        (true, false, false, false) => DataType::Int64,
+        (_, true, false, false) => DataType::Float64,
+        (false, false, false, true) => DataType::Binary,
+        _ => DataType::Utf8,
+    }
+}
+
+fn convert_column(
+    field: &Field,
+    rows: &[Vec<libsql::Value>],
+    idx: usize,
+) -> Result<ArrayRef, ArrowError> {
+    let values = rows.iter().map(|row| &row[idx]);
+    let mismatch = |v: &libsql::Value| {
+        let value_type = match v {
+            libsql::Value::Null => "null",
+            libsql::Value::Integer(_) => "integer",
+            libsql::Value::Real(_) => "real",
+            libsql::Value::Text(_) => "text",
+            libsql::Value::Blob(_) => "blob",
+        };
+        ArrowError::CastError(format!(
+            "Cannot encode {} value of column \"{}\" as {}; cast the column in the query",
+            value_type,
+            field.name(),
+            field.data_type()
+        ))
+    };
+    let array: ArrayRef = match field.data_type() {
+        DataType::Int64 => Arc::new(
+            values
+                .map(|v| match v {
+                    libsql::Value::Null => Ok(None),
+                    libsql::Value::Integer(v) => Ok(Some(*v)),
+                    v => Err(mismatch(v)),
+                })
+                .collect::<Result<Int64Array, _>>()?,
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .map(|v| match v {
+                    libsql::Value::Null => Ok(None),
+                    libsql::Value::Real(v) => Ok(Some(*v)),
+                    // Integers beyond 2^53 can't be represented exactly.
+                    libsql::Value::Integer(v) if v.unsigned_abs() <= 1 << 53 => Ok(Some(*v as f64)),
+                    v => Err(mismatch(v)),
+                })
+                .collect::<Result<Float64Array, _>>()?,
+        ),
+        DataType::Binary => Arc::new(
+            values
+                .map(|v| match v {
+                    libsql::Value::Null => Ok(None),
+                    libsql::Value::Blob(v) => Ok(Some(v.as_slice())),
+                    v => Err(mismatch(v)),
+                })
+                .collect::<Result<BinaryArray, _>>()?,
+        ),
+        _ => Arc::new(
+            values
+                .map(|v| match v {
+                    libsql::Value::Null => Ok(None),
+                    libsql::Value::Integer(v) => Ok(Some(v.to_string())),
+                    libsql::Value::Real(v) => Ok(Some(v.to_string())),
+                    libsql::Value::Text(v) => Ok(Some(v.clone())),
+                    libsql::Value::Blob(blob) => match std::str::from_utf8(blob) {
+                        Ok(text) => Ok(Some(text.to_string())),
+                        Err(_) => Err(mismatch(v)),
+                    },
+                })
+                .collect::<Result<StringArray, _>>()?,
+        ),
+    };
+    Ok(array)
+}
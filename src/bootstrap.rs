@@ -0,0 +1,145 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Suffix of the file in which libSQL records the replication index that an
+/// embedded replica has reached.
+const METADATA_SUFFIX: &str = "-client_wal_index";
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Seeds the embedded replica at `path` with a snapshot of another embedded
+/// replica, so that the first sync only pulls the frames written after the
+/// snapshot was taken. Returns `false` without copying anything if the
+/// replica already exists.
+pub(crate) fn bootstrap_replica(snapshot: &Path, path: &Path) -> io::Result<bool> {
+    if path.exists() {
+        return Ok(false);
+    }
+    let metadata = with_suffix(snapshot, METADATA_SUFFIX);
+    if !metadata.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} not found, the snapshot must be copied with the replication metadata of its replica",
+                metadata.display()
+            ),
+        ));
+    }
+    // Files left behind by an earlier replica at the same path belong to
+    // another database.
+    remove_if_exists(&with_suffix(path, "-shm"))?;
+    let copied = copy_snapshot(snapshot, &metadata, path);
+    if copied.is_err() {
+        // Without the database file, the WAL and the replication index of the
+        // snapshot would make the next open start from a stale index.
+        for suffix in ["-wal", METADATA_SUFFIX] {
+            for file in [with_suffix(path, suffix), partial(path, suffix)] {
+                let _ = remove_if_exists(&file);
+            }
+        }
+        let _ = remove_if_exists(&partial(path, ""));
+    }
+    copied.map(|_| true)
+}
+
+/// The name that a file of the replica at `path` is copied to before it is
+/// moved into place.
+fn partial(path: &Path, suffix: &str) -> PathBuf {
+    with_suffix(path, &format!("{}-bootstrap", suffix))
+}
+
+/// Copies the files of the snapshot to temporary names and moves them into
+/// place, the database file last, so that an interrupted bootstrap starts
+/// over on the next open.
+fn copy_snapshot(snapshot: &Path, metadata: &Path, path: &Path) -> io::Result<()> {
+    let wal = with_suffix(snapshot, "-wal");
+    let wal = if wal.exists() {
+        std::fs::copy(&wal, partial(path, "-wal"))?;
+        true
+    } else {
+        false
+    };
+    std::fs::copy(metadata, partial(path, METADATA_SUFFIX))?;
+    std::fs::copy(snapshot, partial(path, ""))?;
+    if wal {
+        std::fs::rename(partial(path, "-wal"), with_suffix(path, "-wal"))?;
+    } else {
+        remove_if_exists(&with_suffix(path, "-wal"))?;
+    }
+    std::fs::rename(
+        partial(path, METADATA_SUFFIX),
+        with_suffix(path, METADATA_SUFFIX),
+    )?;
+    std::fs::rename(partial(path, ""), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("libsql-js-{}-{}.db", name, std::process::id()))
+    }
+
+    fn write_snapshot(snapshot: &Path) {
+        std::fs::write(with_suffix(snapshot, "-wal"), b"wal").unwrap();
+        std::fs::write(with_suffix(snapshot, METADATA_SUFFIX), b"index").unwrap();
+    }
+
+    fn remove_replica(path: &Path) {
+        for suffix in ["", "-wal", METADATA_SUFFIX] {
+            remove_if_exists(&with_suffix(path, suffix)).unwrap();
+        }
+    }
+
+    #[test]
+    fn bootstrap_copies_the_snapshot() {
+        let snapshot = temp_path("bootstrap-snapshot");
+        let path = temp_path("bootstrap-replica");
+        write_snapshot(&snapshot);
+        std::fs::write(&snapshot, b"db").unwrap();
+
+        assert!(bootstrap_replica(&snapshot, &path).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), b"db");
+        assert_eq!(std::fs::read(with_suffix(&path, "-wal")).unwrap(), b"wal");
+        assert_eq!(
+            std::fs::read(with_suffix(&path, METADATA_SUFFIX)).unwrap(),
+            b"index"
+        );
+        assert!(!partial(&path, "").exists());
+        // An existing replica is left alone.
+        assert!(!bootstrap_replica(&snapshot, &path).unwrap());
+
+        remove_replica(&snapshot);
+        remove_replica(&path);
+    }
+
+    #[test]
+    fn failed_bootstrap_leaves_no_files() {
+        let snapshot = temp_path("bootstrap-broken-snapshot");
+        let path = temp_path("bootstrap-broken-replica");
+        write_snapshot(&snapshot);
+        // Copying a directory as the database file fails.
+        std::fs::create_dir_all(&snapshot).unwrap();
+
+        assert!(bootstrap_replica(&snapshot, &path).is_err());
+        for suffix in ["", "-wal", METADATA_SUFFIX] {
+            assert!(!with_suffix(&path, suffix).exists(), "{}", suffix);
+            assert!(!partial(&path, suffix).exists(), "{}", suffix);
+        }
+
+        std::fs::remove_dir(&snapshot).unwrap();
+        remove_replica(&snapshot);
+    }
+}
@@ -0,0 +1,1081 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use neon::prelude::*;
+use neon::types::JsBigInt;
+use tracing::trace;
+
+/// The maximum number of changed rowids reported for a table.
+const MAX_ROWIDS: usize = 10_000;
+
+/// The largest integer that a JavaScript number represents exactly.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+const WAL_HEADER_SIZE: u64 = 32;
+const FRAME_HEADER_SIZE: u64 = 24;
+/// Offset of `nBackfill` in the shared memory file of the WAL.
+const SHM_BACKFILL_OFFSET: u64 = 96;
+
+const INTERIOR_INDEX_PAGE: u8 = 2;
+const INTERIOR_TABLE_PAGE: u8 = 5;
+const LEAF_TABLE_PAGE: u8 = 13;
+
+/// The tables changed by a sync.
+#[derive(Default)]
+pub(crate) struct SyncChanges {
+    tables: BTreeMap<String, Option<Vec<i64>>>,
+    /// False if some changed pages, such as the overflow pages of large
+    /// values, could not be attributed to a table.
+    complete: bool,
+}
+
+/// The state of the local database files before a sync, which the frames
+/// that the sync appends to the WAL are compared with.
+pub(crate) struct WalPosition {
+    header: Option<WalHeader>,
+    end: u64,
+    db_file: Option<(u64, SystemTime)>,
+    backfill: Option<u32>,
+}
+
+impl WalPosition {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let wal = read_wal(path)?;
+        Ok(WalPosition {
+            header: wal.as_ref().map(|wal| wal.header),
+            end: wal.map_or(0, |wal| wal.end),
+            db_file: db_file_state(path)?,
+            backfill: read_backfill(path)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct WalHeader {
+    page_size: u32,
+    checkpoint_seq: u32,
+    salts: [u8; 8],
+}
+
+struct Wal {
+    header: WalHeader,
+    /// The page number and offset of every committed frame, in order.
+    frames: Vec<(u32, u64)>,
+    /// The number of frames up to the end of each transaction.
+    commits: Vec<usize>,
+    /// The offset after the last committed frame.
+    end: u64,
+}
+
+pub(crate) fn wal_path(path: &Path) -> PathBuf {
+    let mut wal = path.to_path_buf().into_os_string();
+    wal.push("-wal");
+    PathBuf::from(wal)
+}
+
+/// Returns the number of WAL frames that checkpoints copied into the database
+/// file, as recorded in the shared memory file of the WAL.
+fn read_backfill(path: &Path) -> io::Result<Option<u32>> {
+    let mut shm = path.to_path_buf().into_os_string();
+    shm.push("-shm");
+    let mut file = match File::open(shm) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut buf = [0; 4];
+    file.seek(SeekFrom::Start(SHM_BACKFILL_OFFSET))?;
+    match file.read_exact(&mut buf) {
+        // The shared memory file is in native byte order.
+        Ok(()) => Ok(Some(u32::from_ne_bytes(buf))),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn db_file_state(path: &Path) -> io::Result<Option<(u64, SystemTime)>> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(Some((metadata.len(), metadata.modified()?))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns the number of committed frames in the WAL.
+pub(crate) fn wal_frame_count(path: &Path) -> io::Result<u64> {
+    Ok(read_wal(path)?.map_or(0, |wal| wal.frames.len() as u64))
+}
+
+/// Returns the number of committed frames appended to the WAL after
+/// `before`, or `None` if the WAL was restarted since, as the frames that
+/// were appended before the restart are then unknown.
+pub(crate) fn frames_appended(path: &Path, before: &WalPosition) -> io::Result<Option<u64>> {
+    let Some(wal) = read_wal(path)? else {
+        return Ok(before.header.is_none().then_some(0));
+    };
+    match before.header {
+        Some(header) if header == wal.header && before.end <= wal.end => {
+            let frames = wal
+                .frames
+                .iter()
+                .filter(|(_, offset)| *offset >= before.end)
+                .count();
+            Ok(Some(frames as u64))
+        }
+        Some(_) => Ok(None),
+        None => Ok(Some(wal.frames.len() as u64)),
+    }
+}
+
+/// Returns the number of committed frames after frame `frame_no` in the
+/// WAL, the number of transactions that they belong to, and their size in
+/// bytes.
+///
+/// `frame_no` is the `durable_frame_num` of the sync metadata of an offline
+/// database, which is a frame number in the current WAL: libSQL disables
+/// checkpoints on the connections of offline databases and pushes the frames
+/// after it up to the WAL frame count itself. The only checkpoint libSQL
+/// runs, when a pull moves the database to the next generation, resets
+/// `durable_frame_num` to 0.
+pub(crate) fn wal_frames_after(path: &Path, frame_no: u64) -> io::Result<(u64, u64, u64)> {
+    let Some(wal) = read_wal(path)? else {
+        return Ok((0, 0, 0));
+    };
+    let frame_no = frame_no as usize;
+    let frames = wal.frames.len().saturating_sub(frame_no) as u64;
+    let transactions = wal.commits.iter().filter(|end| **end > frame_no).count() as u64;
+    let bytes = frames * (FRAME_HEADER_SIZE + wal.header.page_size as u64);
+    Ok((frames, transactions, bytes))
+}
+
+/// Reads the headers of the committed frames in the WAL.
+///
+/// The frame checksums are not verified, so a frame of a transaction that
+/// was torn by a crash is not detected, but frames left over from before the
+/// WAL was restarted are skipped as their salts don't match.
+fn read_wal(path: &Path) -> io::Result<Option<Wal>> {
+    let mut file = match File::open(wal_path(path)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let len = file.metadata()?.len();
+    if len < WAL_HEADER_SIZE {
+        return Ok(None);
+    }
+    let header = parse_wal_header(&mut file)?;
+    let frame_size = FRAME_HEADER_SIZE + header.page_size as u64;
+    let mut frames = Vec::new();
+    let mut pending = Vec::new();
+    let mut commits = Vec::new();
+    let mut end = WAL_HEADER_SIZE;
+    let mut offset = WAL_HEADER_SIZE;
+    while offset + frame_size <= len {
+        let mut buf = [0; FRAME_HEADER_SIZE as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        if buf[8..16] != header.salts {
+            break;
+        }
+        pending.push((read_u32(&buf, 0), offset));
+        offset += frame_size;
+        // The size of the database is only set in the last frame of a
+        // transaction.
+        if read_u32(&buf, 4) != 0 {
+            frames.append(&mut pending);
+            commits.push(frames.len());
+            end = offset;
+        }
+    }
+    Ok(Some(Wal {
+        header,
+        frames,
+        commits,
+        end,
+    }))
+}
+
+fn parse_wal_header(file: &mut File) -> io::Result<WalHeader> {
+    let mut buf = [0; WAL_HEADER_SIZE as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut buf)?;
+    // The page offsets and sizes computed from the pages assume a valid page
+    // size, which is a power of two from 512 to 65536.
+    let page_size = read_u32(&buf, 8);
+    if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid WAL page size: {}", page_size),
+        ));
+    }
+    Ok(WalHeader {
+        page_size,
+        checkpoint_seq: read_u32(&buf, 12),
+        salts: buf[16..24].try_into().unwrap(),
+    })
+}
+
+fn read_wal_header(path: &Path) -> io::Result<Option<WalHeader>> {
+    let mut file = match File::open(wal_path(path)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if file.metadata()?.len() < WAL_HEADER_SIZE {
+        return Ok(None);
+    }
+    parse_wal_header(&mut file).map(Some)
+}
+
+/// Reads the pages of the database as of a WAL frame.
+struct Pages {
+    db: Option<File>,
+    wal: File,
+    page_size: u64,
+    frames: HashMap<u32, u64>,
+}
+
+impl Pages {
+    fn open(path: &Path, wal: &Wal, end: u64) -> io::Result<Self> {
+        let db = match File::open(path) {
+            Ok(file) => Some(file),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let frames = wal
+            .frames
+            .iter()
+            .filter(|(_, offset)| *offset < end)
+            .copied()
+            .collect();
+        Ok(Pages {
+            db,
+            wal: File::open(wal_path(path))?,
+            page_size: wal.header.page_size as u64,
+            frames,
+        })
+    }
+
+    fn read(&mut self, pgno: u32) -> io::Result<Option<Vec<u8>>> {
+        // Pages are numbered from 1, so a page number of 0 read from a
+        // malformed page doesn't exist.
+        if pgno == 0 {
+            return Ok(None);
+        }
+        let (file, offset) = match self.frames.get(&pgno) {
+            Some(offset) => (&mut self.wal, offset + FRAME_HEADER_SIZE),
+            None => match &mut self.db {
+                Some(db) => (db, (pgno as u64 - 1) * self.page_size),
+                None => return Ok(None),
+            },
+        };
+        if offset + self.page_size > file.metadata()?.len() {
+            return Ok(None);
+        }
+        let mut page = vec![0; self.page_size as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut page)?;
+        Ok(Some(page))
+    }
+}
+
+/// A b-tree in the database schema.
+struct Btree {
+    table: String,
+    root: u32,
+    /// Whether this is the b-tree of the table rather than one of its indexes.
+    is_table: bool,
+}
+
+async fn read_btrees(conn: &libsql::Connection) -> libsql::Result<Vec<Btree>> {
+    let mut btrees = vec![Btree {
+        table: "sqlite_schema".to_string(),
+        root: 1,
+        is_table: true,
+    }];
+    let mut rows = conn
+        .query(
+            "SELECT type, tbl_name, rootpage FROM sqlite_schema WHERE rootpage > 0",
+            (),
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        btrees.push(Btree {
+            is_table: row.get::<String>(0)? == "table",
+            table: row.get(1)?,
+            root: row.get::<u32>(2)?,
+        });
+    }
+    Ok(btrees)
+}
+
+/// Returns the tables whose pages were changed by the frames that a sync
+/// appended to the WAL after `before`, or `None` if they cannot be
+/// determined.
+pub(crate) async fn read_changes(
+    conn: &libsql::Connection,
+    path: &Path,
+    before: io::Result<WalPosition>,
+) -> Option<SyncChanges> {
+    let btrees = match read_btrees(conn).await {
+        Ok(btrees) => btrees,
+        Err(err) => {
+            trace!("Failed to read the schema for the sync changes: {}", err);
+            return None;
+        }
+    };
+    match before.and_then(|before| diff(path, &before, &btrees)) {
+        Ok(changes) => changes,
+        Err(err) => {
+            trace!("Failed to read the sync changes: {}", err);
+            None
+        }
+    }
+}
+
+fn diff(path: &Path, before: &WalPosition, btrees: &[Btree]) -> io::Result<Option<SyncChanges>> {
+    // A checkpoint during the sync overwrites the pages of the database file
+    // that the changed pages would be compared with.
+    let db_file = db_file_state(path)?;
+    if db_file != before.db_file {
+        return Ok(None);
+    }
+    let backfill = read_backfill(path)?;
+    let Some(wal) = read_wal(path)? else {
+        return Ok(Some(SyncChanges {
+            complete: true,
+            ..Default::default()
+        }));
+    };
+    // The size and modification time of the database file may not change
+    // if the checkpoint is quick enough, but the backfill count does unless
+    // the WAL was restarted.
+    if before.header == Some(wal.header) && backfill != before.backfill {
+        return Ok(None);
+    }
+    let Some(changes) = diff_pages(path, before, &wal, btrees)? else {
+        return Ok(None);
+    };
+    // A checkpoint may also run while the pages are read, since the sync no
+    // longer holds the database.
+    if db_file_state(path)? != db_file
+        || read_backfill(path)? != backfill
+        || read_wal_header(path)? != Some(wal.header)
+    {
+        return Ok(None);
+    }
+    Ok(Some(changes))
+}
+
+fn diff_pages(
+    path: &Path,
+    before: &WalPosition,
+    wal: &Wal,
+    btrees: &[Btree],
+) -> io::Result<Option<SyncChanges>> {
+    // If the WAL was restarted without writing to the database file, all of
+    // its frames had been checkpointed before the sync started.
+    let start = match before.header {
+        Some(header) if header == wal.header && before.end <= wal.end => before.end,
+        Some(header) if header.checkpoint_seq != wal.header.checkpoint_seq => WAL_HEADER_SIZE,
+        Some(_) => return Ok(None),
+        None => WAL_HEADER_SIZE,
+    };
+    let changed: HashSet<u32> = wal
+        .frames
+        .iter()
+        .filter(|(_, offset)| *offset >= start)
+        .map(|(pgno, _)| *pgno)
+        .collect();
+    let mut old = Pages::open(path, wal, start)?;
+    let mut new = Pages::open(path, wal, wal.end)?;
+    let Some(header) = new.read(1)? else {
+        return Ok(None);
+    };
+    // Encrypted databases don't have a readable header.
+    if !header.starts_with(b"SQLite format 3\0") {
+        return Ok(None);
+    }
+    // SQLite requires at least 480 usable bytes per page, which the sizes of
+    // the cells are computed from.
+    let reserved = header.get(20).copied().unwrap_or(u8::MAX) as usize;
+    let usable_size = new.page_size as usize - reserved;
+    if usable_size < 480 {
+        return Ok(None);
+    }
+
+    // The root pages of the b-trees may have been reused if the schema
+    // changed, so the pages are then only attributed as of after the sync.
+    let schema = &btrees[..1];
+    let old_schema = map_btrees(&mut old, schema)?;
+    let new_schema = map_btrees(&mut new, schema)?;
+    let schema_changed = changed
+        .iter()
+        .any(|pgno| old_schema.contains_key(pgno) || new_schema.contains_key(pgno))
+        && {
+            let mut cells = Cells::default();
+            cells.add_pages(&mut old, &changed, &old_schema, schema, usable_size, true)?;
+            cells.add_pages(&mut new, &changed, &new_schema, schema, usable_size, false)?;
+            !cells.rowids(0).is_some_and(|rowids| rowids.is_empty())
+        };
+    let old_owners = if schema_changed {
+        HashMap::new()
+    } else {
+        map_btrees(&mut old, btrees)?
+    };
+    let new_owners = map_btrees(&mut new, btrees)?;
+    let mut cells = Cells::default();
+    cells.add_pages(&mut old, &changed, &old_owners, btrees, usable_size, true)?;
+    cells.add_pages(&mut new, &changed, &new_owners, btrees, usable_size, false)?;
+
+    let free = free_pages(&mut new, &header)?;
+    let complete = changed.iter().all(|pgno| {
+        new_owners.contains_key(pgno)
+            || free.contains(pgno)
+            || is_ptrmap_page(*pgno, &header, new.page_size, usable_size)
+    });
+    let mut tables = BTreeMap::new();
+    for (i, btree) in btrees.iter().enumerate() {
+        if !cells.touched.contains(&i) {
+            continue;
+        }
+        let rowids = if schema_changed {
+            None
+        } else {
+            match cells.rowids(i) {
+                Some(rowids) if rowids.is_empty() => continue,
+                Some(rowids) if rowids.len() <= MAX_ROWIDS => Some(rowids),
+                _ => None,
+            }
+        };
+        tables.insert(btree.table.clone(), rowids);
+    }
+    Ok(Some(SyncChanges { tables, complete }))
+}
+
+/// Maps the pages of the b-trees to their index in `btrees`.
+///
+/// Only the interior pages are read. All the leaf pages of a b-tree are at
+/// the same depth, so the walk stops at the first level of leaf pages.
+fn map_btrees(pages: &mut Pages, btrees: &[Btree]) -> io::Result<HashMap<u32, usize>> {
+    let mut owners = HashMap::new();
+    for (i, btree) in btrees.iter().enumerate() {
+        let mut level = vec![btree.root];
+        while !level.is_empty() {
+            level.retain(|pgno| !owners.contains_key(pgno));
+            for pgno in &level {
+                owners.insert(*pgno, i);
+            }
+            let Some(first) = level.first() else {
+                break;
+            };
+            let is_interior = pages
+                .read(*first)?
+                .is_some_and(|page| children(&page, *first).is_some());
+            if !is_interior {
+                break;
+            }
+            let mut next = Vec::new();
+            for pgno in level {
+                if let Some(page) = pages.read(pgno)? {
+                    next.extend(children(&page, pgno).unwrap_or_default());
+                }
+            }
+            level = next;
+        }
+    }
+    Ok(owners)
+}
+
+/// Returns the pages on the freelist.
+fn free_pages(pages: &mut Pages, header: &[u8]) -> io::Result<HashSet<u32>> {
+    let mut free = HashSet::new();
+    let mut trunk = read_u32(header, 32);
+    while trunk != 0 && free.insert(trunk) {
+        let Some(page) = pages.read(trunk)? else {
+            break;
+        };
+        let count = (read_u32(&page, 4) as usize).min((page.len() / 4).saturating_sub(2));
+        for i in 0..count {
+            free.insert(read_u32(&page, 8 + i * 4));
+        }
+        trunk = read_u32(&page, 0);
+    }
+    Ok(free)
+}
+
+/// Returns true if `pgno` is a pointer map page of an auto-vacuum database.
+fn is_ptrmap_page(pgno: u32, header: &[u8], page_size: u64, usable_size: usize) -> bool {
+    if read_u32(header, 52) == 0 || pgno < 2 {
+        return false;
+    }
+    let pages_per_map = usable_size as u32 / 5 + 1;
+    let pending_byte_page = (0x40000000 / page_size) as u32 + 1;
+    let mut ptrmap = (pgno - 2) / pages_per_map * pages_per_map + 2;
+    if ptrmap == pending_byte_page {
+        ptrmap += 1;
+    }
+    ptrmap == pgno
+}
+
+/// The cells of the changed table leaf pages before and after a sync.
+#[derive(Default)]
+struct Cells {
+    old: HashMap<usize, HashMap<i64, u64>>,
+    new: HashMap<usize, HashMap<i64, u64>>,
+    touched: HashSet<usize>,
+    /// The b-trees with changed pages whose rows cannot be read, such as the
+    /// pages of `WITHOUT ROWID` tables.
+    unknown: HashSet<usize>,
+}
+
+impl Cells {
+    fn add_pages(
+        &mut self,
+        pages: &mut Pages,
+        changed: &HashSet<u32>,
+        owners: &HashMap<u32, usize>,
+        btrees: &[Btree],
+        usable_size: usize,
+        old: bool,
+    ) -> io::Result<()> {
+        for pgno in changed {
+            let Some(&i) = owners.get(pgno) else {
+                continue;
+            };
+            if !btrees[i].is_table {
+                continue;
+            }
+            self.touched.insert(i);
+            // Pages appended to the database by the sync have no old cells.
+            let Some(page) = pages.read(*pgno)? else {
+                continue;
+            };
+            let cells = if old {
+                self.old.entry(i).or_default()
+            } else {
+                self.new.entry(i).or_default()
+            };
+            match page.get(header_offset(*pgno)).copied() {
+                Some(LEAF_TABLE_PAGE) => match table_leaf_cells(&page, *pgno, usable_size) {
+                    Some(page_cells) => cells.extend(page_cells),
+                    None => {
+                        self.unknown.insert(i);
+                    }
+                },
+                Some(INTERIOR_TABLE_PAGE) => {}
+                _ => {
+                    self.unknown.insert(i);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the sorted rowids whose cells were added, removed or changed,
+    /// or `None` if they are not known.
+    fn rowids(&self, i: usize) -> Option<Vec<i64>> {
+        if self.unknown.contains(&i) {
+            return None;
+        }
+        let empty = HashMap::new();
+        let old = self.old.get(&i).unwrap_or(&empty);
+        let new = self.new.get(&i).unwrap_or(&empty);
+        let mut rowids: Vec<i64> = old
+            .keys()
+            .chain(new.keys())
+            .filter(|rowid| old.get(rowid) != new.get(rowid))
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        rowids.sort_unstable();
+        Some(rowids)
+    }
+}
+
+/// Page 1 starts with the database header.
+fn header_offset(pgno: u32) -> usize {
+    if pgno == 1 {
+        100
+    } else {
+        0
+    }
+}
+
+/// Returns the child pages of an interior b-tree page, or `None` if the page
+/// is not an interior page.
+fn children(page: &[u8], pgno: u32) -> Option<Vec<u32>> {
+    let h = header_offset(pgno);
+    if !matches!(
+        page.get(h).copied()?,
+        INTERIOR_INDEX_PAGE | INTERIOR_TABLE_PAGE
+    ) {
+        return None;
+    }
+    let count = read_u16(page, h + 3) as usize;
+    let mut children = Vec::with_capacity(count + 1);
+    for i in 0..count {
+        let cell = read_u16(page, h + 12 + i * 2) as usize;
+        children.push(read_u32(page.get(cell..cell + 4)?, 0));
+    }
+    children.push(read_u32(page, h + 8));
+    Some(children)
+}
+
+/// Returns the rowids of the cells in a table leaf page with a hash of the
+/// cell contents, or `None` if the page is malformed.
+fn table_leaf_cells(page: &[u8], pgno: u32, usable_size: usize) -> Option<Vec<(i64, u64)>> {
+    let h = header_offset(pgno);
+    let count = read_u16(page, h + 3) as usize;
+    let mut cells = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = read_u16(page, h + 8 + i * 2) as usize;
+        let (payload_size, n) = read_varint(page.get(start..)?)?;
+        let (rowid, m) = read_varint(page.get(start + n..)?)?;
+        let payload_size = payload_size as usize;
+        let local_size = local_payload_size(payload_size, usable_size);
+        // Cells that spill to overflow pages end with the first overflow page.
+        let overflow = if local_size < payload_size { 4 } else { 0 };
+        let cell = page.get(start..start + n + m + local_size + overflow)?;
+        let mut hasher = DefaultHasher::new();
+        cell.hash(&mut hasher);
+        cells.push((rowid as i64, hasher.finish()));
+    }
+    Some(cells)
+}
+
+/// Returns the number of payload bytes of a table leaf cell that are stored
+/// on the page itself, as specified by the SQLite file format. The usable
+/// size is at least 480 bytes.
+fn local_payload_size(payload_size: usize, usable_size: usize) -> usize {
+    let max_local = usable_size - 35;
+    if payload_size <= max_local {
+        return payload_size;
+    }
+    let min_local = (usable_size - 12) * 32 / 255 - 23;
+    let local = min_local + (payload_size - min_local) % (usable_size - 4);
+    if local <= max_local {
+        local
+    } else {
+        min_local
+    }
+}
+
+/// Reads a SQLite variable-length integer, returning it with its length.
+fn read_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = *buf.get(i)?;
+        if i == 8 {
+            return Some(((value << 8) | byte as u64, 9));
+        }
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    unreachable!()
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    buf.get(offset..offset + 2)
+        .map_or(0, |b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    buf.get(offset..offset + 4)
+        .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+pub(crate) fn convert_changes<'a>(
+    cx: &mut impl Context<'a>,
+    changes: Option<&SyncChanges>,
+) -> JsResult<'a, JsValue> {
+    let Some(changes) = changes else {
+        return Ok(cx.null().upcast());
+    };
+    let obj = cx.empty_object();
+    let tables = cx.empty_array();
+    for (i, (name, rowids)) in changes.tables.iter().enumerate() {
+        let table = cx.empty_object();
+        let name = cx.string(name);
+        table.set(cx, "name", name)?;
+        let rowids: Handle<'_, JsValue> = match rowids {
+            Some(rowids) => {
+                let array = cx.empty_array();
+                for (j, rowid) in rowids.iter().enumerate() {
+                    // Rowids that a number can't represent exactly are
+                    // returned as BigInts.
+                    let rowid: Handle<'_, JsValue> = if rowid.unsigned_abs() <= MAX_SAFE_INTEGER {
+                        cx.number(*rowid as f64).upcast()
+                    } else {
+                        JsBigInt::from_i64(cx, *rowid).upcast()
+                    };
+                    array.set(cx, j as u32, rowid)?;
+                }
+                array.upcast()
+            }
+            None => cx.null().upcast(),
+        };
+        table.set(cx, "rowids", rowids)?;
+        tables.set(cx, i as u32, table)?;
+    }
+    obj.set(cx, "tables", tables)?;
+    let complete = cx.boolean(changes.complete);
+    obj.set(cx, "complete", complete)?;
+    Ok(obj.upcast())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestDb {
+        path: PathBuf,
+        rt: tokio::runtime::Runtime,
+        _db: libsql::Database,
+        conn: libsql::Connection,
+    }
+
+    impl TestDb {
+        /// Opens a new database in WAL mode, in which the WAL is only
+        /// checkpointed explicitly.
+        fn open(name: &str, setup: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "libsql-js-changes-{}-{}.db",
+                name,
+                std::process::id()
+            ));
+            remove_files(&path);
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let (db, conn) = rt.block_on(async {
+                let db = libsql::Builder::new_local(&path).build().await.unwrap();
+                let conn = db.connect().unwrap();
+                conn.execute_batch(setup).await.unwrap();
+                conn.query("PRAGMA journal_mode = WAL", ()).await.unwrap();
+                conn.execute_batch("PRAGMA wal_autocheckpoint = 0")
+                    .await
+                    .unwrap();
+                (db, conn)
+            });
+            TestDb {
+                path,
+                rt,
+                _db: db,
+                conn,
+            }
+        }
+
+        fn exec(&self, sql: &str) {
+            self.rt.block_on(self.conn.execute_batch(sql)).unwrap();
+        }
+
+        fn query_u32(&self, sql: &str) -> u32 {
+            self.rt.block_on(async {
+                let mut rows = self.conn.query(sql, ()).await.unwrap();
+                rows.next().await.unwrap().unwrap().get(0).unwrap()
+            })
+        }
+
+        fn checkpoint(&self) {
+            self.rt.block_on(async {
+                self.conn
+                    .query("PRAGMA wal_checkpoint(PASSIVE)", ())
+                    .await
+                    .unwrap()
+                    .next()
+                    .await
+                    .unwrap();
+            });
+        }
+
+        fn btrees(&self) -> Vec<Btree> {
+            self.rt.block_on(read_btrees(&self.conn)).unwrap()
+        }
+
+        fn changes(&self, before: &WalPosition) -> Option<Vec<(String, Option<Vec<i64>>)>> {
+            let changes = diff(&self.path, before, &self.btrees()).unwrap()?;
+            Some(changes.tables.into_iter().collect())
+        }
+
+        /// Returns the current pages of the database.
+        fn pages(&self) -> Pages {
+            let wal = read_wal(&self.path).unwrap().unwrap();
+            Pages::open(&self.path, &wal, wal.end).unwrap()
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            remove_files(&self.path);
+        }
+    }
+
+    fn remove_files(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.to_path_buf().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn varints() {
+        assert_eq!(read_varint(&[0x00]), Some((0, 1)));
+        assert_eq!(read_varint(&[0x7f, 0xff]), Some((127, 1)));
+        assert_eq!(read_varint(&[0x81, 0x00]), Some((128, 2)));
+        assert_eq!(read_varint(&[0x82, 0x80, 0x01]), Some((0x8001, 3)));
+        assert_eq!(read_varint(&[0xff; 9]), Some((u64::MAX, 9)));
+        assert_eq!(read_varint(&[0x81, 0x81]), None);
+        assert_eq!(read_varint(&[]), None);
+    }
+
+    #[test]
+    fn local_payload_sizes() {
+        assert_eq!(local_payload_size(100, 4096), 100);
+        assert_eq!(local_payload_size(4061, 4096), 4061);
+        // The minimum local size plus the remainder of the overflow pages.
+        assert_eq!(
+            local_payload_size(10_000, 4096),
+            489 + (10_000 - 489) % 4092
+        );
+        assert_eq!(local_payload_size(4062, 4096), 489);
+    }
+
+    #[test]
+    fn leaf_cells_of_real_pages() {
+        let db = TestDb::open("leaf", "PRAGMA page_size = 4096");
+        db.exec(
+            "CREATE TABLE t (x);
+             INSERT INTO t (rowid, x) VALUES (1, 'small'), (2, zeroblob(10000)), (-5, 3.5);
+             INSERT INTO t (rowid, x) VALUES (9007199254740993, NULL);",
+        );
+        let root = db.query_u32("SELECT rootpage FROM sqlite_schema WHERE name = 't'");
+        let page = db.pages().read(root).unwrap().unwrap();
+        assert_eq!(page[0], LEAF_TABLE_PAGE);
+        let mut rowids: Vec<i64> = table_leaf_cells(&page, root, 4096)
+            .unwrap()
+            .into_iter()
+            .map(|(rowid, _)| rowid)
+            .collect();
+        rowids.sort_unstable();
+        assert_eq!(rowids, vec![-5, 1, 2, 9007199254740993]);
+        // A truncated page is malformed.
+        assert_eq!(table_leaf_cells(&page[..200], root, 4096), None);
+    }
+
+    #[test]
+    fn ptrmap_pages() {
+        let db = TestDb::open(
+            "ptrmap",
+            "PRAGMA page_size = 4096; PRAGMA auto_vacuum = FULL",
+        );
+        db.exec("CREATE TABLE t (x); INSERT INTO t VALUES (zeroblob(20000))");
+        let mut pages = db.pages();
+        let header = pages.read(1).unwrap().unwrap();
+        let usable_size = 4096 - header[20] as usize;
+        assert!(is_ptrmap_page(2, &header, 4096, usable_size));
+        assert!(!is_ptrmap_page(3, &header, 4096, usable_size));
+        assert!(is_ptrmap_page(2 + 4096 / 5 + 1, &header, 4096, usable_size));
+        // Page 2 of a database without auto-vacuum is an ordinary page.
+        let mut header = header;
+        header[52..56].fill(0);
+        assert!(!is_ptrmap_page(2, &header, 4096, usable_size));
+    }
+
+    #[test]
+    fn freelist() {
+        let db = TestDb::open("freelist", "");
+        db.exec(
+            "CREATE TABLE t (x);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+             INSERT INTO t SELECT zeroblob(1000) FROM n;
+             DELETE FROM t;",
+        );
+        let mut pages = db.pages();
+        let header = pages.read(1).unwrap().unwrap();
+        let free = free_pages(&mut pages, &header).unwrap();
+        let count = db.query_u32("PRAGMA freelist_count");
+        assert!(count > 0);
+        assert_eq!(free.len(), count as usize);
+        assert!(!free.contains(&1));
+    }
+
+    #[test]
+    fn diff_reports_changed_rows() {
+        let db = TestDb::open("diff", "");
+        db.exec(
+            "CREATE TABLE a (x);
+             CREATE TABLE b (x);
+             CREATE INDEX b_x ON b (x);
+             INSERT INTO a VALUES (1), (2), (3);
+             INSERT INTO b VALUES (1);",
+        );
+        let before = WalPosition::read(&db.path).unwrap();
+        db.exec(
+            "UPDATE a SET x = 20 WHERE rowid = 2;
+             DELETE FROM a WHERE rowid = 3;
+             INSERT INTO b (rowid, x) VALUES (9007199254740993, 2);",
+        );
+        assert_eq!(
+            db.changes(&before),
+            Some(vec![
+                ("a".to_string(), Some(vec![2, 3])),
+                ("b".to_string(), Some(vec![9007199254740993])),
+            ])
+        );
+
+        // Nothing changed since.
+        let before = WalPosition::read(&db.path).unwrap();
+        assert_eq!(db.changes(&before), Some(vec![]));
+    }
+
+    #[test]
+    fn diff_reports_schema_changes() {
+        let db = TestDb::open("schema", "CREATE TABLE a (x)");
+        let before = WalPosition::read(&db.path).unwrap();
+        db.exec("CREATE TABLE b (x); INSERT INTO b VALUES (1)");
+        let changes = db.changes(&before).unwrap();
+        assert!(changes.contains(&("sqlite_schema".to_string(), None)));
+        assert!(changes.contains(&("b".to_string(), None)));
+    }
+
+    #[test]
+    fn appended_frames() {
+        let db = TestDb::open("appended", "CREATE TABLE a (x)");
+        let before = WalPosition::read(&db.path).unwrap();
+        db.exec("INSERT INTO a VALUES (1)");
+        db.exec("INSERT INTO a VALUES (2)");
+        let appended = frames_appended(&db.path, &before).unwrap().unwrap();
+        assert_eq!(appended, wal_frame_count(&db.path).unwrap());
+
+        let before = WalPosition::read(&db.path).unwrap();
+        assert_eq!(frames_appended(&db.path, &before).unwrap(), Some(0));
+        db.exec("INSERT INTO a VALUES (3)");
+        assert_eq!(frames_appended(&db.path, &before).unwrap(), Some(1));
+
+        // The frames appended before the WAL restarted are unknown.
+        db.exec("PRAGMA wal_checkpoint(TRUNCATE)");
+        db.exec("INSERT INTO a VALUES (4)");
+        assert_eq!(frames_appended(&db.path, &before).unwrap(), None);
+    }
+
+    /// A xorshift generator, so that the malformed pages are reproducible.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn fill(&mut self, buf: &mut [u8]) {
+            for byte in buf {
+                *byte = self.next() as u8;
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_pages() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..5000 {
+            let page_size = 512 << random.below(4);
+            let mut page = vec![0; page_size];
+            random.fill(&mut page);
+            let pgno = random.below(3) as u32;
+            let h = header_offset(pgno);
+            page[h] = [INTERIOR_INDEX_PAGE, INTERIOR_TABLE_PAGE, LEAF_TABLE_PAGE][random.below(3)];
+            // Small cell counts and offsets are more likely to point at cells.
+            if random.below(2) == 0 {
+                page[h + 3] = 0;
+                page[h + 4] = random.below(8) as u8;
+            }
+            let page = &page[..random.below(page_size + 1)];
+            let usable_size = page_size - random.below(page_size - 479);
+            let _ = children(page, pgno);
+            let _ = table_leaf_cells(page, pgno, usable_size);
+            let _ = local_payload_size(random.next() as u32 as usize, usable_size);
+            let _ = is_ptrmap_page(random.next() as u32, page, page_size as u64, usable_size);
+        }
+    }
+
+    #[test]
+    fn diff_of_corrupt_frames() {
+        let db = TestDb::open("corrupt", "CREATE TABLE a (x); CREATE TABLE b (x)");
+        db.exec("INSERT INTO a VALUES (1)");
+        let before = WalPosition::read(&db.path).unwrap();
+        db.exec(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+             INSERT INTO b SELECT randomblob(100) FROM n;
+             UPDATE a SET x = 2;",
+        );
+        let btrees = db.btrees();
+        let wal = std::fs::read(wal_path(&db.path)).unwrap();
+        let Wal { header, frames, .. } = read_wal(&db.path).unwrap().unwrap();
+        let page_size = header.page_size as usize;
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200 {
+            // The frame headers are kept, so that the frames are still read
+            // and their corrupt pages are parsed.
+            let mut corrupt = wal.clone();
+            for _ in 0..=random.below(4) {
+                let (_, offset) = frames[random.below(frames.len())];
+                let start = (offset + FRAME_HEADER_SIZE) as usize;
+                let page = &mut corrupt[start..start + page_size];
+                if random.below(2) == 0 {
+                    random.fill(page);
+                } else {
+                    let at = random.below(page.len() - 8);
+                    random.fill(&mut page[at..at + 8]);
+                }
+            }
+            std::fs::write(wal_path(&db.path), &corrupt).unwrap();
+            let _ = diff(&db.path, &before, &btrees);
+        }
+        std::fs::write(wal_path(&db.path), &wal).unwrap();
+    }
+
+    #[test]
+    fn invalid_wal_page_size() {
+        let db = TestDb::open("page-size", "CREATE TABLE a (x)");
+        let before = WalPosition::read(&db.path).unwrap();
+        db.exec("INSERT INTO a VALUES (1)");
+        let btrees = db.btrees();
+        let wal = std::fs::read(wal_path(&db.path)).unwrap();
+        for page_size in [0u32, 1, 256, 4097, 131072] {
+            let mut corrupt = wal.clone();
+            corrupt[8..12].copy_from_slice(&page_size.to_be_bytes());
+            std::fs::write(wal_path(&db.path), &corrupt).unwrap();
+            assert!(read_wal(&db.path).is_err());
+            assert!(diff(&db.path, &before, &btrees).is_err());
+            assert!(frames_appended(&db.path, &before).is_err());
+        }
+        std::fs::write(wal_path(&db.path), &wal).unwrap();
+    }
+
+    #[test]
+    fn diff_gives_up_after_checkpoint() {
+        let db = TestDb::open("checkpoint", "CREATE TABLE a (x)");
+        db.exec("INSERT INTO a VALUES (1)");
+        let before = WalPosition::read(&db.path).unwrap();
+        db.exec("INSERT INTO a VALUES (2)");
+        db.checkpoint();
+        assert_eq!(db.changes(&before), None);
+    }
+}
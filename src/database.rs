@@ -1,30 +1,88 @@
 use libsql::replication::Replicated;
 use neon::prelude::*;
 use std::cell::RefCell;
+use std::path::Path;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::Duration;
+use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
+use tokio::time::Instant;
 use tracing::trace;
 
+use crate::bootstrap::bootstrap_replica;
+use crate::changes::{convert_changes, read_changes, SyncChanges, WalPosition};
 use crate::errors::{throw_database_closed_error, throw_libsql_error};
+use crate::migrate::{
+    convert_migrations, convert_report, migrate, throw_migration_error, Migration, MigrationError,
+    MigrationReport,
+};
+use crate::offline::{
+    check_conflict_policy, convert_pending_changes, convert_pull_stats, convert_push_stats, pull,
+    push, sync_offline, PendingChanges, PendingWrites, PullStats,
+};
 use crate::runtime;
+use crate::schema::{convert_schemas, read_schemas};
+use crate::slow_query::{Location, SlowQuery, SlowQueryLog};
+use crate::statement::Handles;
+use crate::statement_cache::StatementCache;
+use crate::stats::ExecutionStats;
+use crate::stmt_status::{self, ConnectionHandle};
+use crate::sync::{
+    block_on_with_progress, run_sync, sync_until, throw_sync_error, timeout_argument,
+    wait_for_replication_index, DelegatedWrite, PeriodicSync, SharedDatabase, SyncError,
+    SyncEvents, SyncProgress, SyncStatus,
+};
+use crate::transfer::{import_file, parse_format, throw_transfer_error};
 use crate::Statement;
 
 pub(crate) struct Database {
-    db: Arc<Mutex<libsql::Database>>,
+    db: RefCell<Option<Arc<SharedDatabase>>>,
     conn: RefCell<Option<Arc<Mutex<libsql::Connection>>>>,
+    /// The SQLite connection behind `conn`, if it is a local connection.
+    conn_handle: Option<ConnectionHandle>,
     default_safe_integers: RefCell<bool>,
+    statement_cache: Arc<Mutex<StatementCache>>,
+    handles: Arc<Mutex<Handles>>,
+    periodic_sync: RefCell<Option<PeriodicSync>>,
+    /// The period of the periodic sync, if it is enabled.
+    sync_period: RefCell<Option<Duration>>,
+    sync_paused: RefCell<bool>,
+    sync_events: Arc<SyncEvents>,
+    location: Location,
+    /// The local writes that have not been pushed, if the database executes
+    /// writes locally and pushes them to the primary on sync.
+    pending_writes: Option<Arc<PendingWrites>>,
+    slow_query_log: RefCell<Option<Arc<SlowQueryLog>>>,
+    sync_status: Arc<SyncStatus>,
 }
 
 impl Finalize for Database {}
 
 impl Database {
-    pub fn new(db: libsql::Database, conn: libsql::Connection) -> Self {
+    pub fn new(
+        db: libsql::Database,
+        conn: libsql::Connection,
+        conn_handle: Option<ConnectionHandle>,
+        location: Location,
+        offline: bool,
+        path: &str,
+    ) -> Self {
         Database {
-            db: Arc::new(Mutex::new(db)),
+            db: RefCell::new(Some(Arc::new(SharedDatabase::new(db)))),
             conn: RefCell::new(Some(Arc::new(Mutex::new(conn)))),
+            conn_handle,
             default_safe_integers: RefCell::new(false),
+            statement_cache: Arc::new(Mutex::new(StatementCache::new(0))),
+            handles: Arc::new(Mutex::new(Handles::default())),
+            periodic_sync: RefCell::new(None),
+            sync_period: RefCell::new(None),
+            sync_paused: RefCell::new(false),
+            sync_events: Arc::new(SyncEvents::default()),
+            location,
+            pending_writes: offline.then(|| Arc::new(PendingWrites::open(Path::new(path)))),
+            slow_query_log: RefCell::new(None),
+            sync_status: Arc::new(SyncStatus::new(path)),
         }
     }
 
@@ -34,11 +92,17 @@ impl Database {
         let auth_token = cx.argument::<JsString>(1)?.value(&mut cx);
         let encryption_cipher = cx.argument::<JsString>(2)?.value(&mut cx);
         let encryption_key = cx.argument::<JsString>(3)?.value(&mut cx);
+        let location = if is_remote_path(&db_path) {
+            Location::Remote
+        } else {
+            Location::Local
+        };
         let db = if is_remote_path(&db_path) {
             let version = version("remote");
             trace!("Opening remote database: {}", db_path);
             libsql::Database::open_remote_internal(db_path.clone(), auth_token, version)
         } else {
+            trace!("Opening local database: {}", db_path);
             let cipher = libsql::Cipher::from_str(&encryption_cipher).or_else(|err| {
                 throw_libsql_error(
                     &mut cx,
@@ -54,10 +118,19 @@ impl Database {
             rt.block_on(builder.build())
         }
         .or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let remote = matches!(location, Location::Remote);
+        if !remote {
+            stmt_status::register();
+        }
         let conn = db
             .connect()
             .or_else(|err| throw_libsql_error(&mut cx, err))?;
-        let db = Database::new(db, conn);
+        let conn_handle = if remote {
+            None
+        } else {
+            rt.block_on(ConnectionHandle::of(&conn))
+        };
+        let db = Database::new(db, conn, conn_handle, location, false, &db_path);
         Ok(cx.boxed(db))
     }
 
@@ -70,6 +143,7 @@ impl Database {
         let sync_period = cx.argument::<JsNumber>(5)?.value(&mut cx);
         let read_your_writes = cx.argument::<JsBoolean>(6)?.value(&mut cx);
         let offline = cx.argument::<JsBoolean>(7)?.value(&mut cx);
+        let bootstrap_from = cx.argument::<JsString>(8)?.value(&mut cx);
 
         let cipher = libsql::Cipher::from_str(&encryption_cipher).or_else(|err| {
             throw_libsql_error(
@@ -95,41 +169,152 @@ impl Database {
             db_path,
             sync_url
         );
+        if !bootstrap_from.is_empty() {
+            if offline {
+                return cx.throw_type_error("bootstrapFrom is not supported for offline databases");
+            }
+            match bootstrap_replica(Path::new(&bootstrap_from), Path::new(&db_path)) {
+                Ok(true) => trace!("Bootstrapped {} from {}", db_path, bootstrap_from),
+                Ok(false) => {}
+                Err(err) => {
+                    return cx.throw_error(format!(
+                        "Failed to bootstrap the replica from {}: {}",
+                        bootstrap_from, err
+                    ))
+                }
+            }
+        }
         let rt = runtime(&mut cx)?;
         let result = if offline {
-            rt.block_on(libsql::Builder::new_synced_database(db_path, sync_url, sync_auth).build())
+            rt.block_on(
+                libsql::Builder::new_synced_database(db_path.clone(), sync_url, sync_auth).build(),
+            )
         } else {
             rt.block_on(async {
-                let mut builder = libsql::Builder::new_remote_replica(db_path, sync_url, sync_auth);
+                let mut builder =
+                    libsql::Builder::new_remote_replica(db_path.clone(), sync_url, sync_auth);
                 if let Some(encryption_config) = encryption_config {
                     builder = builder.encryption_config(encryption_config);
                 }
-                if let Some(sync_period) = sync_period {
-                    builder = builder.sync_interval(sync_period);
-                }
                 builder.build().await
             })
         };
         let db = result.or_else(|err| cx.throw_error(err.to_string()))?;
+        stmt_status::register();
         let conn = db
             .connect()
             .or_else(|err| throw_libsql_error(&mut cx, err))?;
-        let db = Database::new(db, conn);
+        let conn_handle = rt.block_on(ConnectionHandle::of(&conn));
+        // Offline databases execute writes locally and push them on sync.
+        let location = if offline {
+            Location::Local
+        } else {
+            Location::Replica
+        };
+        let db = Database::new(db, conn, conn_handle, location, offline, &db_path);
+        if !offline {
+            db.sync_period.replace(sync_period);
+            db.restart_periodic_sync(rt);
+        }
         Ok(cx.boxed(db))
     }
 
+    /// Stops the periodic sync, if it is running, and starts it again with
+    /// the current period unless it is disabled or paused.
+    fn restart_periodic_sync(&self, rt: &Runtime) {
+        if let Some(periodic_sync) = self.periodic_sync.take() {
+            periodic_sync.stop();
+        }
+        if *self.sync_paused.borrow() {
+            return;
+        }
+        let (Some(period), Some(db)) = (*self.sync_period.borrow(), self.get_db()) else {
+            return;
+        };
+        let periodic_sync = PeriodicSync::start(
+            rt,
+            Arc::downgrade(&db),
+            period,
+            self.sync_status.clone(),
+            self.sync_events.clone(),
+        );
+        self.periodic_sync.replace(Some(periodic_sync));
+    }
+
+    pub fn js_set_sync_listener(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let listener = cx.argument_opt(0);
+        let listener = match listener {
+            Some(listener) if listener.is_a::<JsFunction, _>(&mut cx) => {
+                Some(listener.downcast_or_throw::<JsFunction, _>(&mut cx)?)
+            }
+            _ => None,
+        };
+        db.sync_events.set_listener(&mut cx, listener);
+        Ok(cx.undefined())
+    }
+
+    /// Throws unless the database is an open embedded replica, which is the
+    /// only kind of database that supports periodic sync.
+    fn check_periodic_sync(&self, cx: &mut FunctionContext) -> NeonResult<()> {
+        if !matches!(self.location, Location::Replica) {
+            return cx.throw_error("Periodic sync is only supported by embedded replicas");
+        }
+        if self.get_db().is_none() {
+            throw_database_closed_error(cx)?;
+        }
+        Ok(())
+    }
+
+    pub fn js_pause_sync(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        db.check_periodic_sync(&mut cx)?;
+        db.sync_paused.replace(true);
+        if let Some(periodic_sync) = db.periodic_sync.take() {
+            periodic_sync.stop();
+        }
+        Ok(cx.undefined())
+    }
+
+    pub fn js_resume_sync(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        db.check_periodic_sync(&mut cx)?;
+        let rt = runtime(&mut cx)?;
+        db.sync_paused.replace(false);
+        db.restart_periodic_sync(rt);
+        Ok(cx.undefined())
+    }
+
+    pub fn js_set_sync_period(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let period = cx.argument::<JsNumber>(0)?.value(&mut cx);
+        if period.is_nan() || period < 0.0 {
+            return cx.throw_range_error("The sync period must be a non-negative number");
+        }
+        db.check_periodic_sync(&mut cx)?;
+        let rt = runtime(&mut cx)?;
+        let period = (period > 0.0).then(|| Duration::from_secs_f64(period));
+        db.sync_period.replace(period);
+        db.restart_periodic_sync(rt);
+        Ok(cx.undefined())
+    }
+
     pub fn js_in_transaction(mut cx: FunctionContext) -> JsResult<JsValue> {
         let db = cx.argument::<JsBox<Database>>(0)?;
-        let conn = db.conn.borrow();
-        let conn = conn.as_ref().unwrap().clone();
+        let conn = match db.get_conn(&mut cx) {
+            Some(conn) => conn,
+            None => throw_database_closed_error(&mut cx)?,
+        };
         let result = !conn.blocking_lock().is_autocommit();
         Ok(cx.boolean(result).upcast())
     }
 
     pub fn js_interrupt(mut cx: FunctionContext) -> JsResult<JsUndefined> {
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
-        let conn = db.conn.borrow();
-        let conn = conn.as_ref().unwrap().clone();
+        let conn = match db.get_conn(&mut cx) {
+            Some(conn) => conn,
+            None => throw_database_closed_error(&mut cx)?,
+        };
         conn.blocking_lock().interrupt().or_else(|err| {
             throw_libsql_error(&mut cx, err)?;
             Ok(())
@@ -138,60 +323,234 @@ impl Database {
     }
 
     pub fn js_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
-        // the conn will be closed when the last statement in discarded. In most situation that
-        // means immediately because you don't want to hold on a statement for longer that its
-        // database is alive.
+        // Statements and rows keep the underlying connection open, so they are finalized
+        // before the connection and the database are dropped.
         trace!("Closing database");
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        if let Some(periodic_sync) = db.periodic_sync.take() {
+            periodic_sync.stop();
+        }
+        db.sync_events.set_listener(&mut cx, None);
+        db.statement_cache.blocking_lock().clear();
+        db.handles.blocking_lock().finalize();
         db.conn.replace(None);
+        db.db.replace(None);
         Ok(cx.undefined())
     }
 
     pub fn js_max_write_replication_index(mut cx: FunctionContext) -> JsResult<JsValue> {
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
-        let replication_index = db.db.blocking_lock().max_write_replication_index();
-        Ok(if let Some(ri) = replication_index {
-            cx.number(ri as f64).upcast()
-        } else {
-            cx.undefined().upcast()
-        })
+        let raw_db = match db.get_db() {
+            Some(db) => db,
+            None => throw_database_closed_error(&mut cx)?,
+        };
+        let replication_index = raw_db.max_write_replication_index();
+        Ok(convert_replication_index(&mut cx, replication_index))
     }
 
     pub fn js_sync_sync(mut cx: FunctionContext) -> JsResult<JsObject> {
         trace!("Synchronizing database (sync)");
-        let db: Handle<'_, JsBox<Database>> = cx.this()?;
-        let db = db.db.clone();
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => throw_database_closed_error(&mut cx)?,
+        };
+        let progress = match cx.argument_opt(0) {
+            Some(callback) if callback.is_a::<JsFunction, _>(&mut cx) => {
+                Some(callback.downcast_or_throw::<JsFunction, _>(&mut cx)?)
+            }
+            _ => None,
+        };
+        let (conn, pending_writes) = this.sync_options(&mut cx, 1)?;
+        let sync_status = this.sync_status.clone();
         let rt = runtime(&mut cx)?;
-        let rep = rt
-            .block_on(async move {
-                let db = db.lock().await;
-                db.sync().await
-            })
-            .or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let sync = sync_database(db, conn, sync_status.clone(), pending_writes);
+        let result = match progress {
+            Some(callback) => block_on_with_progress(&mut cx, rt, sync_status, callback, sync)?,
+            None => rt.block_on(sync),
+        };
+        let result = result.or_else(|err| throw_sync_error(&mut cx, err))?;
 
-        let obj = convert_replicated_to_object(&mut cx, &rep)?;
+        let obj = convert_sync_result(&mut cx, &result)?;
 
         Ok(obj)
     }
 
     pub fn js_sync_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
         trace!("Synchronizing database (async)");
-        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        let progress = match cx.argument_opt(0) {
+            Some(callback) if callback.is_a::<JsFunction, _>(&mut cx) => {
+                let callback = callback.downcast_or_throw::<JsFunction, _>(&mut cx)?;
+                Some(SyncProgress::new(&mut cx, callback))
+            }
+            _ => None,
+        };
+        let (conn, pending_writes) = this.sync_options(&mut cx, 1)?;
+        let sync_status = this.sync_status.clone();
         let (deferred, promise) = cx.promise();
         let channel = cx.channel();
-        let db = db.db.clone();
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
         let rt = runtime(&mut cx)?;
         rt.spawn(async move {
-            let result = db.lock().await.sync().await;
+            let sync = sync_database(db, conn, sync_status.clone(), pending_writes);
+            let result = match progress {
+                Some(progress) => progress.track(sync_status, sync).await,
+                None => sync.await,
+            };
             match result {
-                Ok(rep) => {
+                Ok(result) => {
                     deferred.settle_with(&channel, move |mut cx| {
-                        convert_replicated_to_object(&mut cx, &rep)
+                        convert_sync_result(&mut cx, &result)
                     });
                 }
                 Err(err) => {
                     deferred.settle_with(&channel, |mut cx| {
-                        throw_libsql_error(&mut cx, err)?;
+                        throw_sync_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
+    /// Parses the `changes` and `onConflict` options of `sync()`, passed as
+    /// the arguments at `index` and `index + 1`.
+    fn sync_options(
+        &self,
+        cx: &mut FunctionContext,
+        index: usize,
+    ) -> NeonResult<(
+        Option<Arc<Mutex<libsql::Connection>>>,
+        Option<Arc<PendingWrites>>,
+    )> {
+        let changes = match cx.argument_opt(index) {
+            Some(changes) if changes.is_a::<JsBoolean, _>(cx) => {
+                changes.downcast_or_throw::<JsBoolean, _>(cx)?.value(cx)
+            }
+            _ => false,
+        };
+        let conn = if changes { self.get_conn(cx) } else { None };
+        check_conflict_policy(cx, index + 1)?;
+        Ok((conn, self.pending_writes.clone()))
+    }
+
+    pub fn js_push_sync(mut cx: FunctionContext) -> JsResult<JsObject> {
+        trace!("Pushing local changes (sync)");
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        check_conflict_policy(&mut cx, 0)?;
+        let pending_writes = this.check_push(&mut cx)?;
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => throw_database_closed_error(&mut cx)?,
+        };
+        let sync_status = this.sync_status.clone();
+        let rt = runtime(&mut cx)?;
+        let stats = rt
+            .block_on(push(db, sync_status, pending_writes))
+            .or_else(|err| throw_sync_error(&mut cx, err))?;
+        convert_push_stats(&mut cx, &stats)
+    }
+
+    pub fn js_push_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        trace!("Pushing local changes (async)");
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        check_conflict_policy(&mut cx, 0)?;
+        let pending_writes = this.check_push(&mut cx)?;
+        let sync_status = this.sync_status.clone();
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
+        let rt = runtime(&mut cx)?;
+        rt.spawn(async move {
+            match push(db, sync_status, pending_writes).await {
+                Ok(stats) => {
+                    deferred
+                        .settle_with(&channel, move |mut cx| convert_push_stats(&mut cx, &stats));
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_sync_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
+    /// Throws unless the database has local writes to push.
+    fn check_push(&self, cx: &mut FunctionContext) -> NeonResult<Arc<PendingWrites>> {
+        match &self.pending_writes {
+            Some(pending_writes) => Ok(pending_writes.clone()),
+            None => cx.throw_error("push() requires a database opened with the offline option"),
+        }
+    }
+
+    pub fn js_pull_sync(mut cx: FunctionContext) -> JsResult<JsObject> {
+        trace!("Pulling remote changes (sync)");
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        this.check_pull(&mut cx)?;
+        let (db, conn) = match (this.get_db(), this.get_conn(&mut cx)) {
+            (Some(db), Some(conn)) => (db, conn),
+            _ => throw_database_closed_error(&mut cx)?,
+        };
+        let sync_status = this.sync_status.clone();
+        let pending_writes = this.pending_writes.clone();
+        let rt = runtime(&mut cx)?;
+        let stats = rt
+            .block_on(pull_database(db, conn, sync_status, pending_writes))
+            .or_else(|err| throw_sync_error(&mut cx, err))?;
+        convert_pull_stats(&mut cx, &stats)
+    }
+
+    pub fn js_pull_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        trace!("Pulling remote changes (async)");
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        this.check_pull(&mut cx)?;
+        let sync_status = this.sync_status.clone();
+        let pending_writes = this.pending_writes.clone();
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let (db, conn) = match (this.get_db(), this.get_conn(&mut cx)) {
+            (Some(db), Some(conn)) => (db, conn),
+            _ => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
+        let rt = runtime(&mut cx)?;
+        rt.spawn(async move {
+            match pull_database(db, conn, sync_status, pending_writes).await {
+                Ok(stats) => {
+                    deferred
+                        .settle_with(&channel, move |mut cx| convert_pull_stats(&mut cx, &stats));
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_sync_error(&mut cx, err)?;
                         Ok(cx.undefined())
                     });
                 }
@@ -200,18 +559,31 @@ impl Database {
         Ok(promise)
     }
 
+    /// Throws unless the database pulls frames from a primary, which embedded
+    /// replicas and offline databases do.
+    fn check_pull(&self, cx: &mut FunctionContext) -> NeonResult<()> {
+        if self.pending_writes.is_some() || matches!(self.location, Location::Replica) {
+            return Ok(());
+        }
+        cx.throw_error(
+            "pull() requires an embedded replica or a database opened with the offline option",
+        )
+    }
+
     pub fn js_sync_until_sync(mut cx: FunctionContext) -> JsResult<JsObject> {
         trace!("Synchronizing database until given replication index (sync)");
-        let db: Handle<'_, JsBox<Database>> = cx.this()?;
-        let db = db.db.clone();
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => throw_database_closed_error(&mut cx)?,
+        };
         let replication_index = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+        let timeout = timeout_argument(&mut cx, 1)?;
+        let sync_status = this.sync_status.clone();
         let rt = runtime(&mut cx)?;
         let rep = rt
-            .block_on(async move {
-                let db = db.lock().await;
-                db.sync_until(replication_index).await
-            })
-            .or_else(|err| throw_libsql_error(&mut cx, err))?;
+            .block_on(sync_until(db, sync_status, replication_index, timeout))
+            .or_else(|err| throw_sync_error(&mut cx, err))?;
 
         let obj = convert_replicated_to_object(&mut cx, &rep)?;
 
@@ -220,14 +592,25 @@ impl Database {
 
     pub fn js_sync_until_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
         trace!("Synchronizing database until given replication index (async)");
-        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
         let replication_index = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+        let timeout = timeout_argument(&mut cx, 1)?;
+        let sync_status = this.sync_status.clone();
         let (deferred, promise) = cx.promise();
         let channel = cx.channel();
-        let db = db.db.clone();
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
         let rt = runtime(&mut cx)?;
         rt.spawn(async move {
-            let result = db.lock().await.sync_until(replication_index).await;
+            let result = sync_until(db, sync_status, replication_index, timeout).await;
             match result {
                 Ok(rep) => {
                     deferred.settle_with(&channel, move |mut cx| {
@@ -236,7 +619,7 @@ impl Database {
                 }
                 Err(err) => {
                     deferred.settle_with(&channel, |mut cx| {
-                        throw_libsql_error(&mut cx, err)?;
+                        throw_sync_error(&mut cx, err)?;
                         Ok(cx.undefined())
                     });
                 }
@@ -245,7 +628,81 @@ impl Database {
         Ok(promise)
     }
 
-    pub fn js_exec_sync(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    pub fn js_wait_for_replication_index_sync(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        trace!("Waiting for replication index (sync)");
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => throw_database_closed_error(&mut cx)?,
+        };
+        let replication_index = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+        let timeout = timeout_argument(&mut cx, 1)?;
+        let sync_status = this.sync_status.clone();
+        let rt = runtime(&mut cx)?;
+        rt.block_on(wait_for_replication_index(
+            db,
+            sync_status,
+            replication_index,
+            timeout,
+        ))
+        .or_else(|err| throw_sync_error(&mut cx, err))?;
+        Ok(cx.undefined())
+    }
+
+    pub fn js_wait_for_replication_index_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        trace!("Waiting for replication index (async)");
+        let this: Handle<'_, JsBox<Database>> = cx.this()?;
+        let replication_index = cx.argument::<JsNumber>(0)?.value(&mut cx) as u64;
+        let timeout = timeout_argument(&mut cx, 1)?;
+        let sync_status = this.sync_status.clone();
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let db = match this.get_db() {
+            Some(db) => db,
+            None => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
+        let rt = runtime(&mut cx)?;
+        rt.spawn(async move {
+            let result =
+                wait_for_replication_index(db, sync_status, replication_index, timeout).await;
+            deferred.settle_with(&channel, move |mut cx| {
+                if let Err(err) = result {
+                    throw_sync_error(&mut cx, err)?;
+                }
+                Ok(cx.undefined())
+            });
+        });
+        Ok(promise)
+    }
+
+    pub fn js_pending_changes(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let changes = match &db.pending_writes {
+            Some(pending_writes) => PendingChanges::read(db.sync_status.path(), pending_writes)
+                .or_else(|err| cx.throw_error(err.to_string()))?,
+            // Other databases don't keep local writes for later.
+            None => PendingChanges::default(),
+        };
+        convert_pending_changes(&mut cx, &changes)
+    }
+
+    pub fn js_sync_status(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let periodic_sync = db
+            .periodic_sync
+            .borrow()
+            .as_ref()
+            .is_some_and(|periodic_sync| periodic_sync.is_running());
+        db.sync_status.to_object(&mut cx, periodic_sync)
+    }
+
+    pub fn js_exec_sync(mut cx: FunctionContext) -> JsResult<JsValue> {
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
         let sql = cx.argument::<JsString>(0)?.value(&mut cx);
         trace!("Executing SQL statement (sync): {}", sql);
@@ -253,10 +710,17 @@ impl Database {
             Some(conn) => conn,
             None => throw_database_closed_error(&mut cx)?,
         };
+        let slow_query = db.slow_query(&sql);
         let rt = runtime(&mut cx)?;
-        let result = rt.block_on(async { conn.lock().await.execute_batch(&sql).await });
-        result.or_else(|err| throw_libsql_error(&mut cx, err))?;
-        Ok(cx.undefined())
+        let initial = Instant::now();
+        let pending_writes = db.pending_writes.clone();
+        let replica = db.replica();
+        let result = rt.block_on(exec(&conn, &sql, pending_writes, replica));
+        if let Some(slow_query) = slow_query {
+            slow_query.finish(Instant::now() - initial);
+        }
+        let replication_index = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        Ok(convert_replication_index(&mut cx, replication_index))
     }
 
     pub fn js_exec_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
@@ -275,11 +739,137 @@ impl Database {
                 return Ok(promise);
             }
         };
+        let slow_query = db.slow_query(&sql);
+        let pending_writes = db.pending_writes.clone();
+        let replica = db.replica();
+        let rt = runtime(&mut cx)?;
+        rt.spawn(async move {
+            let initial = Instant::now();
+            let result = exec(&conn, &sql, pending_writes, replica).await;
+            if let Some(slow_query) = slow_query {
+                slow_query.finish(Instant::now() - initial);
+            }
+            match result {
+                Ok(replication_index) => {
+                    deferred.settle_with(&channel, move |mut cx| {
+                        Ok(convert_replication_index(&mut cx, replication_index))
+                    });
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_libsql_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
+    pub fn js_migrate_sync(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let migrations = cx.argument::<JsArray>(0)?;
+        let migrations = convert_migrations(&mut cx, migrations)?;
+        let table = cx.argument::<JsString>(1)?.value(&mut cx);
+        let target = cx.argument::<JsString>(2)?.value(&mut cx);
+        trace!("Running migrations (sync)");
+        let (raw_db, conn) = match (db.get_db(), db.get_conn(&mut cx)) {
+            (Some(raw_db), Some(conn)) => (raw_db, conn),
+            _ => throw_database_closed_error(&mut cx)?,
+        };
+        let replica = matches!(db.location, Location::Replica).then(|| db.sync_status.clone());
+        let rt = runtime(&mut cx)?;
+        let before = db.pending_writes.as_ref().map(|w| w.wal_size());
+        let result = rt.block_on(async {
+            let target = Some(target.as_str()).filter(|target| !target.is_empty());
+            migrate_replica(&raw_db, &conn, replica, &table, &migrations, target).await
+        });
+        if let (Some(pending_writes), Some(before)) = (&db.pending_writes, before) {
+            pending_writes.record_appended(before);
+        }
+        let report = result.or_else(|err| throw_migration_error(&mut cx, err))?;
+        convert_report(&mut cx, &report)
+    }
+
+    pub fn js_migrate_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let migrations = cx.argument::<JsArray>(0)?;
+        let migrations = convert_migrations(&mut cx, migrations)?;
+        let table = cx.argument::<JsString>(1)?.value(&mut cx);
+        let target = cx.argument::<JsString>(2)?.value(&mut cx);
+        trace!("Running migrations (async)");
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let (raw_db, conn) = match (db.get_db(), db.get_conn(&mut cx)) {
+            (Some(raw_db), Some(conn)) => (raw_db, conn),
+            _ => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
+        let replica = matches!(db.location, Location::Replica).then(|| db.sync_status.clone());
+        let pending_writes = db.pending_writes.clone();
+        let rt = runtime(&mut cx)?;
+        rt.spawn(async move {
+            let target = Some(target.as_str()).filter(|target| !target.is_empty());
+            let before = pending_writes.as_ref().map(|w| w.wal_size());
+            let result =
+                migrate_replica(&raw_db, &conn, replica, &table, &migrations, target).await;
+            if let (Some(pending_writes), Some(before)) = (&pending_writes, before) {
+                pending_writes.record_appended(before);
+            }
+            match result {
+                Ok(report) => {
+                    deferred.settle_with(&channel, move |mut cx| convert_report(&mut cx, &report));
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_migration_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
+    pub fn js_schema_sync(mut cx: FunctionContext) -> JsResult<JsArray> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        trace!("Reading schema (sync)");
+        let conn = match db.get_conn(&mut cx) {
+            Some(conn) => conn,
+            None => throw_database_closed_error(&mut cx)?,
+        };
+        let rt = runtime(&mut cx)?;
+        let result = rt.block_on(async { read_schemas(&*conn.lock().await).await });
+        let schemas = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        convert_schemas(&mut cx, &schemas)
+    }
+
+    pub fn js_schema_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        trace!("Reading schema (async)");
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let conn = match db.get_conn(&mut cx) {
+            Some(conn) => conn,
+            None => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
         let rt = runtime(&mut cx)?;
         rt.spawn(async move {
-            match conn.lock().await.execute_batch(&sql).await {
-                Ok(_) => {
-                    deferred.settle_with(&channel, |mut cx| Ok(cx.undefined()));
+            match read_schemas(&*conn.lock().await).await {
+                Ok(schemas) => {
+                    deferred
+                        .settle_with(&channel, move |mut cx| convert_schemas(&mut cx, &schemas));
                 }
                 Err(err) => {
                     deferred.settle_with(&channel, |mut cx| {
@@ -292,6 +882,81 @@ impl Database {
         Ok(promise)
     }
 
+    pub fn js_import_file_sync(mut cx: FunctionContext) -> JsResult<JsNumber> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let table = cx.argument::<JsString>(0)?.value(&mut cx);
+        let path = cx.argument::<JsString>(1)?.value(&mut cx);
+        let format = cx.argument::<JsString>(2)?.value(&mut cx);
+        let format = parse_format(&mut cx, &format)?;
+        let columns = convert_string_array(&mut cx, 3)?;
+        let batch_size = cx.argument::<JsNumber>(4)?.value(&mut cx) as usize;
+        trace!("Importing file (sync): {} into {}", path, table);
+        let conn = match db.get_conn(&mut cx) {
+            Some(conn) => conn,
+            None => throw_database_closed_error(&mut cx)?,
+        };
+        let pending_writes = db.pending_writes.clone();
+        let rt = runtime(&mut cx)?;
+        let result = rt.block_on(async {
+            let conn = conn.lock().await;
+            let before = pending_writes.as_ref().map(|w| w.wal_size());
+            let result =
+                import_file(&conn, &table, &path, format, columns, batch_size.max(1)).await;
+            if let (Some(pending_writes), Some(before)) = (&pending_writes, before) {
+                pending_writes.record_appended(before);
+            }
+            result
+        });
+        let count = result.or_else(|err| throw_transfer_error(&mut cx, err))?;
+        Ok(cx.number(count as f64))
+    }
+
+    pub fn js_import_file_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let table = cx.argument::<JsString>(0)?.value(&mut cx);
+        let path = cx.argument::<JsString>(1)?.value(&mut cx);
+        let format = cx.argument::<JsString>(2)?.value(&mut cx);
+        let format = parse_format(&mut cx, &format)?;
+        let columns = convert_string_array(&mut cx, 3)?;
+        let batch_size = cx.argument::<JsNumber>(4)?.value(&mut cx) as usize;
+        trace!("Importing file (async): {} into {}", path, table);
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let conn = match db.get_conn(&mut cx) {
+            Some(conn) => conn,
+            None => {
+                deferred.settle_with(&channel, |mut cx| {
+                    throw_database_closed_error(&mut cx)?;
+                    Ok(cx.undefined())
+                });
+                return Ok(promise);
+            }
+        };
+        let pending_writes = db.pending_writes.clone();
+        let rt = runtime(&mut cx)?;
+        rt.spawn(async move {
+            let conn = conn.lock().await;
+            let before = pending_writes.as_ref().map(|w| w.wal_size());
+            let result =
+                import_file(&conn, &table, &path, format, columns, batch_size.max(1)).await;
+            if let (Some(pending_writes), Some(before)) = (&pending_writes, before) {
+                pending_writes.record_appended(before);
+            }
+            match result {
+                Ok(count) => {
+                    deferred.settle_with(&channel, move |mut cx| Ok(cx.number(count as f64)));
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_transfer_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
     pub fn js_prepare_sync(mut cx: FunctionContext) -> JsResult<JsBox<Statement>> {
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
         let sql = cx.argument::<JsString>(0)?.value(&mut cx);
@@ -301,14 +966,35 @@ impl Database {
             None => throw_database_closed_error(&mut cx)?,
         };
         let rt = runtime(&mut cx)?;
-        let result = rt.block_on(async { conn.lock().await.prepare(&sql).await });
-        let stmt = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
-        let stmt = Arc::new(Mutex::new(stmt));
+        let cached = db.statement_cache.blocking_lock().take(&sql);
+        let (stmt, status) = match cached {
+            Some(cached) => cached,
+            None => {
+                let conn_handle = db.conn_handle;
+                let result = rt.block_on(async {
+                    stmt_status::prepare(&*conn.lock().await, conn_handle, &sql).await
+                });
+                let (stmt, status) = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+                let stmt = Arc::new(Mutex::new(Some(stmt)));
+                db.handles.blocking_lock().add_statement(&stmt);
+                (stmt, status)
+            }
+        };
         let stmt = Statement {
-            conn: conn.clone(),
+            sql,
+            conn: Arc::downgrade(&conn),
             stmt,
+            status,
+            in_use: Arc::new(()),
+            cache: Arc::downgrade(&db.statement_cache),
+            handles: db.handles.clone(),
+            finalized: RefCell::new(false),
             raw: RefCell::new(false),
             safe_ints: RefCell::new(*db.default_safe_integers.borrow()),
+            stats: Arc::new(ExecutionStats::default()),
+            slow_query_log: db.slow_query_log.borrow().clone(),
+            pending_writes: db.pending_writes.clone(),
+            replica: db.replica_db(),
         };
         Ok(cx.boxed(stmt))
     }
@@ -331,15 +1017,42 @@ impl Database {
                 return Ok(promise);
             }
         };
+        let statement_cache = db.statement_cache.clone();
+        let conn_handle = db.conn_handle;
+        let handles = db.handles.clone();
+        let slow_query_log = db.slow_query_log.borrow().clone();
+        let pending_writes = db.pending_writes.clone();
+        let replica = db.replica_db();
         rt.spawn(async move {
-            match conn.lock().await.prepare(&sql).await {
-                Ok(stmt) => {
-                    let stmt = Arc::new(Mutex::new(stmt));
+            let cached = statement_cache.lock().await.take(&sql);
+            let result = match cached {
+                Some(cached) => Ok(cached),
+                None => match stmt_status::prepare(&*conn.lock().await, conn_handle, &sql).await {
+                    Ok((stmt, status)) => {
+                        let stmt = Arc::new(Mutex::new(Some(stmt)));
+                        handles.lock().await.add_statement(&stmt);
+                        Ok((stmt, status))
+                    }
+                    Err(err) => Err(err),
+                },
+            };
+            match result {
+                Ok((stmt, status)) => {
                     let stmt = Statement {
-                        conn: conn.clone(),
+                        sql,
+                        conn: Arc::downgrade(&conn),
                         stmt,
+                        status,
+                        in_use: Arc::new(()),
+                        cache: Arc::downgrade(&statement_cache),
+                        handles,
+                        finalized: RefCell::new(false),
                         raw: RefCell::new(false),
                         safe_ints: RefCell::new(safe_ints),
+                        stats: Arc::new(ExecutionStats::default()),
+                        slow_query_log,
+                        pending_writes,
+                        replica,
                     };
                     deferred.settle_with(&channel, |mut cx| Ok(cx.boxed(stmt)));
                 }
@@ -354,6 +1067,56 @@ impl Database {
         Ok(promise)
     }
 
+    pub fn js_set_statement_cache_size(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let size = cx.argument::<JsNumber>(0)?.value(&mut cx);
+        if size.is_nan() || size < 0.0 || size.fract() != 0.0 {
+            return cx.throw_range_error("The statement cache size must be a non-negative integer");
+        }
+        db.statement_cache
+            .blocking_lock()
+            .set_capacity(size as usize);
+        Ok(cx.undefined())
+    }
+
+    pub fn js_statement_cache_stats(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let (hits, misses, size, capacity) = {
+            let cache = db.statement_cache.blocking_lock();
+            (cache.hits(), cache.misses(), cache.size(), cache.capacity())
+        };
+        let stats = cx.empty_object();
+        let hits = cx.number(hits as f64);
+        stats.set(&mut cx, "hits", hits)?;
+        let misses = cx.number(misses as f64);
+        stats.set(&mut cx, "misses", misses)?;
+        let size = cx.number(size as f64);
+        stats.set(&mut cx, "size", size)?;
+        let capacity = cx.number(capacity as f64);
+        stats.set(&mut cx, "capacity", capacity)?;
+        Ok(stats)
+    }
+
+    pub fn js_set_slow_query_log(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let db: Handle<'_, JsBox<Database>> = cx.this()?;
+        let threshold = cx.argument::<JsNumber>(0)?.value(&mut cx);
+        let callback = cx.argument::<JsFunction>(1)?;
+        if threshold.is_nan() || threshold < 0.0 {
+            return cx.throw_range_error("The slow query threshold must be a non-negative number");
+        }
+        let threshold = Duration::from_secs_f64(threshold / 1000.0);
+        let log = SlowQueryLog::new(&mut cx, threshold, db.location, callback);
+        db.slow_query_log.replace(Some(Arc::new(log)));
+        Ok(cx.undefined())
+    }
+
+    fn slow_query(&self, sql: &str) -> Option<SlowQuery> {
+        self.slow_query_log
+            .borrow()
+            .as_ref()
+            .map(|log| log.query(sql, &libsql::params::Params::None))
+    }
+
     pub fn js_default_safe_integers(mut cx: FunctionContext) -> JsResult<JsNull> {
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
         let toggle = cx.argument::<JsBoolean>(0)?;
@@ -369,9 +1132,12 @@ impl Database {
     pub fn js_load_extension(mut cx: FunctionContext) -> JsResult<JsUndefined> {
         let db: Handle<'_, JsBox<Database>> = cx.this()?;
         let extension = cx.argument::<JsString>(0)?.value(&mut cx);
-        let entry_point: Option<&str> = match cx.argument_opt(1) {
-            Some(_arg) => todo!(),
-            None => None,
+        let entry_point = match cx.argument_opt(1) {
+            Some(arg) if !arg.is_a::<JsUndefined, _>(&mut cx) => {
+                let arg = arg.downcast_or_throw::<JsString, _>(&mut cx)?;
+                Some(arg.value(&mut cx))
+            }
+            _ => None,
         };
         trace!("Loading extension: {}", extension);
         let conn = match db.get_conn(&mut cx) {
@@ -382,7 +1148,7 @@ impl Database {
         if let Err(err) = conn.load_extension_enable() {
             throw_libsql_error(&mut cx, err)?;
         }
-        if let Err(err) = conn.load_extension(&extension, entry_point) {
+        if let Err(err) = conn.load_extension(&extension, entry_point.as_deref()) {
             let _ = conn.load_extension_disable();
             throw_libsql_error(&mut cx, err)?;
         }
@@ -396,6 +1162,178 @@ impl Database {
         let conn = self.conn.borrow();
         conn.as_ref().map(|conn| conn.clone())
     }
+
+    fn get_db(&self) -> Option<Arc<SharedDatabase>> {
+        let db = self.db.borrow();
+        db.as_ref().map(|db| db.clone())
+    }
+
+    /// Returns the database of an embedded replica for statements, which
+    /// don't keep it open.
+    fn replica_db(&self) -> Option<Weak<SharedDatabase>> {
+        self.replica().map(|db| Arc::downgrade(&db))
+    }
+
+    /// Returns the database of an embedded replica, which delegates writes
+    /// to the primary.
+    fn replica(&self) -> Option<Arc<SharedDatabase>> {
+        if !matches!(self.location, Location::Replica) {
+            return None;
+        }
+        self.get_db()
+    }
+}
+
+/// Executes the statements in `sql` and returns the replication index of
+/// their writes if an embedded replica delegated them to the primary.
+async fn exec(
+    conn: &Mutex<libsql::Connection>,
+    sql: &str,
+    pending_writes: Option<Arc<PendingWrites>>,
+    replica: Option<Arc<SharedDatabase>>,
+) -> libsql::Result<Option<u64>> {
+    let conn = conn.lock().await;
+    let before = pending_writes.as_ref().map(|w| w.wal_size());
+    let delegated_write = replica.map(DelegatedWrite::start);
+    let result = conn.execute_batch(sql).await;
+    // The statements before a failing one stay executed.
+    if let (Some(pending_writes), Some(before)) = (&pending_writes, before) {
+        pending_writes.record_appended(before);
+    }
+    result?;
+    // Writes in a transaction only reach the primary on commit.
+    Ok(delegated_write
+        .filter(|_| conn.is_autocommit())
+        .and_then(DelegatedWrite::finish))
+}
+
+fn convert_replication_index<'a>(
+    cx: &mut impl Context<'a>,
+    replication_index: Option<u64>,
+) -> Handle<'a, JsValue> {
+    match replication_index {
+        Some(replication_index) => cx.number(replication_index as f64).upcast(),
+        None => cx.undefined().upcast(),
+    }
+}
+
+/// The outcome of `sync()`.
+struct SyncResult {
+    rep: Replicated,
+    changes: Option<Option<SyncChanges>>,
+    /// The numbers of pushed and pulled frames of an offline database.
+    frames: Option<(u64, Option<u64>)>,
+}
+
+/// Synchronizes the database and, if a connection is given, reads the
+/// changes that the sync applied to the local database.
+async fn sync_database(
+    db: Arc<SharedDatabase>,
+    conn: Option<Arc<Mutex<libsql::Connection>>>,
+    sync_status: Arc<SyncStatus>,
+    pending_writes: Option<Arc<PendingWrites>>,
+) -> Result<SyncResult, SyncError> {
+    // The connection stays locked from reading the starting position of the
+    // WAL until the changes are read, so that its own writes aren't reported
+    // as changes applied by the sync.
+    let conn = match &conn {
+        Some(conn) => Some(conn.lock().await),
+        None => None,
+    };
+    let before = conn.as_ref().map(|_| WalPosition::read(sync_status.path()));
+    let (rep, frames) = match pending_writes {
+        Some(pending_writes) => {
+            let sync = sync_offline(db, sync_status.clone(), pending_writes).await?;
+            (sync.rep, Some((sync.pushed, sync.pulled)))
+        }
+        None => (run_sync(db, sync_status.clone()).await?, None),
+    };
+    let changes = match (conn, before) {
+        (Some(conn), Some(before)) => Some(read_changes(&conn, sync_status.path(), before).await),
+        _ => None,
+    };
+    Ok(SyncResult {
+        rep,
+        changes,
+        frames,
+    })
+}
+
+/// Pulls the frames of the primary into an embedded replica or an offline
+/// database. Embedded replicas delegate writes to the primary, so they have
+/// no local frames to push and every sync is a pull.
+async fn pull_database(
+    db: Arc<SharedDatabase>,
+    conn: Arc<Mutex<libsql::Connection>>,
+    sync_status: Arc<SyncStatus>,
+    pending_writes: Option<Arc<PendingWrites>>,
+) -> Result<PullStats, SyncError> {
+    match pending_writes {
+        Some(pending_writes) => pull(db, conn, sync_status, pending_writes).await,
+        None => {
+            let rep = run_sync(db, sync_status).await?;
+            Ok(PullStats {
+                pulled: Some(rep.frames_synced() as u64),
+                frame_no: rep.frame_no(),
+                pending_frames: 0,
+            })
+        }
+    }
+}
+
+fn convert_sync_result<'a>(
+    cx: &mut impl Context<'a>,
+    result: &SyncResult,
+) -> JsResult<'a, JsObject> {
+    let obj = convert_replicated_to_object(cx, &result.rep)?;
+    if let Some(changes) = &result.changes {
+        let changes = convert_changes(cx, changes.as_ref())?;
+        obj.set(cx, "changes", changes)?;
+    }
+    if let Some((pushed, pulled)) = result.frames {
+        let pushed = cx.number(pushed as f64);
+        obj.set(cx, "pushed", pushed)?;
+        let pulled: Handle<'_, JsValue> = match pulled {
+            Some(pulled) => cx.number(pulled as f64).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(cx, "pulled", pulled)?;
+    }
+    Ok(obj)
+}
+
+/// Runs the migrations, synchronizing an embedded replica before, so that
+/// migrations applied by other replicas are seen, and after, so that the
+/// migrations, whose writes are delegated to the primary, are visible locally
+/// when `migrate()` returns.
+async fn migrate_replica(
+    db: &SharedDatabase,
+    conn: &Mutex<libsql::Connection>,
+    replica: Option<Arc<SyncStatus>>,
+    table: &str,
+    migrations: &[Migration],
+    target: Option<&str>,
+) -> Result<MigrationReport, MigrationError> {
+    if let Some(sync_status) = &replica {
+        let db = db.lock().await;
+        sync_status.track(db.sync()).await?;
+    }
+    let report = migrate(&*conn.lock().await, table, migrations, target).await?;
+    if let Some(sync_status) = &replica {
+        let db = db.lock().await;
+        sync_status.track(db.sync()).await?;
+    }
+    Ok(report)
+}
+
+fn convert_string_array(cx: &mut FunctionContext, i: usize) -> NeonResult<Vec<String>> {
+    let arr = cx.argument::<JsArray>(i)?;
+    let mut result = Vec::with_capacity(arr.len(cx) as usize);
+    for idx in 0..arr.len(cx) {
+        let v: Handle<'_, JsString> = arr.get(cx, idx)?;
+        result.push(v.value(cx));
+    }
+    Ok(result)
 }
 
 fn is_remote_path(path: &str) -> bool {
@@ -407,7 +1345,7 @@ fn version(protocol: &str) -> String {
     format!("libsql-js-{protocol}-{ver}")
 }
 
-fn convert_replicated_to_object<'a>(
+pub(crate) fn convert_replicated_to_object<'a>(
     cx: &mut impl Context<'a>,
     rep: &Replicated,
 ) -> JsResult<'a, JsObject> {
@@ -1,4 +1,9 @@
-use neon::{context::Context, object::Object, result::NeonResult, types::JsError};
+use neon::{
+    context::Context,
+    object::Object,
+    result::{JsResult, NeonResult},
+    types::JsError,
+};
 
 pub fn throw_database_closed_error<'a, C: Context<'a>, T>(cx: &mut C) -> NeonResult<T> {
     let err = JsError::type_error(cx, "The database connection is not open")?;
@@ -6,25 +11,33 @@ pub fn throw_database_closed_error<'a, C: Context<'a>, T>(cx: &mut C) -> NeonRes
 }
 
 pub fn throw_libsql_error<'a, C: Context<'a>, T>(cx: &mut C, err: libsql::Error) -> NeonResult<T> {
+    let err = convert_libsql_error(cx, err)?;
+    cx.throw(err)
+}
+
+/// Converts a libSQL error into a JavaScript error without throwing it.
+pub fn convert_libsql_error<'a, C: Context<'a>>(
+    cx: &mut C,
+    err: libsql::Error,
+) -> JsResult<'a, JsError> {
     match err {
         libsql::Error::SqliteFailure(code, err) => {
             let err = err.to_string();
-            let err = JsError::error(cx, err).unwrap();
+            let err = JsError::error(cx, err)?;
             let code_num = cx.number(code);
-            err.set(cx, "rawCode", code_num).unwrap();
+            err.set(cx, "rawCode", code_num)?;
             let code = cx.string(convert_sqlite_code(code));
-            err.set(cx, "code", code).unwrap();
+            err.set(cx, "code", code)?;
             let val = cx.boolean(true);
-            err.set(cx, "libsqlError", val).unwrap();
-            cx.throw(err)?
+            err.set(cx, "libsqlError", val)?;
+            Ok(err)
         }
         _ => {
-            todo!("err = {:?}", err);
-            let err = format!("{:?}", err);
-            let err = JsError::error(cx, err).unwrap();
+            let err = err.to_string();
+            let err = JsError::error(cx, err)?;
             let code = cx.string("");
-            err.set(cx, "code", code).unwrap();
-            cx.throw(err)?
+            err.set(cx, "code", code)?;
+            Ok(err)
         }
     }
 }
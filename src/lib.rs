@@ -1,32 +1,142 @@
+mod arrow;
+mod bootstrap;
+mod changes;
 mod database;
 mod errors;
+mod logger;
+mod migrate;
+mod offline;
+mod schema;
+mod slow_query;
 mod statement;
+mod statement_cache;
+mod stats;
+mod stmt_status;
+mod sync;
+mod transfer;
 
 use crate::database::Database;
-use crate::statement::{Rows, Statement};
+use crate::statement::{ArrowBatches, Rows, Statement};
 use neon::prelude::*;
 use once_cell::sync::OnceCell;
-use tokio::runtime::Runtime;
-use tracing::level_filters::LevelFilter;
-use tracing_subscriber::EnvFilter;
+use std::sync::{Mutex, Once};
+use tokio::runtime::{Builder, Runtime, RuntimeFlavor};
+
+static RUNTIME: OnceCell<Runtime> = OnceCell::new();
+
+/// Runtime options set with `configure()` before the runtime is started.
+struct RuntimeConfig {
+    worker_threads: Option<usize>,
+    max_blocking_threads: Option<usize>,
+    thread_name: Option<String>,
+    current_thread: bool,
+}
+
+static RUNTIME_CONFIG: Mutex<Option<RuntimeConfig>> = Mutex::new(None);
+
+/// Name of the thread that drives a current-thread runtime.
+static DRIVER_THREAD_NAME: OnceCell<String> = OnceCell::new();
 
 fn runtime<'a, C: Context<'a>>(cx: &mut C) -> NeonResult<&'static Runtime> {
-    static RUNTIME: OnceCell<Runtime> = OnceCell::new();
+    static DRIVER: Once = Once::new();
+
+    let rt = RUNTIME
+        .get_or_try_init(build_runtime)
+        .or_else(|err| cx.throw_error(&err.to_string()))?;
+    // Tasks spawned on a current-thread runtime only make progress while the runtime is
+    // driven by `block_on()`, which the async API never calls, so one background thread
+    // is always started to drive it for the lifetime of the process. The sync API's
+    // `block_on()` calls on the main thread take turns with it.
+    if rt.handle().runtime_flavor() == RuntimeFlavor::CurrentThread {
+        DRIVER.call_once(|| {
+            let name = DRIVER_THREAD_NAME
+                .get()
+                .cloned()
+                .unwrap_or_else(|| "libsql-runtime".to_string());
+            let spawned = std::thread::Builder::new()
+                .name(name)
+                .spawn(move || rt.block_on(std::future::pending::<()>()));
+            if let Err(err) = spawned {
+                tracing::error!("Failed to start the runtime thread: {}", err);
+            }
+        });
+    }
+    Ok(rt)
+}
+
+fn build_runtime() -> std::io::Result<Runtime> {
+    let config = RUNTIME_CONFIG.lock().unwrap().take();
+    let Some(config) = config else {
+        return Runtime::new();
+    };
+    let mut builder = if config.current_thread {
+        Builder::new_current_thread()
+    } else {
+        Builder::new_multi_thread()
+    };
+    builder.enable_all();
+    if let Some(worker_threads) = config.worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    if let Some(max_blocking_threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(max_blocking_threads);
+    }
+    if let Some(thread_name) = config.thread_name {
+        let _ = DRIVER_THREAD_NAME.set(thread_name.clone());
+        builder.thread_name(thread_name);
+    }
+    builder.build()
+}
+
+fn js_configure(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let worker_threads = cx.argument::<JsNumber>(0)?.value(&mut cx);
+    let max_blocking_threads = cx.argument::<JsNumber>(1)?.value(&mut cx);
+    let thread_name = cx.argument::<JsString>(2)?.value(&mut cx);
+    let current_thread = cx.argument::<JsBoolean>(3)?.value(&mut cx);
+    if RUNTIME.get().is_some() {
+        return cx.throw_error("configure() must be called before the first database is opened");
+    }
+    let worker_threads = thread_count(&mut cx, "workerThreads", worker_threads)?;
+    let max_blocking_threads = thread_count(&mut cx, "maxBlockingThreads", max_blocking_threads)?;
+    let config = RuntimeConfig {
+        worker_threads,
+        max_blocking_threads,
+        thread_name: Some(thread_name).filter(|name| !name.is_empty()),
+        current_thread,
+    };
+    RUNTIME_CONFIG.lock().unwrap().replace(config);
+    Ok(cx.undefined())
+}
 
-    RUNTIME
-        .get_or_try_init(Runtime::new)
-        .or_else(|err| cx.throw_error(&err.to_string()))
+/// Converts a thread count option, where zero means the default.
+fn thread_count(cx: &mut FunctionContext, name: &str, count: f64) -> NeonResult<Option<usize>> {
+    if count.is_nan() || count < 0.0 || count.fract() != 0.0 {
+        return cx.throw_range_error(format!("{} must be a non-negative integer", name));
+    }
+    Ok(Some(count as usize).filter(|count| *count > 0))
+}
+
+fn js_runtime_metrics(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let Some(rt) = RUNTIME.get() else {
+        return Ok(cx.undefined().upcast());
+    };
+    let metrics = rt.metrics();
+    let obj = cx.empty_object();
+    let workers = cx.number(metrics.num_workers() as f64);
+    obj.set(&mut cx, "workers", workers)?;
+    let alive_tasks = cx.number(metrics.num_alive_tasks() as f64);
+    obj.set(&mut cx, "aliveTasks", alive_tasks)?;
+    let global_queue_depth = cx.number(metrics.global_queue_depth() as f64);
+    obj.set(&mut cx, "globalQueueDepth", global_queue_depth)?;
+    Ok(obj.upcast())
 }
 
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
-    let _ = tracing_subscriber::fmt::fmt()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::ERROR.into())
-                .from_env_lossy(),
-        )
-        .try_init();
+    logger::init();
+    cx.export_function("runtimeConfigure", js_configure)?;
+    cx.export_function("runtimeMetrics", js_runtime_metrics)?;
+    cx.export_function("setLogger", logger::js_set_logger)?;
     cx.export_function("databaseOpen", Database::js_open)?;
     cx.export_function("databaseOpenWithSync", Database::js_open_with_sync)?;
     cx.export_function("databaseInTransaction", Database::js_in_transaction)?;
@@ -34,12 +144,45 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("databaseClose", Database::js_close)?;
     cx.export_function("databaseSyncSync", Database::js_sync_sync)?;
     cx.export_function("databaseSyncAsync", Database::js_sync_async)?;
+    cx.export_function("databaseSyncStatus", Database::js_sync_status)?;
+    cx.export_function("databasePendingChanges", Database::js_pending_changes)?;
+    cx.export_function("databasePushSync", Database::js_push_sync)?;
+    cx.export_function("databasePushAsync", Database::js_push_async)?;
+    cx.export_function("databasePullSync", Database::js_pull_sync)?;
+    cx.export_function("databasePullAsync", Database::js_pull_async)?;
+    cx.export_function("databaseSetSyncListener", Database::js_set_sync_listener)?;
+    cx.export_function("databasePauseSync", Database::js_pause_sync)?;
+    cx.export_function("databaseResumeSync", Database::js_resume_sync)?;
+    cx.export_function("databaseSetSyncPeriod", Database::js_set_sync_period)?;
     cx.export_function("databaseSyncUntilSync", Database::js_sync_until_sync)?;
     cx.export_function("databaseSyncUntilAsync", Database::js_sync_until_async)?;
+    cx.export_function(
+        "databaseWaitForReplicationIndexSync",
+        Database::js_wait_for_replication_index_sync,
+    )?;
+    cx.export_function(
+        "databaseWaitForReplicationIndexAsync",
+        Database::js_wait_for_replication_index_async,
+    )?;
     cx.export_function("databaseExecSync", Database::js_exec_sync)?;
     cx.export_function("databaseExecAsync", Database::js_exec_async)?;
+    cx.export_function("databaseMigrateSync", Database::js_migrate_sync)?;
+    cx.export_function("databaseMigrateAsync", Database::js_migrate_async)?;
+    cx.export_function("databaseSchemaSync", Database::js_schema_sync)?;
+    cx.export_function("databaseSchemaAsync", Database::js_schema_async)?;
+    cx.export_function("databaseImportFileSync", Database::js_import_file_sync)?;
+    cx.export_function("databaseImportFileAsync", Database::js_import_file_async)?;
     cx.export_function("databasePrepareSync", Database::js_prepare_sync)?;
     cx.export_function("databasePrepareAsync", Database::js_prepare_async)?;
+    cx.export_function(
+        "databaseSetStatementCacheSize",
+        Database::js_set_statement_cache_size,
+    )?;
+    cx.export_function(
+        "databaseStatementCacheStats",
+        Database::js_statement_cache_stats,
+    )?;
+    cx.export_function("databaseSetSlowQueryLog", Database::js_set_slow_query_log)?;
     cx.export_function(
         "databaseDefaultSafeIntegers",
         Database::js_default_safe_integers,
@@ -52,11 +195,25 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("statementRaw", Statement::js_raw)?;
     cx.export_function("statementIsReader", Statement::js_is_reader)?;
     cx.export_function("statementRun", Statement::js_run)?;
+    cx.export_function("statementRunMany", Statement::js_run_many)?;
     cx.export_function("statementGet", Statement::js_get)?;
     cx.export_function("statementRowsSync", Statement::js_rows_sync)?;
     cx.export_function("statementRowsAsync", Statement::js_rows_async)?;
+    cx.export_function("statementAllSync", Statement::js_all_sync)?;
+    cx.export_function("statementAllAsync", Statement::js_all_async)?;
+    cx.export_function("statementColumnar", Statement::js_columnar)?;
+    cx.export_function("statementArrow", Statement::js_arrow)?;
+    cx.export_function("statementArrowBatches", Statement::js_arrow_batches)?;
+    cx.export_function("statementExportSync", Statement::js_export_sync)?;
+    cx.export_function("statementExportAsync", Statement::js_export_async)?;
     cx.export_function("statementColumns", Statement::js_columns)?;
     cx.export_function("statementSafeIntegers", Statement::js_safe_integers)?;
+    cx.export_function("statementStats", Statement::js_stats)?;
+    cx.export_function("statementExplain", Statement::js_explain)?;
+    cx.export_function("statementFinalize", Statement::js_finalize)?;
     cx.export_function("rowsNext", Rows::js_next)?;
+    cx.export_function("rowsClose", Rows::js_close)?;
+    cx.export_function("arrowBatchesNext", ArrowBatches::js_next)?;
+    cx.export_function("arrowBatchesClose", ArrowBatches::js_close)?;
     Ok(())
 }
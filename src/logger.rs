@@ -0,0 +1,195 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use neon::prelude::*;
+use once_cell::sync::OnceCell;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::registry::Registry;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// JavaScript function that log records are forwarded to.
+struct JsLogger {
+    callback: Arc<Root<JsFunction>>,
+    channel: Channel,
+}
+
+static JS_LOGGER: Mutex<Option<JsLogger>> = Mutex::new(None);
+
+static FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+fn default_filter() -> EnvFilter {
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::ERROR.into())
+        .from_env_lossy()
+}
+
+fn has_js_logger() -> bool {
+    JS_LOGGER.lock().map_or(false, |logger| logger.is_some())
+}
+
+/// Installs the global tracing subscriber.
+///
+/// Events are written to stdout, filtered by `RUST_LOG`, until a JavaScript
+/// logger is set with `setLogger()`, which replaces both the filter and the
+/// output.
+pub(crate) fn init() {
+    let (filter, handle) = reload::Layer::new(default_filter());
+    let stdout = tracing_subscriber::fmt::layer().with_filter(filter_fn(|_| !has_js_logger()));
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(stdout)
+        .with(JsLayer)
+        .try_init();
+    if result.is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+pub(crate) fn js_set_logger(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let level = cx.argument::<JsString>(0)?.value(&mut cx);
+    let callback = cx.argument_opt(1);
+    let callback = match callback {
+        Some(callback) if !callback.is_a::<JsUndefined, _>(&mut cx) => {
+            Some(callback.downcast_or_throw::<JsFunction, _>(&mut cx)?)
+        }
+        _ => None,
+    };
+    let filter = match callback {
+        Some(_) => EnvFilter::try_new(&level)
+            .or_else(|err| cx.throw_type_error(format!("Invalid log level {}: {}", level, err)))?,
+        None => default_filter(),
+    };
+    let logger = callback.map(|callback| {
+        let mut channel = cx.channel();
+        // Logging must not keep the process alive.
+        channel.unref(&mut cx);
+        JsLogger {
+            callback: Arc::new(callback.root(&mut cx)),
+            channel,
+        }
+    });
+    *JS_LOGGER.lock().unwrap() = logger;
+    if let Some(handle) = FILTER.get() {
+        handle
+            .reload(filter)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+    }
+    Ok(cx.undefined())
+}
+
+/// Forwards events to the JavaScript logger as structured records.
+struct JsLayer;
+
+impl<S: Subscriber> Layer<S> for JsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        let logger = JS_LOGGER.lock().unwrap();
+        let Some(logger) = logger.as_ref() else {
+            return;
+        };
+        let metadata = event.metadata();
+        let mut record = Record {
+            level: metadata.level().as_str().to_lowercase(),
+            target: metadata.target().to_string(),
+            message: String::new(),
+            fields: Vec::new(),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |time| time.as_secs_f64() * 1000.0),
+        };
+        event.record(&mut record);
+        let callback = logger.callback.clone();
+        logger.channel.send(move |mut cx| {
+            let callback = callback.to_inner(&mut cx);
+            let record = record.to_object(&mut cx)?;
+            callback.call_with(&cx).arg(record).exec(&mut cx)
+        });
+    }
+}
+
+enum FieldValue {
+    Str(String),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+}
+
+struct Record {
+    level: String,
+    target: String,
+    message: String,
+    fields: Vec<(String, FieldValue)>,
+    time: f64,
+}
+
+impl Record {
+    fn to_object<'a>(&self, cx: &mut impl Context<'a>) -> JsResult<'a, JsObject> {
+        let obj = cx.empty_object();
+        let level = cx.string(&self.level);
+        obj.set(cx, "level", level)?;
+        let target = cx.string(&self.target);
+        obj.set(cx, "target", target)?;
+        let message = cx.string(&self.message);
+        obj.set(cx, "message", message)?;
+        let time = cx.number(self.time);
+        obj.set(cx, "time", time)?;
+        let fields = cx.empty_object();
+        for (name, value) in &self.fields {
+            let value: Handle<'_, JsValue> = match value {
+                FieldValue::Str(v) => cx.string(v).upcast(),
+                FieldValue::I64(v) => cx.number(*v as f64).upcast(),
+                FieldValue::U64(v) => cx.number(*v as f64).upcast(),
+                FieldValue::F64(v) => cx.number(*v).upcast(),
+                FieldValue::Bool(v) => cx.boolean(*v).upcast(),
+            };
+            fields.set(cx, name.as_str(), value)?;
+        }
+        obj.set(cx, "fields", fields)?;
+        Ok(obj)
+    }
+
+    fn push(&mut self, field: &Field, value: FieldValue) {
+        self.fields.push((field.name().to_string(), value));
+    }
+}
+
+impl Visit for Record {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.push(field, FieldValue::Str(format!("{:?}", value)));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.push(field, FieldValue::Str(value.to_string()));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, FieldValue::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, FieldValue::U64(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, FieldValue::F64(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, FieldValue::Bool(value));
+    }
+}
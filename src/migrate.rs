@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+
+use neon::prelude::*;
+
+use crate::errors::throw_libsql_error;
+use crate::transfer::quote_identifier;
+
+pub(crate) struct Migration {
+    id: String,
+    up: String,
+    down: Option<String>,
+}
+
+/// The IDs of the migrations applied and rolled back by `migrate()`.
+#[derive(Default)]
+pub(crate) struct MigrationReport {
+    applied: Vec<String>,
+    rolled_back: Vec<String>,
+}
+
+pub(crate) enum MigrationError {
+    Libsql(libsql::Error),
+    /// Running the migration with the given ID failed.
+    Failed(String, libsql::Error),
+    Invalid(String),
+}
+
+impl From<libsql::Error> for MigrationError {
+    fn from(err: libsql::Error) -> Self {
+        MigrationError::Libsql(err)
+    }
+}
+
+pub(crate) fn throw_migration_error<'a, C: Context<'a>, T>(
+    cx: &mut C,
+    err: MigrationError,
+) -> NeonResult<T> {
+    match err {
+        MigrationError::Libsql(err) => throw_libsql_error(cx, err),
+        MigrationError::Failed(id, libsql::Error::SqliteFailure(code, msg)) => throw_libsql_error(
+            cx,
+            libsql::Error::SqliteFailure(code, format!("Migration {} failed: {}", id, msg)),
+        ),
+        MigrationError::Failed(id, err) => {
+            cx.throw_error(format!("Migration {} failed: {}", id, err))
+        }
+        MigrationError::Invalid(msg) => cx.throw_error(msg),
+    }
+}
+
+/// Converts an array of `{ id, up, down }` objects into migrations.
+pub(crate) fn convert_migrations(
+    cx: &mut FunctionContext,
+    migrations: Handle<'_, JsArray>,
+) -> NeonResult<Vec<Migration>> {
+    let mut result = Vec::with_capacity(migrations.len(cx) as usize);
+    let mut ids = HashSet::new();
+    for i in 0..migrations.len(cx) {
+        let migration: Handle<'_, JsObject> = migrations.get(cx, i)?;
+        let id = migration.get::<JsString, _, _>(cx, "id")?.value(cx);
+        let up = migration.get::<JsString, _, _>(cx, "up")?.value(cx);
+        let down = migration
+            .get_opt::<JsString, _, _>(cx, "down")?
+            .map(|down| down.value(cx));
+        if !ids.insert(id.clone()) {
+            return cx.throw_type_error(format!("Duplicate migration ID: {}", id));
+        }
+        result.push(Migration { id, up, down });
+    }
+    Ok(result)
+}
+
+pub(crate) fn convert_report<'a>(
+    cx: &mut impl Context<'a>,
+    report: &MigrationReport,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let applied = cx.empty_array();
+    for (i, id) in report.applied.iter().enumerate() {
+        let id = cx.string(id);
+        applied.set(cx, i as u32, id)?;
+    }
+    obj.set(cx, "applied", applied)?;
+    let rolled_back = cx.empty_array();
+    for (i, id) in report.rolled_back.iter().enumerate() {
+        let id = cx.string(id);
+        rolled_back.set(cx, i as u32, id)?;
+    }
+    obj.set(cx, "rolledBack", rolled_back)?;
+    Ok(obj)
+}
+
+/// Applies the pending migrations in order, each in its own transaction, and
+/// records them in `table` with a checksum of their `up` SQL.
+///
+/// If `target` is given, only the migrations up to and including it are
+/// applied, and applied migrations that come after it are rolled back in
+/// reverse order with their `down` SQL.
+pub(crate) async fn migrate(
+    conn: &libsql::Connection,
+    table: &str,
+    migrations: &[Migration],
+    target: Option<&str>,
+) -> Result<MigrationReport, MigrationError> {
+    if !conn.is_autocommit() {
+        return Err(MigrationError::Invalid(
+            "Migrations cannot run inside a transaction".to_string(),
+        ));
+    }
+    let end = match target {
+        Some(target) => match migrations.iter().position(|m| m.id == target) {
+            Some(pos) => pos + 1,
+            None => {
+                return Err(MigrationError::Invalid(format!(
+                    "Unknown target migration: {}",
+                    target
+                )))
+            }
+        },
+        None => migrations.len(),
+    };
+    let table = quote_identifier(table);
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} \
+             (id TEXT PRIMARY KEY, checksum TEXT NOT NULL, applied_at INTEGER NOT NULL)",
+            table
+        ),
+        (),
+    )
+    .await?;
+    let mut applied = HashMap::new();
+    let mut rows = conn
+        .query(&format!("SELECT id, checksum FROM {}", table), ())
+        .await?;
+    while let Some(row) = rows.next().await? {
+        applied.insert(row.get::<String>(0)?, row.get::<String>(1)?);
+    }
+    drop(rows);
+    for migration in migrations {
+        let edited = applied
+            .get(&migration.id)
+            .is_some_and(|applied| *applied != checksum(&migration.up));
+        if edited {
+            return Err(MigrationError::Invalid(format!(
+                "Migration {} has been edited after it was applied",
+                migration.id
+            )));
+        }
+    }
+    let mut report = MigrationReport::default();
+    for migration in migrations[end..].iter().rev() {
+        if !applied.contains_key(&migration.id) {
+            continue;
+        }
+        let Some(down) = &migration.down else {
+            return Err(MigrationError::Invalid(format!(
+                "Migration {} cannot be rolled back without down SQL",
+                migration.id
+            )));
+        };
+        let sql = format!("DELETE FROM {} WHERE id = ?", table);
+        if run_migration(conn, &table, migration, down, &sql, false).await? {
+            report.rolled_back.push(migration.id.clone());
+        }
+    }
+    for migration in &migrations[..end] {
+        if applied.contains_key(&migration.id) {
+            continue;
+        }
+        let sql = format!(
+            "INSERT INTO {} (id, checksum, applied_at) VALUES (?1, ?2, unixepoch())",
+            table
+        );
+        if run_migration(conn, &table, migration, &migration.up, &sql, true).await? {
+            report.applied.push(migration.id.clone());
+        }
+    }
+    Ok(report)
+}
+
+/// Runs the `up` or `down` SQL of a migration and the statement that records
+/// it in a transaction, returning false if the migration was already
+/// applied, or rolled back, by another connection.
+async fn run_migration(
+    conn: &libsql::Connection,
+    table: &str,
+    migration: &Migration,
+    sql: &str,
+    record_sql: &str,
+    up: bool,
+) -> Result<bool, MigrationError> {
+    conn.execute("BEGIN IMMEDIATE", ()).await?;
+    let result = async {
+        // Another connection, such as another replica of the database, may
+        // have run the migration since the migrations table was read.
+        let mut rows = conn
+            .query(
+                &format!("SELECT 1 FROM {} WHERE id = ?", table),
+                [migration.id.as_str()],
+            )
+            .await?;
+        let applied = rows.next().await?.is_some();
+        drop(rows);
+        if applied == up {
+            return Ok(false);
+        }
+        conn.execute_batch(sql).await?;
+        if up {
+            let checksum = checksum(&migration.up);
+            conn.execute(record_sql, [migration.id.as_str(), checksum.as_str()])
+                .await?;
+        } else {
+            conn.execute(record_sql, [migration.id.as_str()]).await?;
+        }
+        Ok::<_, libsql::Error>(true)
+    }
+    .await;
+    match result {
+        Ok(done) => {
+            conn.execute("COMMIT", ()).await?;
+            Ok(done)
+        }
+        Err(err) => {
+            if !conn.is_autocommit() {
+                let _ = conn.execute("ROLLBACK", ()).await;
+            }
+            Err(MigrationError::Failed(migration.id.clone(), err))
+        }
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of `sql` as a hex string.
+fn checksum(sql: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in sql.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
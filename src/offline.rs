@@ -0,0 +1,619 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libsql::replication::Replicated;
+use neon::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::changes::{frames_appended, wal_frame_count, wal_frames_after, wal_path, WalPosition};
+use crate::sync::{SharedDatabase, SyncError, SyncStatus};
+
+/// Checks the optional `onConflict` option passed as the argument at `index`.
+///
+/// Only `"fail"` is supported. Recovering from a conflict takes pulling the
+/// remote frames before pushing again, but libSQL only pulls into an offline
+/// database that has no local frames left to push, and retrying the push
+/// alone gets the same rejection.
+pub(crate) fn check_conflict_policy(cx: &mut FunctionContext, index: usize) -> NeonResult<()> {
+    let policy = match cx.argument_opt(index) {
+        Some(policy) if policy.is_a::<JsString, _>(cx) => {
+            policy.downcast_or_throw::<JsString, _>(cx)?.value(cx)
+        }
+        _ => return Ok(()),
+    };
+    match policy.as_str() {
+        "fail" => Ok(()),
+        "pull-then-retry" => cx.throw_type_error(
+            "The pull-then-retry conflict policy is not supported, because the version of libSQL \
+             used by this package cannot pull into a database that has unpushed local frames",
+        ),
+        _ => cx.throw_type_error(format!("Unsupported conflict policy: {}", policy)),
+    }
+}
+
+/// The sync state that libSQL keeps in the `-info` file of an offline
+/// database.
+#[derive(Default)]
+pub(crate) struct SyncMetadata {
+    /// The last local frame that the primary has acknowledged.
+    pub durable_frame_no: u64,
+    pub generation: u64,
+}
+
+impl SyncMetadata {
+    pub fn read(path: &Path) -> Self {
+        let mut info = path.to_path_buf().into_os_string();
+        info.push("-info");
+        let info: serde_json::Value = match std::fs::read(PathBuf::from(info)) {
+            Ok(info) => serde_json::from_slice(&info).unwrap_or_default(),
+            Err(_) => return SyncMetadata::default(),
+        };
+        let field = |name| info.get(name).and_then(|v| v.as_u64()).unwrap_or_default();
+        SyncMetadata {
+            durable_frame_no: field("durable_frame_num"),
+            generation: field("generation"),
+        }
+    }
+}
+
+/// A rejection of the local frames of an offline database by the primary.
+pub(crate) struct Conflict {
+    pub message: String,
+    /// The HTTP status of the rejected push, if the primary sent one.
+    status: Option<u16>,
+    generation: u64,
+    durable_frame_no: u64,
+    pending_frames: u64,
+}
+
+pub(crate) fn convert_conflict<'a, C: Context<'a>>(
+    cx: &mut C,
+    conflict: &Conflict,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let status: Handle<'_, JsValue> = match conflict.status {
+        Some(status) => cx.number(status).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "status", status)?;
+    let generation = cx.number(conflict.generation as f64);
+    obj.set(cx, "generation", generation)?;
+    let durable_frame_no = cx.number(conflict.durable_frame_no as f64);
+    obj.set(cx, "durableFrameNo", durable_frame_no)?;
+    let pending_frames = cx.number(conflict.pending_frames as f64);
+    obj.set(cx, "pendingFrames", pending_frames)?;
+    Ok(obj)
+}
+
+/// The outcome of synchronizing an offline database.
+pub(crate) struct OfflineSync {
+    pub rep: Replicated,
+    /// The number of local frames that the primary acknowledged.
+    pub pushed: u64,
+    /// The number of frames that the sync added to the local WAL, or `None`
+    /// if the WAL was checkpointed during the sync.
+    pub pulled: Option<u64>,
+}
+
+/// Synchronizes an offline database, reporting a rejection of its local
+/// frames by the primary as a conflict. The local frames are never discarded,
+/// so a later sync pushes them again.
+pub(crate) async fn sync_offline(
+    db: Arc<SharedDatabase>,
+    status: Arc<SyncStatus>,
+    pending_writes: Arc<PendingWrites>,
+) -> Result<OfflineSync, SyncError> {
+    let db = db.lock().await;
+    sync_locked(&db, &status, &pending_writes).await
+}
+
+/// Runs a libSQL sync of an offline database while other syncs are held off.
+/// libSQL pushes if the database has local frames that the primary hasn't
+/// acknowledged, and pulls otherwise.
+async fn sync_locked(
+    db: &libsql::Database,
+    status: &SyncStatus,
+    pending_writes: &PendingWrites,
+) -> Result<OfflineSync, SyncError> {
+    let path = status.path();
+    let before = SyncMetadata::read(path);
+    let wal_before = WalPosition::read(path);
+    let err = match status.track(db.sync()).await {
+        Ok(rep) => {
+            let after = SyncMetadata::read(path);
+            if pending_frames(path, &after) == 0 {
+                pending_writes.clear();
+            }
+            let pulled = wal_before
+                .as_ref()
+                .ok()
+                .and_then(|wal_before| frames_appended(path, wal_before).ok().flatten());
+            return Ok(OfflineSync {
+                rep,
+                pushed: after
+                    .durable_frame_no
+                    .saturating_sub(before.durable_frame_no),
+                pulled,
+            });
+        }
+        Err(err) => err,
+    };
+    let Some(rejection) = PushRejection::of(&err) else {
+        return Err(SyncError::Libsql(err));
+    };
+    let metadata = SyncMetadata::read(path);
+    Err(SyncError::Conflict(Conflict {
+        message: err.to_string(),
+        status: rejection.status(),
+        generation: metadata.generation,
+        durable_frame_no: metadata.durable_frame_no,
+        pending_frames: pending_frames(path, &metadata),
+    }))
+}
+
+/// The outcome of `push()`.
+pub(crate) struct PushStats {
+    pushed: u64,
+    pending_frames: u64,
+    metadata: SyncMetadata,
+}
+
+/// Pushes the local frames of an offline database to the primary without
+/// pulling. libSQL pulls when there are no local frames to push, so nothing
+/// is synced then.
+pub(crate) async fn push(
+    db: Arc<SharedDatabase>,
+    status: Arc<SyncStatus>,
+    pending_writes: Arc<PendingWrites>,
+) -> Result<PushStats, SyncError> {
+    let path = status.path();
+    let db = db.lock().await;
+    let pushed = if pending_frames(path, &SyncMetadata::read(path)) > 0 {
+        sync_locked(&db, &status, &pending_writes).await?.pushed
+    } else {
+        0
+    };
+    drop(db);
+    let metadata = SyncMetadata::read(path);
+    Ok(PushStats {
+        pushed,
+        pending_frames: pending_frames(path, &metadata),
+        metadata,
+    })
+}
+
+pub(crate) fn convert_push_stats<'a, C: Context<'a>>(
+    cx: &mut C,
+    stats: &PushStats,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let pushed = cx.number(stats.pushed as f64);
+    obj.set(cx, "pushed", pushed)?;
+    let pending_frames = cx.number(stats.pending_frames as f64);
+    obj.set(cx, "pendingFrames", pending_frames)?;
+    let durable_frame_no = cx.number(stats.metadata.durable_frame_no as f64);
+    obj.set(cx, "durableFrameNo", durable_frame_no)?;
+    let generation = cx.number(stats.metadata.generation as f64);
+    obj.set(cx, "generation", generation)?;
+    Ok(obj)
+}
+
+/// The outcome of `pull()`.
+pub(crate) struct PullStats {
+    /// The number of frames that the pull added to the local WAL, or `None`
+    /// if the WAL was checkpointed during the pull.
+    pub pulled: Option<u64>,
+    pub frame_no: Option<u64>,
+    pub pending_frames: u64,
+}
+
+/// Pulls the frames of the primary into an offline database without pushing.
+///
+/// libSQL pushes instead of pulling while the database has local frames that
+/// the primary hasn't acknowledged, so nothing is pulled then. The connection
+/// of the database is locked, so that its writes can't add local frames
+/// between checking for them and pulling.
+pub(crate) async fn pull(
+    db: Arc<SharedDatabase>,
+    conn: Arc<Mutex<libsql::Connection>>,
+    status: Arc<SyncStatus>,
+    pending_writes: Arc<PendingWrites>,
+) -> Result<PullStats, SyncError> {
+    let path = status.path();
+    let _conn = conn.lock().await;
+    let db = db.lock().await;
+    let pending = pending_frames(path, &SyncMetadata::read(path));
+    if pending > 0 {
+        return Ok(PullStats {
+            pulled: Some(0),
+            frame_no: None,
+            pending_frames: pending,
+        });
+    }
+    let sync = sync_locked(&db, &status, &pending_writes).await?;
+    Ok(PullStats {
+        pulled: sync.pulled,
+        frame_no: sync.rep.frame_no(),
+        pending_frames: pending_frames(path, &SyncMetadata::read(path)),
+    })
+}
+
+pub(crate) fn convert_pull_stats<'a, C: Context<'a>>(
+    cx: &mut C,
+    stats: &PullStats,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let pulled: Handle<'_, JsValue> = match stats.pulled {
+        Some(pulled) => cx.number(pulled as f64).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "pulled", pulled)?;
+    let frame_no: Handle<'_, JsValue> = match stats.frame_no {
+        Some(frame_no) => cx.number(frame_no as f64).upcast(),
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "frameNo", frame_no)?;
+    let pending_frames = cx.number(stats.pending_frames as f64);
+    obj.set(cx, "pendingFrames", pending_frames)?;
+    Ok(obj)
+}
+
+/// A sync error with which the primary rejected the pushed frames.
+#[derive(Debug, PartialEq)]
+enum PushRejection {
+    /// A push answered with HTTP status 409.
+    Conflict,
+    /// The primary already has different frames at the pushed frame numbers.
+    FrameConflict,
+    /// The generation of the primary is behind the local one.
+    LowerGeneration,
+}
+
+impl PushRejection {
+    /// Recognizes a rejection by the variant of libSQL's sync error.
+    ///
+    /// libSQL wraps its sync errors in `Error::Sync` without exposing their
+    /// type, so they can't be downcast or matched on. The variant is read
+    /// from their derived `Debug` output instead, which follows the
+    /// definition of the type rather than the wording of its messages.
+    fn of(err: &libsql::Error) -> Option<Self> {
+        let libsql::Error::Sync(err) = err else {
+            return None;
+        };
+        let debug = format!("{:?}", err);
+        let (variant, fields) = debug.split_once('(')?;
+        match variant {
+            "PushFrame" if leading_status(fields) == Some(409) => Some(PushRejection::Conflict),
+            "InvalidPushFrameConflict" => Some(PushRejection::FrameConflict),
+            "InvalidLocalGeneration" => Some(PushRejection::LowerGeneration),
+            _ => None,
+        }
+    }
+
+    fn status(&self) -> Option<u16> {
+        match self {
+            PushRejection::Conflict => Some(409),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the HTTP status code that the fields of a `PushFrame` error start
+/// with, which `http::StatusCode` formats as a bare number.
+fn leading_status(fields: &str) -> Option<u16> {
+    let end = fields
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(fields.len());
+    fields[..end].parse().ok()
+}
+
+/// Returns the number of local frames of an offline database that the
+/// primary has not acknowledged.
+pub(crate) fn pending_frames(path: &Path, metadata: &SyncMetadata) -> u64 {
+    wal_frame_count(path)
+        .unwrap_or_default()
+        .saturating_sub(metadata.durable_frame_no)
+}
+
+/// Records when the oldest local write that has not been pushed was made,
+/// which the WAL doesn't record.
+///
+/// The time is kept in the `-pending` file of the database, so that it
+/// survives restarts.
+pub(crate) struct PendingWrites {
+    db_path: PathBuf,
+    path: PathBuf,
+    since: std::sync::Mutex<Option<SystemTime>>,
+}
+
+impl PendingWrites {
+    pub fn open(db_path: &Path) -> Self {
+        let mut path = db_path.to_path_buf().into_os_string();
+        path.push("-pending");
+        let path = PathBuf::from(path);
+        let since = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|since| since.trim().parse().ok())
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis));
+        PendingWrites {
+            db_path: db_path.to_path_buf(),
+            path,
+            since: std::sync::Mutex::new(since),
+        }
+    }
+
+    /// Records a write that changed rows.
+    pub fn record(&self) {
+        let mut since = self.since.lock().unwrap();
+        if since.is_some() {
+            return;
+        }
+        let now = SystemTime::now();
+        *since = Some(now);
+        let millis = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let _ = std::fs::write(&self.path, millis.to_string());
+    }
+
+    /// Returns the size of the WAL before a write whose changed rows are not
+    /// counted, to pass to `record_appended()` after it.
+    pub fn wal_size(&self) -> u64 {
+        std::fs::metadata(wal_path(&self.db_path)).map_or(0, |metadata| metadata.len())
+    }
+
+    /// Records a write if the WAL grew since it was `before` bytes long,
+    /// which also catches writes that change no rows, such as `CREATE TABLE`.
+    /// libSQL disables checkpoints on the connections of offline databases,
+    /// so the WAL only grows until a sync checkpoints it.
+    pub fn record_appended(&self, before: u64) {
+        if self.wal_size() > before {
+            self.record();
+        }
+    }
+
+    /// Forgets the recorded time once all local writes have been pushed.
+    fn clear(&self) {
+        if self.since.lock().unwrap().take().is_some() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The local writes of an offline database that have not been pushed.
+#[derive(Default)]
+pub(crate) struct PendingChanges {
+    frames: u64,
+    transactions: u64,
+    bytes: u64,
+    since: Option<SystemTime>,
+}
+
+impl PendingChanges {
+    pub fn read(path: &Path, pending_writes: &PendingWrites) -> std::io::Result<Self> {
+        let metadata = SyncMetadata::read(path);
+        let (frames, transactions, bytes) = wal_frames_after(path, metadata.durable_frame_no)?;
+        let since = match frames {
+            0 => None,
+            _ => *pending_writes.since.lock().unwrap(),
+        };
+        Ok(PendingChanges {
+            frames,
+            transactions,
+            bytes,
+            since,
+        })
+    }
+}
+
+pub(crate) fn convert_pending_changes<'a, C: Context<'a>>(
+    cx: &mut C,
+    changes: &PendingChanges,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let frames = cx.number(changes.frames as f64);
+    obj.set(cx, "frames", frames)?;
+    let transactions = cx.number(changes.transactions as f64);
+    obj.set(cx, "transactions", transactions)?;
+    let bytes = cx.number(changes.bytes as f64);
+    obj.set(cx, "bytes", bytes)?;
+    let since: Handle<'_, JsValue> = match changes.since {
+        Some(since) => {
+            let since = since.duration_since(UNIX_EPOCH).unwrap_or_default();
+            cx.number(since.as_secs_f64() * 1000.0).upcast()
+        }
+        None => cx.null().upcast(),
+    };
+    obj.set(cx, "oldestUnpushedTime", since)?;
+    Ok(obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formats like `http::StatusCode`, as a bare number.
+    struct StatusCode(u16);
+
+    impl std::fmt::Debug for StatusCode {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            std::fmt::Debug::fmt(&self.0, f)
+        }
+    }
+
+    /// Mirrors the variants of libSQL's private sync error type, whose
+    /// `Debug` output conflicts are recognized by.
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum SyncError {
+        PushFrame(StatusCode, String),
+        PullFrame(StatusCode, String),
+        InvalidPushFrameConflict(u32, u32),
+        InvalidLocalGeneration(u32, u32),
+        InvalidPushFrameNoLow(u32, u32),
+        HttpDispatch(String),
+    }
+
+    impl std::fmt::Display for SyncError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("sync error")
+        }
+    }
+
+    impl std::error::Error for SyncError {}
+
+    fn rejection(err: SyncError) -> Option<PushRejection> {
+        PushRejection::of(&libsql::Error::Sync(err.into()))
+    }
+
+    #[test]
+    fn conflicts() {
+        let rejected = rejection(SyncError::PushFrame(
+            StatusCode(409),
+            "frame mismatch".into(),
+        ));
+        assert_eq!(rejected, Some(PushRejection::Conflict));
+        assert_eq!(rejected.unwrap().status(), Some(409));
+        let rejected = rejection(SyncError::InvalidPushFrameConflict(5, 7));
+        assert_eq!(rejected, Some(PushRejection::FrameConflict));
+        assert_eq!(rejected.unwrap().status(), None);
+        assert_eq!(
+            rejection(SyncError::InvalidLocalGeneration(2, 1)),
+            Some(PushRejection::LowerGeneration)
+        );
+    }
+
+    #[test]
+    fn other_errors() {
+        assert_eq!(
+            rejection(SyncError::PushFrame(StatusCode(503), "".into())),
+            None
+        );
+        assert_eq!(
+            rejection(SyncError::PullFrame(StatusCode(409), "".into())),
+            None
+        );
+        assert_eq!(rejection(SyncError::InvalidPushFrameNoLow(5, 3)), None);
+        assert_eq!(
+            rejection(SyncError::HttpDispatch("connection refused".into())),
+            None
+        );
+        // The messages don't matter, only the variants.
+        assert_eq!(
+            PushRejection::of(&libsql::Error::Sync(
+                "server returned a conflict: sent=5, got=7".into()
+            )),
+            None
+        );
+        // Only sync errors are rejections of a push.
+        assert_eq!(
+            PushRejection::of(&libsql::Error::ConnectionFailed(
+                "InvalidPushFrameConflict(5, 7)".to_string()
+            )),
+            None
+        );
+        assert_eq!(leading_status("409, \"frame mismatch\""), Some(409));
+        assert_eq!(leading_status("\"409\""), None);
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "libsql-js-offline-{}-{}.db",
+            name,
+            std::process::id()
+        ));
+        remove_files(&path);
+        path
+    }
+
+    fn remove_files(path: &Path) {
+        for suffix in ["", "-wal", "-shm", "-info", "-pending"] {
+            let mut file = path.to_path_buf().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    fn millis(since: Option<SystemTime>) -> Option<u128> {
+        since.map(|since| since.duration_since(UNIX_EPOCH).unwrap().as_millis())
+    }
+
+    #[test]
+    fn pending_writes_keep_the_oldest_time() {
+        let path = temp_path("pending-writes");
+        let pending_writes = PendingWrites::open(&path);
+        assert_eq!(millis(*pending_writes.since.lock().unwrap()), None);
+
+        pending_writes.record();
+        let since = millis(*pending_writes.since.lock().unwrap());
+        assert!(since.is_some());
+        std::thread::sleep(Duration::from_millis(5));
+        pending_writes.record();
+        assert_eq!(millis(*pending_writes.since.lock().unwrap()), since);
+
+        let reopened = PendingWrites::open(&path);
+        assert_eq!(millis(*reopened.since.lock().unwrap()), since);
+        reopened.clear();
+        assert_eq!(
+            millis(*PendingWrites::open(&path).since.lock().unwrap()),
+            None
+        );
+        remove_files(&path);
+    }
+
+    #[test]
+    fn pending_changes_after_the_durable_frame() {
+        let path = temp_path("pending-changes");
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let db = rt
+            .block_on(libsql::Builder::new_local(&path).build())
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let exec = |sql: &str| rt.block_on(conn.execute_batch(sql)).unwrap();
+        rt.block_on(conn.query("PRAGMA journal_mode = WAL", ()))
+            .unwrap();
+        exec("PRAGMA wal_autocheckpoint = 0");
+        let pending_writes = PendingWrites::open(&path);
+
+        // A write that changes no rows is recorded by the growth of the WAL.
+        let before = pending_writes.wal_size();
+        exec("CREATE TABLE t (x)");
+        pending_writes.record_appended(before);
+        assert!(pending_writes.since.lock().unwrap().is_some());
+        let before = pending_writes.wal_size();
+        exec("SELECT * FROM t");
+        pending_writes.record_appended(before);
+        assert_eq!(pending_writes.wal_size(), before);
+
+        // The frames up to the durable frame have been pushed.
+        let durable_frame_no = wal_frame_count(&path).unwrap();
+        let mut info = path.clone().into_os_string();
+        info.push("-info");
+        std::fs::write(
+            &info,
+            format!(r#"{{"generation":1,"durable_frame_num":{durable_frame_no}}}"#),
+        )
+        .unwrap();
+        let changes = PendingChanges::read(&path, &pending_writes).unwrap();
+        assert_eq!((changes.frames, changes.transactions), (0, 0));
+        assert_eq!(changes.since, None);
+
+        exec("INSERT INTO t VALUES (1)");
+        exec("INSERT INTO t VALUES (2)");
+        let changes = PendingChanges::read(&path, &pending_writes).unwrap();
+        let frames = wal_frame_count(&path).unwrap() - durable_frame_no;
+        assert_eq!((changes.frames, changes.transactions), (frames, 2));
+        assert_eq!(changes.bytes, frames * (24 + 4096));
+        assert!(changes.since.is_some());
+        assert_eq!(pending_frames(&path, &SyncMetadata::read(&path)), frames);
+
+        drop(conn);
+        drop(db);
+        remove_files(&path);
+    }
+}
@@ -0,0 +1,381 @@
+use neon::prelude::*;
+
+use crate::transfer::quote_identifier;
+
+/// The tables, views and triggers of a database schema, such as `main`,
+/// `temp` or an attached database.
+pub(crate) struct Schema {
+    name: String,
+    tables: Vec<Table>,
+    views: Vec<View>,
+    triggers: Vec<Trigger>,
+}
+
+struct Table {
+    name: String,
+    sql: Option<String>,
+    columns: Vec<Column>,
+    indexes: Vec<Index>,
+    foreign_keys: Vec<ForeignKey>,
+}
+
+struct View {
+    name: String,
+    sql: Option<String>,
+    columns: Vec<Column>,
+}
+
+struct Trigger {
+    name: String,
+    table: String,
+    sql: Option<String>,
+}
+
+struct Column {
+    name: String,
+    decl_type: String,
+    not_null: bool,
+    default: Option<String>,
+    /// Position of the column in the primary key starting from 1, or 0 if
+    /// the column is not part of it.
+    primary_key: i64,
+    /// Value of the `hidden` column of `pragma_table_xinfo`.
+    hidden: i64,
+}
+
+struct Index {
+    name: String,
+    unique: bool,
+    origin: String,
+    partial: bool,
+    columns: Vec<Option<String>>,
+}
+
+struct ForeignKey {
+    table: String,
+    from: Vec<String>,
+    to: Vec<Option<String>>,
+    on_update: String,
+    on_delete: String,
+}
+
+/// Reads the schemas of all databases attached to the connection from the
+/// schema table and the pragma table-valued functions.
+pub(crate) async fn read_schemas(conn: &libsql::Connection) -> libsql::Result<Vec<Schema>> {
+    let mut names = Vec::new();
+    let mut rows = conn
+        .query("SELECT name FROM pragma_database_list", ())
+        .await?;
+    while let Some(row) = rows.next().await? {
+        names.push(text(&row, 0)?);
+    }
+    let mut schemas = Vec::with_capacity(names.len());
+    for name in names {
+        schemas.push(read_schema(conn, name).await?);
+    }
+    Ok(schemas)
+}
+
+async fn read_schema(conn: &libsql::Connection, name: String) -> libsql::Result<Schema> {
+    let mut schema = Schema {
+        name,
+        tables: Vec::new(),
+        views: Vec::new(),
+        triggers: Vec::new(),
+    };
+    let sql = format!(
+        "SELECT type, name, tbl_name, sql FROM {}.sqlite_schema \
+         WHERE type IN ('table', 'view', 'trigger') AND name NOT LIKE 'sqlite_%' \
+         ORDER BY name",
+        quote_identifier(&schema.name)
+    );
+    let mut entries = Vec::new();
+    let mut rows = conn.query(&sql, ()).await?;
+    while let Some(row) = rows.next().await? {
+        entries.push((
+            text(&row, 0)?,
+            text(&row, 1)?,
+            text(&row, 2)?,
+            nullable_text(&row, 3)?,
+        ));
+    }
+    for (kind, name, table, sql) in entries {
+        match kind.as_str() {
+            "table" => {
+                let columns = read_columns(conn, &schema.name, &name).await?;
+                let indexes = read_indexes(conn, &schema.name, &name).await?;
+                let foreign_keys = read_foreign_keys(conn, &schema.name, &name).await?;
+                schema.tables.push(Table {
+                    name,
+                    sql,
+                    columns,
+                    indexes,
+                    foreign_keys,
+                });
+            }
+            "view" => {
+                let columns = read_columns(conn, &schema.name, &name).await?;
+                schema.views.push(View { name, sql, columns });
+            }
+            _ => schema.triggers.push(Trigger { name, table, sql }),
+        }
+    }
+    Ok(schema)
+}
+
+async fn read_columns(
+    conn: &libsql::Connection,
+    schema: &str,
+    table: &str,
+) -> libsql::Result<Vec<Column>> {
+    let mut columns = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT name, type, \"notnull\", dflt_value, pk, hidden \
+             FROM pragma_table_xinfo(?1, ?2) ORDER BY cid",
+            [table, schema],
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        columns.push(Column {
+            name: text(&row, 0)?,
+            decl_type: text(&row, 1)?,
+            not_null: integer(&row, 2)? != 0,
+            default: nullable_text(&row, 3)?,
+            primary_key: integer(&row, 4)?,
+            hidden: integer(&row, 5)?,
+        });
+    }
+    Ok(columns)
+}
+
+async fn read_indexes(
+    conn: &libsql::Connection,
+    schema: &str,
+    table: &str,
+) -> libsql::Result<Vec<Index>> {
+    let mut indexes = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT name, \"unique\", origin, partial FROM pragma_index_list(?1, ?2) \
+             ORDER BY name",
+            [table, schema],
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        indexes.push(Index {
+            name: text(&row, 0)?,
+            unique: integer(&row, 1)? != 0,
+            origin: text(&row, 2)?,
+            partial: integer(&row, 3)? != 0,
+            columns: Vec::new(),
+        });
+    }
+    for index in &mut indexes {
+        let mut rows = conn
+            .query(
+                "SELECT name FROM pragma_index_info(?1, ?2) ORDER BY seqno",
+                [index.name.as_str(), schema],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            // Expressions in the index have no column name.
+            index.columns.push(nullable_text(&row, 0)?);
+        }
+    }
+    Ok(indexes)
+}
+
+async fn read_foreign_keys(
+    conn: &libsql::Connection,
+    schema: &str,
+    table: &str,
+) -> libsql::Result<Vec<ForeignKey>> {
+    let mut foreign_keys: Vec<(i64, ForeignKey)> = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete \
+             FROM pragma_foreign_key_list(?1, ?2) ORDER BY id, seq",
+            [table, schema],
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        let id = integer(&row, 0)?;
+        // The columns of a composite foreign key are returned as one row each.
+        if foreign_keys.last().map(|(last, _)| *last) != Some(id) {
+            let foreign_key = ForeignKey {
+                table: text(&row, 1)?,
+                from: Vec::new(),
+                to: Vec::new(),
+                on_update: text(&row, 4)?,
+                on_delete: text(&row, 5)?,
+            };
+            foreign_keys.push((id, foreign_key));
+        }
+        let (_, foreign_key) = foreign_keys.last_mut().unwrap();
+        foreign_key.from.push(text(&row, 2)?);
+        // The referenced columns are NULL if the parent table's primary key
+        // is referenced implicitly.
+        foreign_key.to.push(nullable_text(&row, 3)?);
+    }
+    Ok(foreign_keys.into_iter().map(|(_, fk)| fk).collect())
+}
+
+fn nullable_text(row: &libsql::Row, idx: i32) -> libsql::Result<Option<String>> {
+    match row.get_value(idx)? {
+        libsql::Value::Text(v) => Ok(Some(v)),
+        libsql::Value::Integer(v) => Ok(Some(v.to_string())),
+        libsql::Value::Real(v) => Ok(Some(v.to_string())),
+        libsql::Value::Null | libsql::Value::Blob(_) => Ok(None),
+    }
+}
+
+fn text(row: &libsql::Row, idx: i32) -> libsql::Result<String> {
+    Ok(nullable_text(row, idx)?.unwrap_or_default())
+}
+
+fn integer(row: &libsql::Row, idx: i32) -> libsql::Result<i64> {
+    match row.get_value(idx)? {
+        libsql::Value::Integer(v) => Ok(v),
+        _ => Ok(0),
+    }
+}
+
+pub(crate) fn convert_schemas<'a>(
+    cx: &mut impl Context<'a>,
+    schemas: &[Schema],
+) -> JsResult<'a, JsArray> {
+    let result = cx.empty_array();
+    for (i, schema) in schemas.iter().enumerate() {
+        let obj = cx.empty_object();
+        let name = cx.string(&schema.name);
+        obj.set(cx, "name", name)?;
+        let tables = cx.empty_array();
+        for (i, table) in schema.tables.iter().enumerate() {
+            let table = convert_table(cx, table)?;
+            tables.set(cx, i as u32, table)?;
+        }
+        obj.set(cx, "tables", tables)?;
+        let views = cx.empty_array();
+        for (i, view) in schema.views.iter().enumerate() {
+            let obj = cx.empty_object();
+            let name = cx.string(&view.name);
+            obj.set(cx, "name", name)?;
+            let columns = convert_columns(cx, &view.columns)?;
+            obj.set(cx, "columns", columns)?;
+            let sql = convert_nullable_text(cx, &view.sql);
+            obj.set(cx, "sql", sql)?;
+            views.set(cx, i as u32, obj)?;
+        }
+        obj.set(cx, "views", views)?;
+        let triggers = cx.empty_array();
+        for (i, trigger) in schema.triggers.iter().enumerate() {
+            let obj = cx.empty_object();
+            let name = cx.string(&trigger.name);
+            obj.set(cx, "name", name)?;
+            let table = cx.string(&trigger.table);
+            obj.set(cx, "table", table)?;
+            let sql = convert_nullable_text(cx, &trigger.sql);
+            obj.set(cx, "sql", sql)?;
+            triggers.set(cx, i as u32, obj)?;
+        }
+        obj.set(cx, "triggers", triggers)?;
+        result.set(cx, i as u32, obj)?;
+    }
+    Ok(result)
+}
+
+fn convert_table<'a>(cx: &mut impl Context<'a>, table: &Table) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+    let name = cx.string(&table.name);
+    obj.set(cx, "name", name)?;
+    let columns = convert_columns(cx, &table.columns)?;
+    obj.set(cx, "columns", columns)?;
+    let indexes = cx.empty_array();
+    for (i, index) in table.indexes.iter().enumerate() {
+        let obj = cx.empty_object();
+        let name = cx.string(&index.name);
+        obj.set(cx, "name", name)?;
+        let unique = cx.boolean(index.unique);
+        obj.set(cx, "unique", unique)?;
+        let origin = cx.string(&index.origin);
+        obj.set(cx, "origin", origin)?;
+        let partial = cx.boolean(index.partial);
+        obj.set(cx, "partial", partial)?;
+        let columns = convert_text_array(cx, &index.columns)?;
+        obj.set(cx, "columns", columns)?;
+        indexes.set(cx, i as u32, obj)?;
+    }
+    obj.set(cx, "indexes", indexes)?;
+    let foreign_keys = cx.empty_array();
+    for (i, foreign_key) in table.foreign_keys.iter().enumerate() {
+        let obj = cx.empty_object();
+        let table = cx.string(&foreign_key.table);
+        obj.set(cx, "table", table)?;
+        let from = foreign_key
+            .from
+            .iter()
+            .cloned()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let from = convert_text_array(cx, &from)?;
+        obj.set(cx, "from", from)?;
+        let to = convert_text_array(cx, &foreign_key.to)?;
+        obj.set(cx, "to", to)?;
+        let on_update = cx.string(&foreign_key.on_update);
+        obj.set(cx, "onUpdate", on_update)?;
+        let on_delete = cx.string(&foreign_key.on_delete);
+        obj.set(cx, "onDelete", on_delete)?;
+        foreign_keys.set(cx, i as u32, obj)?;
+    }
+    obj.set(cx, "foreignKeys", foreign_keys)?;
+    let sql = convert_nullable_text(cx, &table.sql);
+    obj.set(cx, "sql", sql)?;
+    Ok(obj)
+}
+
+fn convert_columns<'a>(cx: &mut impl Context<'a>, columns: &[Column]) -> JsResult<'a, JsArray> {
+    let result = cx.empty_array();
+    for (i, column) in columns.iter().enumerate() {
+        let obj = cx.empty_object();
+        let name = cx.string(&column.name);
+        obj.set(cx, "name", name)?;
+        let decl_type = cx.string(&column.decl_type);
+        obj.set(cx, "type", decl_type)?;
+        let not_null = cx.boolean(column.not_null);
+        obj.set(cx, "notNull", not_null)?;
+        let default = convert_nullable_text(cx, &column.default);
+        obj.set(cx, "default", default)?;
+        let primary_key = cx.number(column.primary_key as f64);
+        obj.set(cx, "primaryKey", primary_key)?;
+        let hidden = cx.boolean(column.hidden == 1);
+        obj.set(cx, "hidden", hidden)?;
+        let generated: Handle<'_, JsValue> = match column.hidden {
+            2 => cx.string("virtual").upcast(),
+            3 => cx.string("stored").upcast(),
+            _ => cx.null().upcast(),
+        };
+        obj.set(cx, "generated", generated)?;
+        result.set(cx, i as u32, obj)?;
+    }
+    Ok(result)
+}
+
+fn convert_text_array<'a>(
+    cx: &mut impl Context<'a>,
+    values: &[Option<String>],
+) -> JsResult<'a, JsArray> {
+    let result = cx.empty_array();
+    for (i, v) in values.iter().enumerate() {
+        let v = convert_nullable_text(cx, v);
+        result.set(cx, i as u32, v)?;
+    }
+    Ok(result)
+}
+
+fn convert_nullable_text<'a>(cx: &mut impl Context<'a>, v: &Option<String>) -> Handle<'a, JsValue> {
+    match v {
+        Some(v) => cx.string(v).upcast(),
+        None => cx.null().upcast(),
+    }
+}
@@ -0,0 +1,190 @@
+use fallible_iterator::FallibleIterator;
+use neon::prelude::*;
+use sqlite3_parser::ast::{Cmd, Stmt, TransactionType};
+use sqlite3_parser::lexer::sql::Parser;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where the statements of a database are executed.
+#[derive(Clone, Copy)]
+pub(crate) enum Location {
+    Local,
+    Remote,
+    /// An embedded replica, which executes reads locally and delegates writes
+    /// to the primary.
+    Replica,
+}
+
+/// Reports statements that take longer than a threshold to a JavaScript
+/// callback.
+pub(crate) struct SlowQueryLog {
+    threshold: Duration,
+    location: Location,
+    callback: Arc<Root<JsFunction>>,
+    channel: Channel,
+}
+
+impl SlowQueryLog {
+    pub fn new<'a, C: Context<'a>>(
+        cx: &mut C,
+        threshold: Duration,
+        location: Location,
+        callback: Handle<'a, JsFunction>,
+    ) -> Self {
+        let mut channel = cx.channel();
+        // Reporting slow queries must not keep the process alive.
+        channel.unref(cx);
+        SlowQueryLog {
+            threshold,
+            location,
+            callback: Arc::new(callback.root(cx)),
+            channel,
+        }
+    }
+
+    /// Starts timing an execution of `sql` with the given bind parameters.
+    pub fn query(self: &Arc<Self>, sql: &str, params: &libsql::params::Params) -> SlowQuery {
+        SlowQuery {
+            log: self.clone(),
+            sql: sql.to_string(),
+            parameters: summarize_params(params),
+        }
+    }
+
+    fn mode(&self, sql: &str) -> &'static str {
+        match self.location {
+            Location::Local => "local",
+            Location::Remote => "remote",
+            Location::Replica if is_read(sql) => "local",
+            Location::Replica => "delegated",
+        }
+    }
+}
+
+/// An execution of a statement that is reported if it turns out to be slow.
+pub(crate) struct SlowQuery {
+    log: Arc<SlowQueryLog>,
+    sql: String,
+    parameters: String,
+}
+
+impl SlowQuery {
+    pub fn finish(&self, duration: Duration) {
+        if duration <= self.log.threshold {
+            return;
+        }
+        let callback = self.log.callback.clone();
+        let sql = self.sql.clone();
+        let parameters = self.parameters.clone();
+        let mode = self.log.mode(&sql);
+        self.log.channel.send(move |mut cx| {
+            let callback = callback.to_inner(&mut cx);
+            let event = cx.empty_object();
+            let sql = cx.string(sql);
+            event.set(&mut cx, "sql", sql)?;
+            let parameters = cx.string(parameters);
+            event.set(&mut cx, "parameters", parameters)?;
+            let duration_ms = cx.number(duration.as_secs_f64() * 1000.0);
+            event.set(&mut cx, "durationMs", duration_ms)?;
+            let mode = cx.string(mode);
+            event.set(&mut cx, "mode", mode)?;
+            callback.call_with(&cx).arg(event).exec(&mut cx)
+        });
+    }
+}
+
+/// Returns true if every statement in `sql` is a read that an embedded
+/// replica executes locally. The statements are classified the same way as
+/// libSQL does when it decides whether to delegate them to the primary.
+fn is_read(sql: &str) -> bool {
+    let mut parser = Parser::new(sql.as_bytes());
+    loop {
+        match parser.next() {
+            Ok(Some(cmd)) if is_read_cmd(&cmd) => {}
+            Ok(Some(_)) | Err(_) => return false,
+            Ok(None) => return true,
+        }
+    }
+}
+
+fn is_read_cmd(cmd: &Cmd) -> bool {
+    match cmd {
+        Cmd::Stmt(Stmt::Select { .. }) => true,
+        Cmd::Stmt(Stmt::Begin(Some(TransactionType::ReadOnly), _)) => true,
+        Cmd::Stmt(
+            Stmt::Commit { .. }
+            | Stmt::Rollback {
+                savepoint_name: None,
+                ..
+            },
+        ) => true,
+        Cmd::Stmt(Stmt::Pragma(name, _)) => matches!(
+            name.name.0.as_str(),
+            "table_list"
+                | "index_list"
+                | "table_info"
+                | "table_xinfo"
+                | "index_info"
+                | "index_xinfo"
+                | "pragma_list"
+                | "compile_options"
+                | "database_list"
+                | "function_list"
+                | "module_list"
+                | "encoding"
+        ),
+        _ => false,
+    }
+}
+
+/// Describes the bind parameters by their types, so that their values are
+/// not leaked into logs.
+fn summarize_params(params: &libsql::params::Params) -> String {
+    match params {
+        libsql::params::Params::None => String::new(),
+        libsql::params::Params::Positional(values) => values
+            .iter()
+            .map(summarize_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        libsql::params::Params::Named(values) => values
+            .iter()
+            .map(|(name, v)| format!("{}={}", name, summarize_value(v)))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+fn summarize_value(v: &libsql::Value) -> String {
+    match v {
+        libsql::Value::Null => "null".to_string(),
+        libsql::Value::Integer(_) => "integer".to_string(),
+        libsql::Value::Real(_) => "real".to_string(),
+        libsql::Value::Text(v) => format!("text({})", v.len()),
+        libsql::Value::Blob(v) => format!("blob({})", v.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_read;
+
+    #[test]
+    fn classifies_reads() {
+        assert!(is_read("SELECT * FROM t"));
+        assert!(is_read("-- comment\n  select 1; VALUES (1)"));
+        assert!(is_read("WITH x AS (SELECT 1) SELECT * FROM x"));
+        assert!(is_read("PRAGMA table_info(t)"));
+        assert!(is_read("BEGIN READONLY; SELECT 1; COMMIT"));
+    }
+
+    #[test]
+    fn classifies_writes() {
+        assert!(!is_read("INSERT INTO t VALUES (1)"));
+        assert!(!is_read("WITH x AS (SELECT 1) DELETE FROM t WHERE a IN x"));
+        assert!(!is_read("SELECT 1; UPDATE t SET a = 1"));
+        assert!(!is_read("PRAGMA user_version = 1"));
+        assert!(!is_read("BEGIN"));
+        assert!(!is_read("SELECT FROM"));
+    }
+}
@@ -1,22 +1,110 @@
-use neon::types::buffer::TypedArray;
+use neon::types::buffer::{Binary, TypedArray};
 use neon::types::JsPromise;
 use neon::{prelude::*, types::JsBigInt};
 use tokio::time::Instant;
 use std::cell::RefCell;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::collections::HashSet;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tracing::trace;
 
-use crate::errors::throw_libsql_error;
+use crate::arrow::{self, ArrowStream};
+use crate::errors::{throw_database_closed_error, throw_libsql_error};
+use crate::offline::PendingWrites;
 use crate::runtime;
+use crate::slow_query::{SlowQuery, SlowQueryLog};
+use crate::statement_cache::StatementCache;
+use crate::stats::ExecutionStats;
+use crate::stmt_status::StatementHandle;
+use crate::sync::{DelegatedWrite, SharedDatabase};
+use crate::transfer::{export_rows, parse_format, throw_transfer_error, TransferError};
 
 pub(crate) struct Statement {
-    pub conn: Arc<Mutex<libsql::Connection>>,
-    pub stmt: Arc<Mutex<libsql::Statement>>,
+    pub sql: String,
+    pub conn: Weak<Mutex<libsql::Connection>>,
+    /// The prepared statement, which is taken out when the database is closed.
+    pub stmt: Arc<Mutex<Option<libsql::Statement>>>,
+    /// The SQLite statement behind the prepared statement, if it was prepared
+    /// on a local connection.
+    pub status: Option<StatementHandle>,
+    /// Token held by open iterators and by queries started by the async API,
+    /// so that the statement isn't returned to the statement cache while it is
+    /// in use.
+    pub in_use: Arc<()>,
+    /// The statement cache that the prepared statement is returned to when
+    /// this object is finalized or garbage collected.
+    pub cache: Weak<Mutex<StatementCache>>,
+    pub handles: Arc<Mutex<Handles>>,
+    pub finalized: RefCell<bool>,
     pub raw: RefCell<bool>,
     pub safe_ints: RefCell<bool>,
+    pub stats: Arc<ExecutionStats>,
+    pub slow_query_log: Option<Arc<SlowQueryLog>>,
+    pub pending_writes: Option<Arc<PendingWrites>>,
+    /// The database of an embedded replica, which delegates writes to the
+    /// primary.
+    pub replica: Option<Weak<SharedDatabase>>,
 }
 
-impl Finalize for Statement {}
+impl Finalize for Statement {
+    fn finalize<'a, C: Context<'a>>(self, _: &mut C) {
+        self.release();
+    }
+}
+
+/// Statements and rows opened on a database connection, which are finalized
+/// when the database is closed so that the connection is closed with it.
+#[derive(Default)]
+pub(crate) struct Handles {
+    statements: Vec<Weak<Mutex<Option<libsql::Statement>>>>,
+    rows: Vec<Weak<Mutex<Option<libsql::Rows>>>>,
+}
+
+impl Handles {
+    pub fn add_statement(&mut self, stmt: &Arc<Mutex<Option<libsql::Statement>>>) {
+        self.statements.retain(|stmt| stmt.strong_count() > 0);
+        self.statements.push(Arc::downgrade(stmt));
+    }
+
+    pub fn add_rows(&mut self, rows: &Arc<Mutex<Option<libsql::Rows>>>) {
+        self.rows.retain(|rows| rows.strong_count() > 0);
+        self.rows.push(Arc::downgrade(rows));
+    }
+
+    pub fn finalize(&mut self) {
+        for rows in self.rows.drain(..) {
+            if let Some(rows) = rows.upgrade() {
+                rows.blocking_lock().take();
+            }
+        }
+        for stmt in self.statements.drain(..) {
+            if let Some(stmt) = stmt.upgrade() {
+                stmt.blocking_lock().take();
+            }
+        }
+    }
+}
+
+/// Locks the prepared statement, returning `None` if its database has been
+/// closed, and adds the status counters of its previous executions to `stats`.
+async fn lock_statement<'a>(
+    stmt: &'a Mutex<Option<libsql::Statement>>,
+    status: Option<StatementHandle>,
+    stats: &ExecutionStats,
+) -> Option<MappedMutexGuard<'a, libsql::Statement>> {
+    let raw_stmt = MutexGuard::try_map(stmt.lock().await, Option::as_mut).ok()?;
+    add_status(status, stats);
+    Some(raw_stmt)
+}
+
+/// Adds the status counters accumulated by the SQLite statement since they
+/// were last added to `stats`. The prepared statement must be locked.
+fn add_status(status: Option<StatementHandle>, stats: &ExecutionStats) {
+    if let Some(status) = status {
+        stats.add_status(status.take());
+    }
+}
 
 fn js_value_to_value(
     cx: &mut FunctionContext,
@@ -24,12 +112,14 @@ fn js_value_to_value(
 ) -> NeonResult<libsql::Value> {
     if v.is_a::<JsNull, _>(cx) || v.is_a::<JsUndefined, _>(cx) {
         Ok(libsql::Value::Null)
-    } else if v.is_a::<JsBoolean, _>(cx) {
-        todo!("bool");
     } else if v.is_a::<JsNumber, _>(cx) {
         let v = v.downcast_or_throw::<JsNumber, _>(cx)?;
         let v = v.value(cx);
         Ok(libsql::Value::Real(v))
+    } else if v.is_a::<JsBoolean, _>(cx) {
+        let v = v.downcast_or_throw::<JsBoolean, _>(cx)?;
+        let v = v.value(cx);
+        Ok(libsql::Value::Integer(v as i64))
     } else if v.is_a::<JsString, _>(cx) {
         let v = v.downcast_or_throw::<JsString, _>(cx)?;
         let v = v.value(cx);
@@ -53,9 +143,114 @@ fn js_value_to_value(
 }
 
 impl Statement {
+    /// Locks the prepared statement, throwing if it has been finalized or its
+    /// database has been closed, and adds the status counters of its previous
+    /// executions to the statistics.
+    fn lock_sync<'a, C: Context<'a>>(
+        &self,
+        cx: &mut C,
+    ) -> NeonResult<MappedMutexGuard<'_, libsql::Statement>> {
+        if *self.finalized.borrow() {
+            return cx.throw_type_error("The statement has been finalized");
+        }
+        match MutexGuard::try_map(self.stmt.blocking_lock(), Option::as_mut) {
+            Ok(raw_stmt) => {
+                add_status(self.status, &self.stats);
+                Ok(raw_stmt)
+            }
+            Err(_) => throw_database_closed_error(cx),
+        }
+    }
+
+    /// Returns the prepared statement to the statement cache if it is idle,
+    /// or finalizes it otherwise.
+    fn release(&self) {
+        if self.finalized.replace(true) {
+            return;
+        }
+        if Arc::strong_count(&self.in_use) > 1 {
+            // An iterator is open or a query started by the async API hasn't
+            // finished yet, and the statement is dropped with the last of them.
+            return;
+        }
+        let Ok(mut raw_stmt) = self.stmt.try_lock() else {
+            return;
+        };
+        match self.cache.upgrade() {
+            Some(cache) if raw_stmt.is_some() && self.conn.strong_count() > 0 => {
+                if let Some(raw_stmt) = raw_stmt.as_mut() {
+                    raw_stmt.reset();
+                }
+                // The counters are reset, so that the next owner's statistics
+                // start from zero.
+                add_status(self.status, &self.stats);
+                drop(raw_stmt);
+                cache
+                    .blocking_lock()
+                    .put(self.sql.clone(), self.stmt.clone(), self.status);
+            }
+            _ => {
+                raw_stmt.take();
+            }
+        }
+    }
+
+    /// Records a write that changed rows of an offline database.
+    fn record_write(&self) {
+        if let Some(pending_writes) = &self.pending_writes {
+            pending_writes.record();
+        }
+    }
+
+    /// Starts tracking a write that an embedded replica delegates to the
+    /// primary. The connection must stay locked until the write is finished.
+    fn delegated_write(&self) -> Option<DelegatedWrite> {
+        let db = self.replica.as_ref()?.upgrade()?;
+        Some(DelegatedWrite::start(db))
+    }
+
+    /// Returns the names of the statement's parameters, or `None` if some
+    /// parameters are positional. The statement is unlocked again before the
+    /// caller reads bind parameters from JavaScript objects, whose getters
+    /// may use the statement.
+    fn parameter_names<'a, C: Context<'a>>(&self, cx: &mut C) -> NeonResult<Option<Vec<String>>> {
+        let raw_stmt = self.lock_sync(cx)?;
+        let names = (0..raw_stmt.parameter_count())
+            .map(|idx| {
+                raw_stmt
+                    .parameter_name((idx + 1) as i32)
+                    .map(str::to_string)
+            })
+            .collect();
+        Ok(names)
+    }
+
+    fn slow_query(&self, params: &libsql::params::Params) -> Option<SlowQuery> {
+        self.slow_query_log
+            .as_ref()
+            .map(|log| log.query(&self.sql, params))
+    }
+
+    fn get_conn<'a, C: Context<'a>>(
+        &self,
+        cx: &mut C,
+    ) -> NeonResult<Arc<Mutex<libsql::Connection>>> {
+        match self.conn.upgrade() {
+            Some(conn) => Ok(conn),
+            None => throw_database_closed_error(cx),
+        }
+    }
+
+    pub fn js_finalize(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        trace!("Finalizing statement");
+        stmt.release();
+        Ok(cx.undefined())
+    }
+
     pub fn js_raw(mut cx: FunctionContext) -> JsResult<JsNull> {
         let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
-        let raw_stmt = stmt.stmt.blocking_lock();
+        let raw_stmt = stmt.lock_sync(&mut cx)?;
         if raw_stmt.columns().is_empty() {
             return cx.throw_error("The raw() method is only for statements that return data");
         }
@@ -71,39 +266,48 @@ impl Statement {
 
     pub fn js_is_reader(mut cx: FunctionContext) -> JsResult<JsBoolean> {
         let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
-        let raw_stmt = stmt.stmt.blocking_lock();
+        let raw_stmt = stmt.lock_sync(&mut cx)?;
         Ok(cx.boolean(!raw_stmt.columns().is_empty()))
     }
 
     pub fn js_run(mut cx: FunctionContext) -> JsResult<JsValue> {
         let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
-        let raw_conn = stmt.conn.clone();
-        let total_changes_before = raw_conn.blocking_lock().total_changes();
+        let raw_conn = stmt.get_conn(&mut cx)?;
         let params = cx.argument::<JsValue>(0)?;
         let params = convert_params(&mut cx, &stmt, params)?;
-        let mut raw_stmt = stmt.stmt.blocking_lock();
+        let slow_query = stmt.slow_query(&params);
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        // The connection is held across the write, so that the changes and
+        // the replication index are the write's own.
+        let raw_conn = raw_conn.blocking_lock();
+        let total_changes_before = raw_conn.total_changes();
+        let delegated_write = stmt.delegated_write();
         raw_stmt.reset();
         let fut = raw_stmt.run(params);
         let rt = runtime(&mut cx)?;
 
         let initial = Instant::now();
 
-        rt.block_on(fut)
-            .or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let result = rt.block_on(fut);
 
         let duration = Instant::now() - initial;
+        if let Some(slow_query) = slow_query {
+            slow_query.finish(duration);
+        }
+        result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        stmt.stats.record(duration, 0);
 
-        let (changes, last_insert_rowid) = {
-            let raw_conn = stmt.conn.clone();
-            let raw_conn = raw_conn.blocking_lock();
-            let changes = if raw_conn.total_changes() == total_changes_before {
-                0
-            } else {
-                raw_conn.changes()
-            };
-            let last_insert_rowid = raw_conn.last_insert_rowid();
-            (changes, last_insert_rowid)
+        let changes = if raw_conn.total_changes() == total_changes_before {
+            0
+        } else {
+            stmt.record_write();
+            raw_conn.changes()
         };
+        let last_insert_rowid = raw_conn.last_insert_rowid();
+        // A write in a transaction only reaches the primary on commit.
+        let replication_index = delegated_write
+            .filter(|_| raw_conn.is_autocommit())
+            .and_then(DelegatedWrite::finish);
 
         let info = cx.empty_object();
 
@@ -116,6 +320,94 @@ impl Statement {
         let last_insert_row_id = cx.number(last_insert_rowid as f64);
         info.set(&mut cx, "lastInsertRowid", last_insert_row_id)?;
 
+        if let Some(replication_index) = replication_index {
+            let replication_index = cx.number(replication_index as f64);
+            info.set(&mut cx, "replicationIndex", replication_index)?;
+        }
+
+        Ok(info.upcast())
+    }
+
+    pub fn js_run_many(mut cx: FunctionContext) -> JsResult<JsValue> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let batch = cx.argument::<JsValue>(0)?;
+        let batch = convert_batch(&mut cx, &stmt, batch)?;
+        let use_transaction = cx.argument::<JsBoolean>(1)?.value(&mut cx);
+        let raw_conn = stmt.get_conn(&mut cx)?;
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        let rt = runtime(&mut cx)?;
+
+        let initial = Instant::now();
+
+        let result = rt.block_on(async {
+            let raw_conn = raw_conn.lock().await;
+            let total_changes_before = raw_conn.total_changes();
+            let delegated_write = stmt.delegated_write();
+            // Only start a transaction if the caller is not already in one.
+            let begin = use_transaction && raw_conn.is_autocommit();
+            if begin {
+                raw_conn.execute("BEGIN", ()).await?;
+            }
+            let mut result = Ok(());
+            for params in batch {
+                raw_stmt.reset();
+                if let Err(err) = raw_stmt.run(params).await {
+                    result = Err(err);
+                    break;
+                }
+            }
+            raw_stmt.reset();
+            if begin {
+                if result.is_ok() {
+                    // A failed commit, such as one that finds the database
+                    // busy, leaves the transaction open.
+                    if let Err(err) = raw_conn.execute("COMMIT", ()).await {
+                        result = Err(err);
+                    }
+                }
+                if result.is_err() {
+                    // The error that aborted the batch is reported even if
+                    // the rollback fails too.
+                    if let Err(err) = raw_conn.execute("ROLLBACK", ()).await {
+                        trace!("Failed to roll back batch: {}", err);
+                    }
+                }
+            }
+            // Without a transaction, the rows written before a failure stay
+            // written.
+            let changes = raw_conn.total_changes() - total_changes_before;
+            if changes > 0 && (result.is_ok() || !begin) {
+                stmt.record_write();
+            }
+            // The batch only reaches the primary on commit if it ran in a
+            // transaction started by the caller.
+            let replication_index = delegated_write
+                .filter(|_| raw_conn.is_autocommit())
+                .and_then(DelegatedWrite::finish);
+            result.map(|_| (changes, raw_conn.last_insert_rowid(), replication_index))
+        });
+        let (changes, last_insert_rowid, replication_index) =
+            result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+
+        let duration = Instant::now() - initial;
+        stmt.stats.record(duration, 0);
+
+        let info = cx.empty_object();
+
+        let changes = cx.number(changes as f64);
+        info.set(&mut cx, "changes", changes)?;
+
+        let duration = cx.number(duration.as_secs_f64());
+        info.set(&mut cx, "duration", duration)?;
+
+        let last_insert_row_id = cx.number(last_insert_rowid as f64);
+        info.set(&mut cx, "lastInsertRowid", last_insert_row_id)?;
+
+        if let Some(replication_index) = replication_index {
+            let replication_index = cx.number(replication_index as f64);
+            info.set(&mut cx, "replicationIndex", replication_index)?;
+        }
+
         Ok(info.upcast())
     }
 
@@ -124,19 +416,25 @@ impl Statement {
         let params = cx.argument::<JsValue>(0)?;
         let params = convert_params(&mut cx, &stmt, params)?;
         let safe_ints = *stmt.safe_ints.borrow();
-        let mut raw_stmt = stmt.stmt.blocking_lock();
+        let slow_query = stmt.slow_query(&params);
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
         let fut = raw_stmt.query(params);
         let rt = runtime(&mut cx)?;
-        let result = rt.block_on(fut);
-        let mut rows = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
 
         let initial = Instant::now();
 
-        let result = rt
-            .block_on(rows.next())
-            .or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let result = rt.block_on(async move {
+            let mut rows = fut.await?;
+            let row = rows.next().await?;
+            Ok::<_, libsql::Error>((rows, row))
+        });
 
         let duration = Instant::now() - initial;
+        if let Some(slow_query) = slow_query {
+            slow_query.finish(duration);
+        }
+        let (rows, result) = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        stmt.stats.record(duration, result.is_some() as u64);
 
         let result = match result {
             Some(row) => {
@@ -167,17 +465,30 @@ impl Statement {
         let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
         let params = cx.argument::<JsValue>(0)?;
         let params = convert_params(&mut cx, &stmt, params)?;
+        let slow_query = stmt.slow_query(&params);
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
         let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
         let result = rt.block_on(async move {
-            let mut raw_stmt = stmt.stmt.lock().await;
             raw_stmt.reset();
             raw_stmt.query(params).await
         });
+        if let (Err(_), Some(slow_query)) = (&result, &slow_query) {
+            slow_query.finish(Instant::now() - initial);
+        }
         let rows = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let rows = Arc::new(Mutex::new(Some(rows)));
+        stmt.handles.blocking_lock().add_rows(&rows);
         let rows = Rows {
-            rows: RefCell::new(rows),
+            rows,
+            iterator: RefCell::new(Some(stmt.in_use.clone())),
+            done: RefCell::new(false),
             raw: *stmt.raw.borrow(),
             safe_ints: *stmt.safe_ints.borrow(),
+            stats: stmt.stats.clone(),
+            elapsed: RefCell::new(Instant::now() - initial),
+            rows_returned: RefCell::new(0),
+            slow_query,
         };
         Ok(cx.boxed(rows).upcast())
     }
@@ -187,7 +498,7 @@ impl Statement {
         let params = cx.argument::<JsValue>(0)?;
         let params = convert_params(&mut cx, &stmt, params)?;
         {
-            let mut raw_stmt = stmt.stmt.blocking_lock();
+            let mut raw_stmt = stmt.lock_sync(&mut cx)?;
             raw_stmt.reset();
         }
         let (deferred, promise) = cx.promise();
@@ -196,22 +507,46 @@ impl Statement {
         let raw = *stmt.raw.borrow();
         let safe_ints = *stmt.safe_ints.borrow();
         let raw_stmt = stmt.stmt.clone();
+        let status = stmt.status;
+        let iterator = Some(stmt.in_use.clone());
+        let handles = stmt.handles.clone();
+        let stats = stmt.stats.clone();
+        let slow_query = stmt.slow_query(&params);
         rt.spawn(async move {
-            let result = {
-                let mut raw_stmt = raw_stmt.lock().await;
-                raw_stmt.query(params).await
+            let initial = Instant::now();
+            let result = match lock_statement(&raw_stmt, status, &stats).await {
+                Some(mut raw_stmt) => raw_stmt.query(params).await.map(Some),
+                None => Ok(None),
             };
+            let elapsed = Instant::now() - initial;
+            if let (Err(_), Some(slow_query)) = (&result, &slow_query) {
+                slow_query.finish(elapsed);
+            }
             match result {
-                Ok(rows) => {
+                Ok(Some(rows)) => {
                     deferred.settle_with(&channel, move |mut cx| {
+                        let rows = Arc::new(Mutex::new(Some(rows)));
+                        handles.blocking_lock().add_rows(&rows);
                         let rows = Rows {
-                            rows: RefCell::new(rows),
+                            rows,
+                            iterator: RefCell::new(iterator),
+                            done: RefCell::new(false),
                             raw,
                             safe_ints,
+                            stats,
+                            elapsed: RefCell::new(elapsed),
+                            rows_returned: RefCell::new(0),
+                            slow_query,
                         };
                         Ok(cx.boxed(rows))
                     });
                 }
+                Ok(None) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_database_closed_error(&mut cx)?;
+                        Ok(cx.undefined())
+                    });
+                }
                 Err(err) => {
                     deferred.settle_with(&channel, |mut cx| {
                         throw_libsql_error(&mut cx, err)?;
@@ -223,10 +558,414 @@ impl Statement {
         Ok(promise)
     }
 
+    pub fn js_all_sync(mut cx: FunctionContext) -> JsResult<JsValue> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let raw = *stmt.raw.borrow();
+        let safe_ints = *stmt.safe_ints.borrow();
+        let slow_query = stmt.slow_query(&params);
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        raw_stmt.reset();
+        let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
+        let result_arr = cx.empty_array();
+        let count = rt.block_on(async {
+            let mut rows = raw_stmt
+                .query(params)
+                .await
+                .or_else(|err| throw_libsql_error(&mut cx, err))?;
+            let mut keys = Vec::<Handle<JsString>>::with_capacity(rows.column_count() as usize);
+            for idx in 0..rows.column_count() {
+                let column_name = rows.column_name(idx).unwrap_or_default();
+                keys.push(cx.string(column_name));
+            }
+            let mut count = 0;
+            while let Some(row) = rows
+                .next()
+                .await
+                .or_else(|err| throw_libsql_error(&mut cx, err))?
+            {
+                if raw {
+                    let result = cx.empty_array();
+                    for idx in 0..rows.column_count() {
+                        let v = row
+                            .get_value(idx)
+                            .or_else(|err| throw_libsql_error(&mut cx, err))?;
+                        let v = convert_value(&mut cx, safe_ints, v)?;
+                        result.set(&mut cx, idx as u32, v)?;
+                    }
+                    result_arr.set(&mut cx, count, result)?;
+                } else {
+                    let result = cx.empty_object();
+                    for idx in 0..rows.column_count() {
+                        let v = row
+                            .get_value(idx)
+                            .or_else(|err| throw_libsql_error(&mut cx, err))?;
+                        let v = convert_value(&mut cx, safe_ints, v)?;
+                        result.set(&mut cx, keys[idx as usize], v)?;
+                    }
+                    result_arr.set(&mut cx, count, result)?;
+                }
+                count += 1;
+            }
+            Ok(count)
+        });
+        raw_stmt.reset();
+        let duration = Instant::now() - initial;
+        if let Some(slow_query) = slow_query {
+            slow_query.finish(duration);
+        }
+        let count = count?;
+        stmt.stats.record(duration, count as u64);
+        Ok(result_arr.upcast())
+    }
+
+    pub fn js_all_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let rt = runtime(&mut cx)?;
+        let raw = *stmt.raw.borrow();
+        let safe_ints = *stmt.safe_ints.borrow();
+        let raw_stmt = stmt.stmt.clone();
+        let status = stmt.status;
+        let in_use = stmt.in_use.clone();
+        let stats = stmt.stats.clone();
+        let slow_query = stmt.slow_query(&params);
+        rt.spawn(async move {
+            let result = async {
+                let Some(mut raw_stmt) = lock_statement(&raw_stmt, status, &stats).await else {
+                    return Ok(None);
+                };
+                let initial = Instant::now();
+                raw_stmt.reset();
+                let result = async {
+                    let mut rows = raw_stmt.query(params).await?;
+                    let columns = (0..rows.column_count())
+                        .map(|idx| rows.column_name(idx).unwrap_or_default().to_string())
+                        .collect::<Vec<_>>();
+                    let mut values = Vec::new();
+                    while let Some(row) = rows.next().await? {
+                        let mut row_values = Vec::with_capacity(columns.len());
+                        for idx in 0..rows.column_count() {
+                            row_values.push(row.get_value(idx)?);
+                        }
+                        values.push(row_values);
+                    }
+                    Ok::<_, libsql::Error>((columns, values))
+                }
+                .await;
+                raw_stmt.reset();
+                let duration = Instant::now() - initial;
+                if let Some(slow_query) = slow_query {
+                    slow_query.finish(duration);
+                }
+                let (columns, values) = result?;
+                stats.record(duration, values.len() as u64);
+                Ok::<_, libsql::Error>(Some((columns, values)))
+            }
+            .await;
+            drop(in_use);
+            match result {
+                Ok(Some((columns, values))) => {
+                    deferred.settle_with(&channel, move |mut cx| {
+                        convert_rows(&mut cx, raw, safe_ints, &columns, values)
+                    });
+                }
+                Ok(None) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_database_closed_error(&mut cx)?;
+                        Ok(cx.undefined())
+                    });
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_libsql_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
+    pub fn js_columnar(mut cx: FunctionContext) -> JsResult<JsValue> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let safe_ints = *stmt.safe_ints.borrow();
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        // Columns are keyed by name, so a duplicate name would hide a column.
+        let mut names = HashSet::new();
+        for col in raw_stmt.columns() {
+            if !names.insert(col.name().to_string()) {
+                return cx.throw_error(format!(
+                    "Duplicate column name in columnar result: {}; use aliases to make column names unique",
+                    col.name()
+                ));
+            }
+        }
+        raw_stmt.reset();
+        let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
+        let result = rt.block_on(async {
+            let mut rows = raw_stmt.query(params).await?;
+            let mut columns = (0..rows.column_count())
+                .map(|idx| {
+                    let name = rows.column_name(idx).unwrap_or_default().to_string();
+                    (name, Vec::new())
+                })
+                .collect::<Vec<(String, Vec<libsql::Value>)>>();
+            let mut len = 0;
+            while let Some(row) = rows.next().await? {
+                for (idx, (_, values)) in columns.iter_mut().enumerate() {
+                    values.push(row.get_value(idx as i32)?);
+                }
+                len += 1;
+            }
+            Ok::<_, libsql::Error>((columns, len))
+        });
+        raw_stmt.reset();
+        let (columns, len) = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        stmt.stats.record(Instant::now() - initial, len as u64);
+
+        let result = cx.empty_object();
+        let len_value = cx.number(len as f64);
+        result.set(&mut cx, "length", len_value)?;
+        let columns_obj = cx.empty_object();
+        let nulls_obj = cx.empty_object();
+        for (name, values) in columns {
+            let nulls = null_bitmap(&values);
+            let nulls = JsUint8Array::from_slice(&mut cx, &nulls)?;
+            nulls_obj.set(&mut cx, name.as_str(), nulls)?;
+            let column = convert_column(&mut cx, safe_ints, values)?;
+            columns_obj.set(&mut cx, name.as_str(), column)?;
+        }
+        result.set(&mut cx, "columns", columns_obj)?;
+        result.set(&mut cx, "nulls", nulls_obj)?;
+        Ok(result.upcast())
+    }
+
+    pub fn js_arrow(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        raw_stmt.reset();
+        let decl_types = raw_stmt
+            .columns()
+            .iter()
+            .map(|col| col.decl_type().map(|decl_type| decl_type.to_string()))
+            .collect::<Vec<_>>();
+        let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
+        let result = rt.block_on(raw_stmt.query(params));
+        let mut rows = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let names = (0..rows.column_count())
+            .map(|idx| rows.column_name(idx).unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        let mut stream = ArrowStream::new(names, decl_types);
+        let mut batch = Vec::with_capacity(arrow::BATCH_SIZE);
+        let mut count = 0;
+        while let Some(row) = rt
+            .block_on(rows.next())
+            .or_else(|err| throw_libsql_error(&mut cx, err))?
+        {
+            let mut values = Vec::with_capacity(rows.column_count() as usize);
+            for idx in 0..rows.column_count() {
+                let v = row
+                    .get_value(idx)
+                    .or_else(|err| throw_libsql_error(&mut cx, err))?;
+                values.push(v);
+            }
+            batch.push(values);
+            count += 1;
+            if batch.len() == arrow::BATCH_SIZE {
+                stream
+                    .write_batch(&batch)
+                    .or_else(|err| cx.throw_error(err.to_string()))?;
+                batch.clear();
+            }
+        }
+        stream
+            .write_batch(&batch)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+        drop(rows);
+        raw_stmt.reset();
+        stmt.stats.record(Instant::now() - initial, count);
+        let buf = stream
+            .finish()
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+        Ok(JsBuffer::external(&mut cx, buf))
+    }
+
+    pub fn js_arrow_batches(mut cx: FunctionContext) -> JsResult<JsValue> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        raw_stmt.reset();
+        let decl_types = raw_stmt
+            .columns()
+            .iter()
+            .map(|col| col.decl_type().map(|decl_type| decl_type.to_string()))
+            .collect::<Vec<_>>();
+        let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
+        let result = rt.block_on(raw_stmt.query(params));
+        let rows = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+        let names = (0..rows.column_count())
+            .map(|idx| rows.column_name(idx).unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        let rows = Arc::new(Mutex::new(Some(rows)));
+        stmt.handles.blocking_lock().add_rows(&rows);
+        let batches = ArrowBatches {
+            rows,
+            stream: RefCell::new(Some(ArrowStream::new(names, decl_types))),
+            iterator: RefCell::new(Some(stmt.in_use.clone())),
+            stats: stmt.stats.clone(),
+            elapsed: RefCell::new(Instant::now() - initial),
+            rows_returned: RefCell::new(0),
+        };
+        Ok(cx.boxed(batches).upcast())
+    }
+
+    pub fn js_export_sync(mut cx: FunctionContext) -> JsResult<JsNumber> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let format = cx.argument::<JsString>(1)?.value(&mut cx);
+        let format = parse_format(&mut cx, &format)?;
+        let path = cx.argument::<JsString>(2)?.value(&mut cx);
+        trace!("Exporting query results (sync): {}", path);
+        let mut raw_stmt = stmt.lock_sync(&mut cx)?;
+        let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
+        let result = rt.block_on(async {
+            raw_stmt.reset();
+            let mut rows = raw_stmt.query(params).await?;
+            let result = export_rows(&mut rows, format, &path).await;
+            drop(rows);
+            raw_stmt.reset();
+            result
+        });
+        let count = result.or_else(|err| throw_transfer_error(&mut cx, err))?;
+        stmt.stats.record(Instant::now() - initial, count);
+        Ok(cx.number(count as f64))
+    }
+
+    pub fn js_export_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let params = cx.argument::<JsValue>(0)?;
+        let params = convert_params(&mut cx, &stmt, params)?;
+        let format = cx.argument::<JsString>(1)?.value(&mut cx);
+        let format = parse_format(&mut cx, &format)?;
+        let path = cx.argument::<JsString>(2)?.value(&mut cx);
+        trace!("Exporting query results (async): {}", path);
+        let (deferred, promise) = cx.promise();
+        let channel = cx.channel();
+        let rt = runtime(&mut cx)?;
+        let raw_stmt = stmt.stmt.clone();
+        let status = stmt.status;
+        let in_use = stmt.in_use.clone();
+        let stats = stmt.stats.clone();
+        rt.spawn(async move {
+            let result = async {
+                let Some(mut raw_stmt) = lock_statement(&raw_stmt, status, &stats).await else {
+                    return Ok(None);
+                };
+                let initial = Instant::now();
+                raw_stmt.reset();
+                let mut rows = raw_stmt.query(params).await?;
+                let result = export_rows(&mut rows, format, &path).await;
+                drop(rows);
+                raw_stmt.reset();
+                let count = result?;
+                stats.record(Instant::now() - initial, count);
+                Ok::<_, TransferError>(Some(count))
+            }
+            .await;
+            drop(in_use);
+            match result {
+                Ok(Some(count)) => {
+                    deferred.settle_with(&channel, move |mut cx| Ok(cx.number(count as f64)));
+                }
+                Ok(None) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_database_closed_error(&mut cx)?;
+                        Ok(cx.undefined())
+                    });
+                }
+                Err(err) => {
+                    deferred.settle_with(&channel, |mut cx| {
+                        throw_transfer_error(&mut cx, err)?;
+                        Ok(cx.undefined())
+                    });
+                }
+            }
+        });
+        Ok(promise)
+    }
+
+    pub fn js_explain(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let bytecode = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+        drop(stmt.lock_sync(&mut cx)?);
+        let raw_conn = stmt.get_conn(&mut cx)?;
+        let sql = stmt.sql.clone();
+        trace!("Explaining SQL statement: {}", sql);
+        let rt = runtime(&mut cx)?;
+        let result = rt.block_on(async {
+            let raw_conn = raw_conn.lock().await;
+            let mut rows = raw_conn
+                .query(&format!("EXPLAIN QUERY PLAN {}", sql), ())
+                .await?;
+            let mut plan = Vec::new();
+            while let Some(row) = rows.next().await? {
+                plan.push(PlanNode {
+                    id: row.get(0)?,
+                    parent: row.get(1)?,
+                    detail: row.get(3)?,
+                });
+            }
+            let program = if bytecode {
+                let mut rows = raw_conn.query(&format!("EXPLAIN {}", sql), ()).await?;
+                let columns = (0..rows.column_count())
+                    .map(|idx| rows.column_name(idx).unwrap_or_default().to_string())
+                    .collect::<Vec<_>>();
+                let mut values = Vec::new();
+                while let Some(row) = rows.next().await? {
+                    let mut row_values = Vec::with_capacity(columns.len());
+                    for idx in 0..rows.column_count() {
+                        row_values.push(row.get_value(idx)?);
+                    }
+                    values.push(row_values);
+                }
+                Some((columns, values))
+            } else {
+                None
+            };
+            Ok::<_, libsql::Error>((plan, program))
+        });
+        let (plan, program) = result.or_else(|err| throw_libsql_error(&mut cx, err))?;
+
+        let result = cx.empty_object();
+        let plan = convert_plan(&mut cx, &plan, 0, &mut HashSet::new())?;
+        result.set(&mut cx, "plan", plan)?;
+        if let Some((columns, values)) = program {
+            let bytecode = convert_rows(&mut cx, false, false, &columns, values)?;
+            result.set(&mut cx, "bytecode", bytecode)?;
+        }
+        Ok(result)
+    }
+
     pub fn js_columns(mut cx: FunctionContext) -> JsResult<JsValue> {
         let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        let raw_stmt = stmt.lock_sync(&mut cx)?;
         let result = cx.empty_array();
-        let raw_stmt = stmt.stmt.blocking_lock();
         for (i, col) in raw_stmt.columns().iter().enumerate() {
             let column = cx.empty_object();
             let column_name = cx.string(col.name());
@@ -263,8 +1002,21 @@ impl Statement {
         Ok(result.upcast())
     }
 
+    pub fn js_stats(mut cx: FunctionContext) -> JsResult<JsObject> {
+        let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        // While a query started by the async API is running, the counters are
+        // left for a later call to add.
+        if let Ok(raw_stmt) = stmt.stmt.try_lock() {
+            if raw_stmt.is_some() {
+                add_status(stmt.status, &stmt.stats);
+            }
+        }
+        stmt.stats.to_object(&mut cx)
+    }
+
     pub fn js_safe_integers(mut cx: FunctionContext) -> JsResult<JsNull> {
         let stmt: Handle<'_, JsBox<Statement>> = cx.this()?;
+        drop(stmt.lock_sync(&mut cx)?);
         let toggle = cx.argument::<JsBoolean>(0)?;
         let toggle = toggle.value(&mut cx);
         stmt.set_safe_integers(toggle);
@@ -277,9 +1029,18 @@ impl Statement {
 }
 
 pub(crate) struct Rows {
-    rows: RefCell<libsql::Rows>,
+    /// The rows, which are dropped once they have all been read or the
+    /// iterator is closed, so that the read transaction doesn't outlive them.
+    rows: Arc<Mutex<Option<libsql::Rows>>>,
+    iterator: RefCell<Option<Arc<()>>>,
+    done: RefCell<bool>,
     raw: bool,
     safe_ints: bool,
+    stats: Arc<ExecutionStats>,
+    /// Wall time spent starting the query and reading its rows so far.
+    elapsed: RefCell<Duration>,
+    rows_returned: RefCell<u64>,
+    slow_query: Option<SlowQuery>,
 }
 
 impl Finalize for Rows {}
@@ -287,17 +1048,25 @@ impl Finalize for Rows {}
 impl Rows {
     pub fn js_next(mut cx: FunctionContext) -> JsResult<JsNull> {
         let result_arr = cx.argument::<JsArray>(0)?;
-        let rows: Handle<'_, JsBox<Rows>> = cx.this()?;
-        let raw = rows.raw;
-        let safe_ints = rows.safe_ints;
-        let mut rows = rows.rows.borrow_mut();
+        let this: Handle<'_, JsBox<Rows>> = cx.this()?;
+        let res = cx.null();
+        if *this.done.borrow() {
+            return Ok(res);
+        }
+        let raw = this.raw;
+        let safe_ints = this.safe_ints;
+        let mut slot = this.rows.blocking_lock();
+        let rows = match slot.as_mut() {
+            Some(rows) => rows,
+            None => return throw_database_closed_error(&mut cx),
+        };
         let rt = runtime(&mut cx)?;
         let count = result_arr.len(&mut cx);
-        let res = cx.null();
-        rt.block_on(async move {
+        let initial = Instant::now();
+        let result = rt.block_on(async move {
             let mut keys = Vec::<Handle<JsString>>::with_capacity(rows.column_count() as usize);
             for idx in 0..rows.column_count() {
-                let column_name = rows.column_name(idx).unwrap();
+                let column_name = rows.column_name(idx).unwrap_or_default();
                 keys.push(cx.string(column_name));
             }
             for idx in 0..count {
@@ -338,14 +1107,141 @@ impl Rows {
                         }
                     }
                     None => {
-                        break;
+                        return Ok((idx, true));
                     }
                 };
             }
-            Ok(())
-        })?;
+            Ok((count, false))
+        });
+        *this.elapsed.borrow_mut() += Instant::now() - initial;
+        let (returned, exhausted) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                slot.take();
+                this.close();
+                return Err(err);
+            }
+        };
+        *this.rows_returned.borrow_mut() += returned as u64;
+        if exhausted {
+            slot.take();
+            this.close();
+        }
         Ok(res)
     }
+
+    pub fn js_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let this: Handle<'_, JsBox<Rows>> = cx.this()?;
+        this.rows.blocking_lock().take();
+        this.close();
+        Ok(cx.undefined())
+    }
+
+    fn close(&self) {
+        if !self.done.replace(true) {
+            let elapsed = *self.elapsed.borrow();
+            self.stats.record(elapsed, *self.rows_returned.borrow());
+            if let Some(slow_query) = &self.slow_query {
+                slow_query.finish(elapsed);
+            }
+        }
+        self.iterator.replace(None);
+    }
+}
+
+/// Reads the rows of a query into Arrow record batches on demand, returning
+/// the IPC stream in chunks of one record batch each.
+pub(crate) struct ArrowBatches {
+    rows: Arc<Mutex<Option<libsql::Rows>>>,
+    /// The encoder, which is dropped once the end of the stream is returned.
+    stream: RefCell<Option<ArrowStream>>,
+    iterator: RefCell<Option<Arc<()>>>,
+    stats: Arc<ExecutionStats>,
+    elapsed: RefCell<Duration>,
+    rows_returned: RefCell<u64>,
+}
+
+impl Finalize for ArrowBatches {}
+
+impl ArrowBatches {
+    pub fn js_next(mut cx: FunctionContext) -> JsResult<JsValue> {
+        let this: Handle<'_, JsBox<ArrowBatches>> = cx.this()?;
+        let mut stream = this.stream.borrow_mut();
+        let Some(encoder) = stream.as_mut() else {
+            return Ok(cx.null().upcast());
+        };
+        let mut slot = this.rows.blocking_lock();
+        let Some(rows) = slot.as_mut() else {
+            return throw_database_closed_error(&mut cx);
+        };
+        let rt = runtime(&mut cx)?;
+        let initial = Instant::now();
+        let result = rt.block_on(read_batch(rows));
+        *this.elapsed.borrow_mut() += Instant::now() - initial;
+        let result = result.map(|(batch, exhausted)| {
+            *this.rows_returned.borrow_mut() += batch.len() as u64;
+            let chunk = encoder.write_batch(&batch).and_then(|()| {
+                if exhausted {
+                    encoder.finish()
+                } else {
+                    Ok(encoder.take_output())
+                }
+            });
+            (chunk, exhausted)
+        });
+        drop(slot);
+        drop(stream);
+        match result {
+            Ok((Ok(chunk), exhausted)) => {
+                if exhausted {
+                    this.close();
+                }
+                Ok(JsBuffer::external(&mut cx, chunk).upcast())
+            }
+            Ok((Err(err), _)) => {
+                this.close();
+                cx.throw_error(err.to_string())
+            }
+            Err(err) => {
+                this.close();
+                throw_libsql_error(&mut cx, err)
+            }
+        }
+    }
+
+    pub fn js_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+        let this: Handle<'_, JsBox<ArrowBatches>> = cx.this()?;
+        this.close();
+        Ok(cx.undefined())
+    }
+
+    fn close(&self) {
+        self.rows.blocking_lock().take();
+        self.stream.replace(None);
+        if self.iterator.replace(None).is_some() {
+            let elapsed = *self.elapsed.borrow();
+            self.stats.record(elapsed, *self.rows_returned.borrow());
+        }
+    }
+}
+
+/// Reads up to `arrow::BATCH_SIZE` rows, returning them along with whether
+/// the rows have all been read.
+async fn read_batch(
+    rows: &mut libsql::Rows,
+) -> Result<(Vec<Vec<libsql::Value>>, bool), libsql::Error> {
+    let mut batch = Vec::with_capacity(arrow::BATCH_SIZE);
+    while batch.len() < arrow::BATCH_SIZE {
+        let Some(row) = rows.next().await? else {
+            return Ok((batch, true));
+        };
+        let mut values = Vec::with_capacity(rows.column_count() as usize);
+        for idx in 0..rows.column_count() {
+            values.push(row.get_value(idx)?);
+        }
+        batch.push(values);
+    }
+    Ok((batch, false))
 }
 
 fn convert_params(
@@ -381,11 +1277,11 @@ fn convert_params_object(
     v: Handle<'_, JsObject>,
 ) -> NeonResult<libsql::params::Params> {
     let mut params = vec![];
-    let stmt = &stmt.stmt;
-    let raw_stmt = stmt.blocking_lock();
-    for idx in 0..raw_stmt.parameter_count() {
-        let name = raw_stmt.parameter_name((idx + 1) as i32).unwrap();
-        let name = name.to_string();
+    let names = match stmt.parameter_names(cx)? {
+        Some(names) => names,
+        None => return cx.throw_type_error("Object bind parameters require named parameters"),
+    };
+    for name in names {
         let v = v.get(cx, &name[1..])?;
         let v = js_value_to_value(cx, v)?;
         params.push((name, v));
@@ -393,6 +1289,94 @@ fn convert_params_object(
     Ok(libsql::params::Params::Named(params))
 }
 
+/// Converts the bind parameters of a batch into one `Params` per execution.
+///
+/// The batch is either an array with one set of bind parameters per
+/// execution, or an object that maps parameter names to equally sized
+/// columns of values (arrays or typed arrays).
+fn convert_batch(
+    cx: &mut FunctionContext,
+    stmt: &Statement,
+    v: Handle<'_, JsValue>,
+) -> NeonResult<Vec<libsql::params::Params>> {
+    if v.is_a::<JsArray, _>(cx) {
+        let v = v.downcast_or_throw::<JsArray, _>(cx)?;
+        let mut batch = Vec::with_capacity(v.len(cx) as usize);
+        for i in 0..v.len(cx) {
+            let params = v.get(cx, i)?;
+            batch.push(convert_params(cx, stmt, params)?);
+        }
+        Ok(batch)
+    } else {
+        let v = v.downcast_or_throw::<JsObject, _>(cx)?;
+        convert_batch_columnar(cx, stmt, v)
+    }
+}
+
+fn convert_batch_columnar(
+    cx: &mut FunctionContext,
+    stmt: &Statement,
+    v: Handle<'_, JsObject>,
+) -> NeonResult<Vec<libsql::params::Params>> {
+    let names = match stmt.parameter_names(cx)? {
+        Some(names) => names,
+        None => return cx.throw_type_error("Columnar bind parameters require named parameters"),
+    };
+    let mut columns = Vec::with_capacity(names.len());
+    let mut len = None;
+    for name in names {
+        let column: Handle<'_, JsObject> = v.get(cx, &name[1..])?;
+        let column_len: Handle<'_, JsNumber> = column.get(cx, "length")?;
+        let column_len = column_len.value(cx) as u32;
+        match len {
+            Some(len) if len != column_len => {
+                return cx.throw_range_error("Columnar bind parameters must have equal lengths")
+            }
+            _ => len = Some(column_len),
+        }
+        let integers = integer_column(cx, column);
+        columns.push((name, column, integers));
+    }
+    let len = len.unwrap_or(0);
+    let mut batch = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let mut params = Vec::with_capacity(columns.len());
+        for (name, column, integers) in &columns {
+            let v = match integers {
+                Some(integers) => libsql::Value::Integer(integers[i as usize]),
+                None => {
+                    let v = column.get(cx, i)?;
+                    js_value_to_value(cx, v)?
+                }
+            };
+            params.push((name.clone(), v));
+        }
+        batch.push(libsql::params::Params::Named(params));
+    }
+    Ok(batch)
+}
+
+/// Reads a column of a columnar batch that is an integer typed array, whose
+/// values are bound as integers rather than as numbers.
+fn integer_column(cx: &mut FunctionContext, column: Handle<'_, JsObject>) -> Option<Vec<i64>> {
+    typed_integers::<i8>(cx, column)
+        .or_else(|| typed_integers::<i16>(cx, column))
+        .or_else(|| typed_integers::<i32>(cx, column))
+        .or_else(|| typed_integers::<u8>(cx, column))
+        .or_else(|| typed_integers::<u16>(cx, column))
+        .or_else(|| typed_integers::<u32>(cx, column))
+        .or_else(|| typed_integers::<i64>(cx, column))
+}
+
+fn typed_integers<T>(cx: &mut FunctionContext, column: Handle<'_, JsObject>) -> Option<Vec<i64>>
+where
+    T: Binary + Copy + Into<i64>,
+    JsTypedArray<T>: Value,
+{
+    let column = column.downcast::<JsTypedArray<T>, _>(cx).ok()?;
+    Some(column.as_slice(cx).iter().map(|&v| v.into()).collect())
+}
+
 fn convert_row(
     cx: &mut FunctionContext,
     safe_ints: bool,
@@ -404,7 +1388,7 @@ fn convert_row(
         let v = row
             .get_value(idx)
             .or_else(|err| throw_libsql_error(cx, err))?;
-        let column_name = rows.column_name(idx).unwrap();
+        let column_name = rows.column_name(idx).unwrap_or_default();
         let v: Handle<'_, JsValue> = match v {
             libsql::Value::Null => cx.null().upcast(),
             libsql::Value::Integer(v) => {
@@ -451,3 +1435,159 @@ fn convert_row_raw(
     }
     Ok(())
 }
+
+fn convert_value<'a>(
+    cx: &mut impl Context<'a>,
+    safe_ints: bool,
+    v: libsql::Value,
+) -> JsResult<'a, JsValue> {
+    let v: Handle<'_, JsValue> = match v {
+        libsql::Value::Null => cx.null().upcast(),
+        libsql::Value::Integer(v) => {
+            if safe_ints {
+                neon::types::JsBigInt::from_i64(cx, v).upcast()
+            } else {
+                cx.number(v as f64).upcast()
+            }
+        }
+        libsql::Value::Real(v) => cx.number(v).upcast(),
+        libsql::Value::Text(v) => cx.string(v).upcast(),
+        libsql::Value::Blob(v) => JsBuffer::from_slice(cx, &v)?.upcast(),
+    };
+    Ok(v)
+}
+
+/// Converts rows collected off the JavaScript thread into an array of row
+/// objects, or arrays in raw mode, creating the column name keys only once.
+fn convert_rows<'a>(
+    cx: &mut impl Context<'a>,
+    raw: bool,
+    safe_ints: bool,
+    columns: &[String],
+    rows: Vec<Vec<libsql::Value>>,
+) -> JsResult<'a, JsArray> {
+    let result_arr = cx.empty_array();
+    let keys = columns
+        .iter()
+        .map(|column| cx.string(column))
+        .collect::<Vec<_>>();
+    for (i, row) in rows.into_iter().enumerate() {
+        if raw {
+            let result = cx.empty_array();
+            for (idx, v) in row.into_iter().enumerate() {
+                let v = convert_value(cx, safe_ints, v)?;
+                result.set(cx, idx as u32, v)?;
+            }
+            result_arr.set(cx, i as u32, result)?;
+        } else {
+            let result = cx.empty_object();
+            for (idx, v) in row.into_iter().enumerate() {
+                let v = convert_value(cx, safe_ints, v)?;
+                result.set(cx, keys[idx], v)?;
+            }
+            result_arr.set(cx, i as u32, result)?;
+        }
+    }
+    Ok(result_arr)
+}
+
+/// A row of the output of `EXPLAIN QUERY PLAN`.
+struct PlanNode {
+    id: i64,
+    parent: i64,
+    detail: String,
+}
+
+/// Converts the query plan nodes whose parent is `parent` into an array of
+/// nodes, each with the array of its own children. Nodes already in
+/// `visited` are skipped, so a malformed plan with a cycle can't recurse
+/// forever.
+fn convert_plan<'a>(
+    cx: &mut impl Context<'a>,
+    plan: &[PlanNode],
+    parent: i64,
+    visited: &mut HashSet<i64>,
+) -> JsResult<'a, JsArray> {
+    let nodes = cx.empty_array();
+    let mut count = 0;
+    for node in plan.iter().filter(|node| node.parent == parent) {
+        if !visited.insert(node.id) {
+            continue;
+        }
+        let obj = cx.empty_object();
+        let id = cx.number(node.id as f64);
+        obj.set(cx, "id", id)?;
+        let parent = cx.number(node.parent as f64);
+        obj.set(cx, "parent", parent)?;
+        let detail = cx.string(&node.detail);
+        obj.set(cx, "detail", detail)?;
+        let children = convert_plan(cx, plan, node.id, visited)?;
+        obj.set(cx, "children", children)?;
+        nodes.set(cx, count, obj)?;
+        count += 1;
+    }
+    Ok(nodes)
+}
+
+/// Returns a bitmap with bit `i` set if the value in row `i` is NULL.
+fn null_bitmap(values: &[libsql::Value]) -> Vec<u8> {
+    let mut bitmap = vec![0u8; (values.len() + 7) / 8];
+    for (i, v) in values.iter().enumerate() {
+        if let libsql::Value::Null = v {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bitmap
+}
+
+/// Converts the values of a result column into a single JavaScript value.
+///
+/// Numeric columns become a `Float64Array` (or `BigInt64Array` for integer
+/// columns in safe integers mode) where NULLs are stored as zero, so the null
+/// bitmap must be consulted to tell them apart. Text and blob columns become
+/// arrays of strings and buffers, and columns that mix storage classes fall
+/// back to an array of values.
+fn convert_column<'a>(
+    cx: &mut impl Context<'a>,
+    safe_ints: bool,
+    values: Vec<libsql::Value>,
+) -> JsResult<'a, JsValue> {
+    let (mut integers, mut reals, mut texts, mut blobs) = (0, 0, 0, 0);
+    for v in &values {
+        match v {
+            libsql::Value::Null => {}
+            libsql::Value::Integer(_) => integers += 1,
+            libsql::Value::Real(_) => reals += 1,
+            libsql::Value::Text(_) => texts += 1,
+            libsql::Value::Blob(_) => blobs += 1,
+        }
+    }
+    let numeric = (integers > 0 || reals > 0) && texts == 0 && blobs == 0;
+    if numeric && reals == 0 && safe_ints {
+        let column = values
+            .iter()
+            .map(|v| match v {
+                libsql::Value::Integer(v) => *v,
+                _ => 0,
+            })
+            .collect::<Vec<_>>();
+        Ok(JsBigInt64Array::from_slice(cx, &column)?.upcast())
+    } else if numeric {
+        let column = values
+            .iter()
+            .map(|v| match v {
+                libsql::Value::Integer(v) => *v as f64,
+                libsql::Value::Real(v) => *v,
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+        Ok(JsFloat64Array::from_slice(cx, &column)?.upcast())
+    } else {
+        let column = cx.empty_array();
+        for (i, v) in values.into_iter().enumerate() {
+            let v = convert_value(cx, safe_ints, v)?;
+            column.set(cx, i as u32, v)?;
+        }
+        Ok(column.upcast())
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::stmt_status::StatementHandle;
+
+/// A cached prepared statement and the SQLite statement behind it.
+pub(crate) type CachedStatement = (
+    Arc<Mutex<Option<libsql::Statement>>>,
+    Option<StatementHandle>,
+);
+
+/// A least recently used cache of idle prepared statements keyed by SQL text.
+///
+/// A cached statement is checked out by `prepare()`, which removes it from
+/// the cache so that the `Statement` object it is handed to owns it
+/// exclusively. The statement is checked back in when that object is
+/// finalized or garbage collected.
+pub(crate) struct StatementCache {
+    capacity: usize,
+    entries: HashMap<String, CachedStatement>,
+    // SQL texts ordered from least to most recently used.
+    order: VecDeque<String>,
+    hits: u64,
+    misses: u64,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        StatementCache {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.evict();
+        }
+    }
+
+    /// Checks out the cached statement for `sql`, if there is one.
+    pub fn take(&mut self, sql: &str) -> Option<CachedStatement> {
+        if self.capacity == 0 {
+            return None;
+        }
+        match self.entries.remove(sql) {
+            Some(stmt) => {
+                self.hits += 1;
+                self.order.retain(|s| s != sql);
+                Some(stmt)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Checks in an idle statement. The statement is dropped, which finalizes
+    /// it, if the cache is disabled or already holds a statement for `sql`.
+    pub fn put(
+        &mut self,
+        sql: String,
+        stmt: Arc<Mutex<Option<libsql::Statement>>>,
+        status: Option<StatementHandle>,
+    ) {
+        if self.capacity == 0 || self.entries.contains_key(&sql) {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.order.push_back(sql.clone());
+        self.entries.insert(sql, (stmt, status));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    fn evict(&mut self) {
+        if let Some(sql) = self.order.pop_front() {
+            self.entries.remove(&sql);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use neon::prelude::*;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::stmt_status::StatementStatus;
+
+/// Execution statistics of a prepared statement.
+///
+/// Every call that executes the statement counts as one execution, and its
+/// wall time covers both starting the query and reading its rows. The
+/// statistics are shared with the rows being iterated, which record their
+/// execution once they have all been read or the iterator is closed.
+///
+/// SQLite's status counters are added separately, whenever the statement is
+/// next locked, because iterated rows are read without locking it.
+#[derive(Default)]
+pub(crate) struct ExecutionStats {
+    inner: Mutex<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    executions: u64,
+    total_time: Duration,
+    min_time: Option<Duration>,
+    max_time: Duration,
+    rows_returned: u64,
+    status: StatementStatus,
+}
+
+impl ExecutionStats {
+    pub fn record(&self, duration: Duration, rows_returned: u64) {
+        let mut counters = self.inner.lock().unwrap();
+        counters.executions += 1;
+        counters.total_time += duration;
+        counters.min_time = Some(counters.min_time.map_or(duration, |min| min.min(duration)));
+        counters.max_time = counters.max_time.max(duration);
+        counters.rows_returned += rows_returned;
+    }
+
+    pub fn add_status(&self, status: StatementStatus) {
+        let mut counters = self.inner.lock().unwrap();
+        counters.status.rows_read += status.rows_read;
+        counters.status.full_scan_steps += status.full_scan_steps;
+        counters.status.sorts += status.sorts;
+        counters.status.auto_indexes += status.auto_indexes;
+        counters.status.vm_steps += status.vm_steps;
+    }
+
+    pub fn to_object<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+        let counters = self.inner.lock().unwrap();
+        let stats = cx.empty_object();
+        let executions = cx.number(counters.executions as f64);
+        stats.set(cx, "executions", executions)?;
+        let total_time = cx.number(counters.total_time.as_secs_f64());
+        stats.set(cx, "totalTime", total_time)?;
+        let min_time = cx.number(counters.min_time.unwrap_or_default().as_secs_f64());
+        stats.set(cx, "minTime", min_time)?;
+        let max_time = cx.number(counters.max_time.as_secs_f64());
+        stats.set(cx, "maxTime", max_time)?;
+        let rows_returned = cx.number(counters.rows_returned as f64);
+        stats.set(cx, "rowsReturned", rows_returned)?;
+        let rows_read = cx.number(counters.status.rows_read as f64);
+        stats.set(cx, "rowsRead", rows_read)?;
+        let full_scan_steps = cx.number(counters.status.full_scan_steps as f64);
+        stats.set(cx, "fullScanSteps", full_scan_steps)?;
+        let sorts = cx.number(counters.status.sorts as f64);
+        stats.set(cx, "sorts", sorts)?;
+        let auto_indexes = cx.number(counters.status.auto_indexes as f64);
+        stats.set(cx, "autoIndexes", auto_indexes)?;
+        let vm_steps = cx.number(counters.status.vm_steps as f64);
+        stats.set(cx, "vmSteps", vm_steps)?;
+        Ok(stats)
+    }
+}
@@ -0,0 +1,218 @@
+use libsql::ffi;
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr::{self, NonNull};
+use std::sync::Once;
+
+/// Name of the SQL function that returns the handle of the SQLite connection
+/// it runs on. libSQL doesn't expose the SQLite connection and statements
+/// behind its own, so the function is how their status counters are found.
+const HANDLE_FUNCTION: &str = "libsql_js_connection_handle\0";
+
+/// Registers the handle function on every SQLite connection opened from now on.
+/// This initializes SQLite, so it must be called after libSQL has configured
+/// it, which libSQL does when the first local database is built.
+pub(crate) fn register() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        ffi::sqlite3_auto_extension(Some(register_handle_function));
+    });
+}
+
+unsafe extern "C" fn register_handle_function(
+    db: *mut ffi::sqlite3,
+    _err: *mut *const c_char,
+    _api: *const ffi::sqlite3_api_routines,
+) -> c_int {
+    ffi::sqlite3_create_function_v2(
+        db,
+        HANDLE_FUNCTION.as_ptr() as *const c_char,
+        0,
+        ffi::SQLITE_UTF8 | ffi::SQLITE_DIRECTONLY,
+        ptr::null_mut(),
+        Some(handle_function),
+        None,
+        None,
+        None,
+    )
+}
+
+unsafe extern "C" fn handle_function(
+    ctx: *mut ffi::sqlite3_context,
+    _argc: c_int,
+    _argv: *mut *mut ffi::sqlite3_value,
+) {
+    ffi::sqlite3_result_int64(ctx, ffi::sqlite3_context_db_handle(ctx) as i64);
+}
+
+/// The SQLite connection behind a local libSQL connection, which is valid as
+/// long as the libSQL connection is open.
+#[derive(Clone, Copy)]
+pub(crate) struct ConnectionHandle(NonNull<ffi::sqlite3>);
+
+// SAFETY: SQLite connections are opened in serialized mode, and the handle is
+// only used while holding the connection's mutex.
+unsafe impl Send for ConnectionHandle {}
+unsafe impl Sync for ConnectionHandle {}
+
+impl ConnectionHandle {
+    /// Returns the SQLite connection behind `conn`, or `None` if it is a
+    /// remote connection or was opened before `register()` was called.
+    pub async fn of(conn: &libsql::Connection) -> Option<Self> {
+        let sql = format!("SELECT {}()", HANDLE_FUNCTION.trim_end_matches('\0'));
+        let mut rows = conn.query(&sql, ()).await.ok()?;
+        let handle = rows.next().await.ok()??.get::<i64>(0).ok()?;
+        NonNull::new(handle as *mut ffi::sqlite3).map(ConnectionHandle)
+    }
+
+    /// Runs `f` with the most recently prepared statement of the connection,
+    /// or null, while holding the connection's mutex so that the statement
+    /// cannot be finalized in the meantime.
+    fn with_newest<T>(&self, f: impl FnOnce(*mut ffi::sqlite3_stmt) -> T) -> Option<T> {
+        unsafe {
+            let mutex = ffi::sqlite3_db_mutex(self.0.as_ptr());
+            if mutex.is_null() {
+                return None;
+            }
+            ffi::sqlite3_mutex_enter(mutex);
+            let result = f(ffi::sqlite3_next_stmt(self.0.as_ptr(), ptr::null_mut()));
+            ffi::sqlite3_mutex_leave(mutex);
+            Some(result)
+        }
+    }
+}
+
+/// Prepares a statement and finds the SQLite statement behind it. The libSQL
+/// connection must be locked, so that no other statement is prepared on it
+/// in the meantime.
+pub(crate) async fn prepare(
+    conn: &libsql::Connection,
+    handle: Option<ConnectionHandle>,
+    sql: &str,
+) -> libsql::Result<(libsql::Statement, Option<StatementHandle>)> {
+    let newest = handle.and_then(|handle| handle.with_newest(|stmt| stmt as usize));
+    let stmt = conn.prepare(sql).await?;
+    let status = match (handle, newest) {
+        (Some(handle), Some(newest)) => handle
+            .with_newest(|stmt| unsafe { prepared_statement(handle, stmt, newest, sql) })
+            .flatten(),
+        _ => None,
+    };
+    Ok((stmt, status))
+}
+
+/// Returns `stmt` if it is the statement that was just prepared for `sql`.
+/// SQLite adds statements to the front of the connection's list, so it must
+/// be followed by the statement that was the newest before preparing. This
+/// rules out statements that other threads prepared and finalized while
+/// executing a query. Must be called with the connection's mutex held.
+unsafe fn prepared_statement(
+    handle: ConnectionHandle,
+    stmt: *mut ffi::sqlite3_stmt,
+    previous: usize,
+    sql: &str,
+) -> Option<StatementHandle> {
+    if stmt.is_null() || stmt as usize == previous {
+        return None;
+    }
+    if ffi::sqlite3_next_stmt(handle.0.as_ptr(), stmt) as usize != previous {
+        return None;
+    }
+    let stmt_sql = ffi::sqlite3_sql(stmt);
+    if stmt_sql.is_null() {
+        return None;
+    }
+    let stmt_sql = CStr::from_ptr(stmt_sql).to_bytes();
+    if stmt_sql.is_empty() || !sql.as_bytes().starts_with(stmt_sql) {
+        return None;
+    }
+    NonNull::new(stmt).map(StatementHandle)
+}
+
+/// The SQLite statement behind a prepared libSQL statement, which is valid as
+/// long as the libSQL statement is.
+#[derive(Clone, Copy)]
+pub(crate) struct StatementHandle(NonNull<ffi::sqlite3_stmt>);
+
+// SAFETY: the status counters are only read while holding the lock of the
+// libSQL statement, which keeps the SQLite statement alive.
+unsafe impl Send for StatementHandle {}
+unsafe impl Sync for StatementHandle {}
+
+/// Status counters of a SQLite statement.
+#[derive(Default)]
+pub(crate) struct StatementStatus {
+    pub rows_read: u64,
+    pub full_scan_steps: u64,
+    pub sorts: u64,
+    pub auto_indexes: u64,
+    pub vm_steps: u64,
+}
+
+impl StatementHandle {
+    /// Reads the status counters accumulated since they were last taken and
+    /// resets them. The libSQL statement must be locked and not finalized.
+    pub fn take(&self) -> StatementStatus {
+        // The counters are unsigned 32-bit integers returned as `int`.
+        let counter =
+            |op| unsafe { ffi::sqlite3_stmt_status(self.0.as_ptr(), op, 1) as u32 as u64 };
+        StatementStatus {
+            rows_read: counter(ffi::LIBSQL_STMTSTATUS_ROWS_READ),
+            full_scan_steps: counter(ffi::SQLITE_STMTSTATUS_FULLSCAN_STEP),
+            sorts: counter(ffi::SQLITE_STMTSTATUS_SORT),
+            auto_indexes: counter(ffi::SQLITE_STMTSTATUS_AUTOINDEX),
+            vm_steps: counter(ffi::SQLITE_STMTSTATUS_VM_STEP),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn status_counters() {
+        block_on(async {
+            let db = libsql::Builder::new_local(":memory:")
+                .build()
+                .await
+                .unwrap();
+            register();
+            let conn = db.connect().unwrap();
+            let handle = ConnectionHandle::of(&conn).await;
+            assert!(handle.is_some());
+            conn.execute_batch(
+                "CREATE TABLE t (a, b); INSERT INTO t VALUES (1, 2), (3, 4), (5, 6)",
+            )
+            .await
+            .unwrap();
+
+            // Another statement is prepared and still alive.
+            let _other = conn.prepare("SELECT 1").await.unwrap();
+            let sql = "SELECT * FROM t WHERE b > 1 ORDER BY a DESC";
+            let (stmt, status) = prepare(&conn, handle, sql).await.unwrap();
+            let status = status.unwrap();
+            let mut rows = stmt.query(()).await.unwrap();
+            while rows.next().await.unwrap().is_some() {}
+            drop(rows);
+            let counters = status.take();
+            assert_eq!(counters.rows_read, 6);
+            assert_eq!(counters.sorts, 1);
+            assert_eq!(counters.auto_indexes, 0);
+            assert!(counters.full_scan_steps > 0);
+            assert!(counters.vm_steps > 0);
+            assert_eq!(status.take().vm_steps, 0);
+
+            let (_stmt, status) = prepare(&conn, None, "SELECT a FROM t").await.unwrap();
+            assert!(status.is_none());
+            assert!(prepare(&conn, handle, "SELEC").await.is_err());
+        });
+    }
+}
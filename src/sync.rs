@@ -0,0 +1,827 @@
+use std::future::Future;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libsql::replication::Replicated;
+use neon::prelude::*;
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+use tracing::trace;
+
+use crate::database::convert_replicated_to_object;
+use crate::errors::{convert_libsql_error, throw_libsql_error};
+use crate::offline::{convert_conflict, Conflict};
+
+/// How often the progress of a running sync is reported.
+pub(crate) const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+const WAL_HEADER_SIZE: u64 = 32;
+const WAL_FRAME_HEADER_SIZE: u64 = 24;
+
+/// The state of the synchronization of an embedded replica, which is updated
+/// by every sync, whether it is started by `sync()`, `syncUntil()` or the
+/// periodic sync.
+pub(crate) struct SyncStatus {
+    /// Path of the local database file.
+    path: PathBuf,
+    inner: Mutex<SyncState>,
+}
+
+#[derive(Default)]
+struct SyncState {
+    running: usize,
+    last_sync_time: Option<SystemTime>,
+    last_error: Option<String>,
+    frame_no: Option<u64>,
+    frames_synced: u64,
+}
+
+impl SyncStatus {
+    pub fn new(path: &str) -> Self {
+        SyncStatus {
+            path: PathBuf::from(path),
+            inner: Mutex::new(SyncState::default()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs a sync, recording its outcome.
+    pub async fn track(
+        &self,
+        sync: impl Future<Output = libsql::Result<Replicated>>,
+    ) -> libsql::Result<Replicated> {
+        self.inner.lock().unwrap().running += 1;
+        let running = Running(&self.inner);
+        let result = sync.await;
+        drop(running);
+        let mut state = self.inner.lock().unwrap();
+        match &result {
+            Ok(rep) => {
+                state.last_sync_time = Some(SystemTime::now());
+                state.last_error = None;
+                state.frame_no = rep.frame_no().or(state.frame_no);
+                state.frames_synced += rep.frames_synced() as u64;
+            }
+            Err(err) => state.last_error = Some(err.to_string()),
+        }
+        result
+    }
+
+    /// Returns the highest replication index reached by a sync.
+    pub fn frame_no(&self) -> Option<u64> {
+        self.inner.lock().unwrap().frame_no
+    }
+
+    pub fn record_error(&self, err: &str) {
+        self.inner.lock().unwrap().last_error = Some(err.to_string());
+    }
+
+    fn wal_path(&self) -> PathBuf {
+        let mut wal = self.path.clone().into_os_string();
+        wal.push("-wal");
+        PathBuf::from(wal)
+    }
+
+    /// Returns the number of bytes in the local database and its WAL, which
+    /// grow as the frames pulled by a sync are applied.
+    fn local_size(&self) -> u64 {
+        [self.path.clone(), self.wal_path()]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Returns the number of frames in the local WAL, computed from its size
+    /// and the page size recorded in its header.
+    fn wal_frames(&self) -> u64 {
+        let Ok(mut file) = std::fs::File::open(self.wal_path()) else {
+            return 0;
+        };
+        let mut header = [0u8; WAL_HEADER_SIZE as usize];
+        if file.read_exact(&mut header).is_err() {
+            return 0;
+        }
+        let page_size = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as u64;
+        let len = file.metadata().map_or(0, |metadata| metadata.len());
+        if page_size == 0 {
+            return 0;
+        }
+        // The WAL may have been truncated or reset since its header was read.
+        len.saturating_sub(WAL_HEADER_SIZE) / (WAL_FRAME_HEADER_SIZE + page_size)
+    }
+
+    pub fn to_object<'a, C: Context<'a>>(
+        &self,
+        cx: &mut C,
+        periodic_sync: bool,
+    ) -> JsResult<'a, JsObject> {
+        let state = self.inner.lock().unwrap();
+        let obj = cx.empty_object();
+        let syncing = cx.boolean(state.running > 0);
+        obj.set(cx, "syncing", syncing)?;
+        let last_sync_time: Handle<'_, JsValue> = match state.last_sync_time {
+            Some(time) => {
+                let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                cx.number(time.as_secs_f64() * 1000.0).upcast()
+            }
+            None => cx.null().upcast(),
+        };
+        obj.set(cx, "lastSyncTime", last_sync_time)?;
+        let last_error: Handle<'_, JsValue> = match &state.last_error {
+            Some(err) => cx.string(err).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(cx, "lastError", last_error)?;
+        let replication_index: Handle<'_, JsValue> = match state.frame_no {
+            Some(frame_no) => cx.number(frame_no as f64).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(cx, "replicationIndex", replication_index)?;
+        let frames_synced = cx.number(state.frames_synced as f64);
+        obj.set(cx, "framesSynced", frames_synced)?;
+        let periodic_sync = cx.boolean(periodic_sync);
+        obj.set(cx, "periodicSync", periodic_sync)?;
+        Ok(obj)
+    }
+}
+
+/// Marks a sync as finished when dropped, even if the sync panicked.
+struct Running<'a>(&'a Mutex<SyncState>);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.0.lock() {
+            state.running -= 1;
+        }
+    }
+}
+
+/// A database shared by a connection and its syncs.
+///
+/// Syncs take turns through a lock of their own, so that reading the state of
+/// the database, such as the replication index of delegated writes, doesn't
+/// wait for a sync that can take minutes.
+pub(crate) struct SharedDatabase {
+    db: libsql::Database,
+    syncing: tokio::sync::Mutex<()>,
+}
+
+impl SharedDatabase {
+    pub fn new(db: libsql::Database) -> Self {
+        SharedDatabase {
+            db,
+            syncing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Waits for the running sync to finish and returns the database for the
+    /// next one.
+    pub async fn lock(&self) -> SyncGuard<'_> {
+        let syncing = self.syncing.lock().await;
+        SyncGuard {
+            db: &self.db,
+            _syncing: syncing,
+        }
+    }
+
+    /// Returns the highest replication index of the writes that the database
+    /// delegated to the primary.
+    pub fn max_write_replication_index(&self) -> Option<u64> {
+        self.db.max_write_replication_index()
+    }
+}
+
+/// Access to a database for a sync, which holds off other syncs.
+pub(crate) struct SyncGuard<'a> {
+    db: &'a libsql::Database,
+    _syncing: tokio::sync::MutexGuard<'a, ()>,
+}
+
+impl Deref for SyncGuard<'_> {
+    type Target = libsql::Database;
+
+    fn deref(&self) -> &libsql::Database {
+        self.db
+    }
+}
+
+/// Finds the replication index of a write that an embedded replica delegates
+/// to the primary.
+///
+/// libSQL only keeps the highest index of the delegated writes, which a write
+/// raises to its own unless it didn't change the primary. The caller holds the
+/// connection from `start()` to `finish()`, so that the index can't come from
+/// another write.
+pub(crate) struct DelegatedWrite {
+    db: Arc<SharedDatabase>,
+    before: Option<u64>,
+}
+
+impl DelegatedWrite {
+    pub fn start(db: Arc<SharedDatabase>) -> Self {
+        let before = db.max_write_replication_index();
+        DelegatedWrite { db, before }
+    }
+
+    /// Returns the replication index of the write, or `None` if it didn't
+    /// change the primary.
+    pub fn finish(self) -> Option<u64> {
+        write_replication_index(self.before, self.db.max_write_replication_index())
+    }
+}
+
+fn write_replication_index(before: Option<u64>, after: Option<u64>) -> Option<u64> {
+    if after == before {
+        None
+    } else {
+        after
+    }
+}
+
+/// Runs a sync once the running sync, if any, has finished.
+pub(crate) async fn run_sync(
+    db: Arc<SharedDatabase>,
+    status: Arc<SyncStatus>,
+) -> Result<Replicated, SyncError> {
+    let db = db.lock().await;
+    Ok(status.track(db.sync()).await?)
+}
+
+/// Syncs until the replica reaches `index`, giving up after `timeout`.
+pub(crate) async fn sync_until(
+    db: Arc<SharedDatabase>,
+    status: Arc<SyncStatus>,
+    index: u64,
+    timeout: Option<Duration>,
+) -> Result<Replicated, SyncError> {
+    let sync = tokio::spawn({
+        let status = status.clone();
+        async move {
+            let db = db.lock().await;
+            status.track(db.sync_until(index)).await
+        }
+    });
+    with_timeout(&status, index, timeout, sync).await
+}
+
+/// Waits for a sync that waits for the replica to reach `index`, giving up
+/// after `timeout`.
+///
+/// Giving up only stops the wait. The sync keeps running in its own task
+/// until it finishes, because libSQL's sync isn't known to be safe to cancel
+/// while it applies frames, and later syncs wait for it to release the
+/// database.
+async fn with_timeout<T>(
+    status: &SyncStatus,
+    index: u64,
+    timeout: Option<Duration>,
+    sync: JoinHandle<libsql::Result<T>>,
+) -> Result<T, SyncError> {
+    let result = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, sync).await {
+            Ok(result) => result,
+            Err(_) => {
+                let msg = format!(
+                    "Timed out after {} ms waiting for replication index {}",
+                    timeout.as_millis(),
+                    index
+                );
+                status.record_error(&msg);
+                return Err(SyncError::Timeout(msg));
+            }
+        },
+        None => sync.await,
+    };
+    // The task only fails if the runtime shuts down while it runs.
+    let result = result.map_err(|err| libsql::Error::Sync(err.into()))?;
+    Ok(result?)
+}
+
+/// Waits for the replica to reach `index`, without syncing if a previous
+/// sync already got there.
+pub(crate) async fn wait_for_replication_index(
+    db: Arc<SharedDatabase>,
+    status: Arc<SyncStatus>,
+    index: u64,
+    timeout: Option<Duration>,
+) -> Result<(), SyncError> {
+    if status.frame_no().is_some_and(|frame_no| frame_no >= index) {
+        return Ok(());
+    }
+    sync_until(db, status, index, timeout).await.map(drop)
+}
+
+/// Parses the optional timeout in milliseconds passed as the argument at
+/// `index`.
+pub(crate) fn timeout_argument(
+    cx: &mut FunctionContext,
+    index: usize,
+) -> NeonResult<Option<Duration>> {
+    let timeout = match cx.argument_opt(index) {
+        Some(timeout) if timeout.is_a::<JsNumber, _>(cx) => {
+            timeout.downcast_or_throw::<JsNumber, _>(cx)?.value(cx)
+        }
+        _ => return Ok(None),
+    };
+    if !timeout.is_finite() || timeout < 0.0 {
+        return cx.throw_range_error("timeoutMs must be a non-negative number");
+    }
+    Ok(Some(Duration::from_millis(timeout as u64)))
+}
+
+pub(crate) enum SyncError {
+    Libsql(libsql::Error),
+    /// The primary rejected the local frames of an offline database.
+    Conflict(Conflict),
+    /// The replica did not reach a replication index in time.
+    Timeout(String),
+}
+
+impl From<libsql::Error> for SyncError {
+    fn from(err: libsql::Error) -> Self {
+        SyncError::Libsql(err)
+    }
+}
+
+pub(crate) fn throw_sync_error<'a, C: Context<'a>, T>(cx: &mut C, err: SyncError) -> NeonResult<T> {
+    match err {
+        SyncError::Libsql(err) => throw_libsql_error(cx, err),
+        SyncError::Conflict(conflict) => {
+            let err = JsError::error(cx, &conflict.message)?;
+            let code = cx.string("SYNC_CONFLICT");
+            err.set(cx, "code", code)?;
+            let conflict = convert_conflict(cx, &conflict)?;
+            err.set(cx, "conflict", conflict)?;
+            cx.throw(err)
+        }
+        SyncError::Timeout(msg) => {
+            let err = JsError::error(cx, msg)?;
+            let code = cx.string("SYNC_TIMEOUT");
+            err.set(cx, "code", code)?;
+            cx.throw(err)
+        }
+    }
+}
+
+/// Measures the progress of a running sync from the growth of the local
+/// database files.
+///
+/// libSQL applies the pulled frames without reporting them, but it appends
+/// every frame to the local WAL, so the frames applied so far are counted
+/// from its size.
+pub(crate) struct ProgressMeter {
+    status: Arc<SyncStatus>,
+    initial_size: u64,
+    /// Replication index reached before the sync started, if known.
+    initial_frame_no: Option<u64>,
+    wal_frames: u64,
+    frames_applied: u64,
+}
+
+pub(crate) struct Progress {
+    pub bytes_written: u64,
+    pub frames_applied: u64,
+    pub replication_index: Option<u64>,
+}
+
+impl ProgressMeter {
+    pub fn new(status: Arc<SyncStatus>) -> Self {
+        ProgressMeter {
+            initial_size: status.local_size(),
+            initial_frame_no: status.frame_no(),
+            wal_frames: status.wal_frames(),
+            frames_applied: 0,
+            status,
+        }
+    }
+
+    pub fn sample(&mut self) -> Progress {
+        let wal_frames = self.status.wal_frames();
+        // A WAL that shrank was restarted by a checkpoint, so all of its
+        // frames are new.
+        self.frames_applied += if wal_frames < self.wal_frames {
+            wal_frames
+        } else {
+            wal_frames - self.wal_frames
+        };
+        self.wal_frames = wal_frames;
+        Progress {
+            bytes_written: self.status.local_size().saturating_sub(self.initial_size),
+            frames_applied: self.frames_applied,
+            replication_index: self
+                .initial_frame_no
+                .map(|frame_no| frame_no + self.frames_applied),
+        }
+    }
+}
+
+impl Progress {
+    pub fn to_object<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+        let obj = cx.empty_object();
+        let bytes_written = cx.number(self.bytes_written as f64);
+        obj.set(cx, "bytesWritten", bytes_written)?;
+        let frames_applied = cx.number(self.frames_applied as f64);
+        obj.set(cx, "framesApplied", frames_applied)?;
+        let replication_index: Handle<'_, JsValue> = match self.replication_index {
+            Some(index) => cx.number(index as f64).upcast(),
+            None => cx.null().upcast(),
+        };
+        obj.set(cx, "replicationIndex", replication_index)?;
+        Ok(obj)
+    }
+}
+
+/// Reports the progress of a sync to a JavaScript callback.
+pub(crate) struct SyncProgress {
+    callback: Arc<Root<JsFunction>>,
+    channel: Channel,
+}
+
+impl SyncProgress {
+    pub fn new<'a, C: Context<'a>>(cx: &mut C, callback: Handle<'a, JsFunction>) -> Self {
+        SyncProgress {
+            callback: Arc::new(callback.root(cx)),
+            channel: cx.channel(),
+        }
+    }
+
+    /// Runs a sync, reporting its progress every `PROGRESS_INTERVAL` while it
+    /// runs.
+    pub async fn track<T>(self, status: Arc<SyncStatus>, sync: impl Future<Output = T>) -> T {
+        let mut meter = ProgressMeter::new(status);
+        let reporter = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let progress = meter.sample();
+                let callback = self.callback.clone();
+                self.channel.send(move |mut cx| {
+                    let callback = callback.to_inner(&mut cx);
+                    let progress = progress.to_object(&mut cx)?;
+                    callback.call_with(&cx).arg(progress).exec(&mut cx)
+                });
+            }
+        });
+        let result = sync.await;
+        reporter.abort();
+        result
+    }
+}
+
+/// Runs a sync on the JavaScript thread, calling `callback` with its progress
+/// every `PROGRESS_INTERVAL` while it runs.
+///
+/// If the callback throws, the sync still runs to completion, because it
+/// can't be cancelled safely, and the exception is rethrown afterwards.
+pub(crate) fn block_on_with_progress<'a, T>(
+    cx: &mut FunctionContext<'a>,
+    rt: &Runtime,
+    status: Arc<SyncStatus>,
+    callback: Handle<'a, JsFunction>,
+    sync: impl Future<Output = T>,
+) -> NeonResult<T> {
+    let mut meter = ProgressMeter::new(status);
+    rt.block_on(async {
+        let mut sync = std::pin::pin!(sync);
+        let mut thrown = None;
+        loop {
+            match tokio::time::timeout(PROGRESS_INTERVAL, &mut sync).await {
+                Ok(result) => break thrown.map_or(Ok(result), Err),
+                Err(_) if thrown.is_none() => {
+                    let progress = meter.sample();
+                    let reported = progress
+                        .to_object(cx)
+                        .and_then(|progress| callback.call_with(cx).arg(progress).exec(cx));
+                    thrown = reported.err();
+                }
+                Err(_) => {}
+            }
+        }
+    })
+}
+
+/// Delivers the outcome of every periodic sync to a JavaScript listener,
+/// which is called with the event name, `"sync"` or `"syncError"`, and the
+/// sync stats or the error.
+#[derive(Default)]
+pub(crate) struct SyncEvents {
+    listener: Mutex<Option<(Arc<Root<JsFunction>>, Channel)>>,
+}
+
+impl SyncEvents {
+    pub fn set_listener<'a, C: Context<'a>>(
+        &self,
+        cx: &mut C,
+        listener: Option<Handle<'a, JsFunction>>,
+    ) {
+        let listener = listener.map(|listener| {
+            let mut channel = cx.channel();
+            // Delivering sync events must not keep the process alive.
+            channel.unref(cx);
+            (Arc::new(listener.root(cx)), channel)
+        });
+        *self.listener.lock().unwrap() = listener;
+    }
+
+    fn emit(&self, result: libsql::Result<Replicated>) {
+        let Some((listener, channel)) = self.listener.lock().unwrap().clone() else {
+            return;
+        };
+        channel.send(move |mut cx| {
+            let listener = listener.to_inner(&mut cx);
+            let (event, payload): (_, Handle<'_, JsValue>) = match result {
+                Ok(rep) => {
+                    let stats = convert_replicated_to_object(&mut cx, &rep)?;
+                    ("sync", stats.upcast())
+                }
+                Err(err) => {
+                    let err = convert_libsql_error(&mut cx, err)?;
+                    ("syncError", err.upcast())
+                }
+            };
+            let event = cx.string(event);
+            listener
+                .call_with(&cx)
+                .arg(event)
+                .arg(payload)
+                .exec(&mut cx)
+        });
+    }
+}
+
+/// A background task that synchronizes an embedded replica periodically.
+pub(crate) struct PeriodicSync {
+    task: JoinHandle<()>,
+    stopped: Arc<AtomicBool>,
+    /// Whether the task is running a sync.
+    syncing: Arc<AtomicBool>,
+}
+
+impl PeriodicSync {
+    /// Synchronizes the database every `period` until the task is stopped or
+    /// the database is closed.
+    ///
+    /// This replaces the background sync of `libsql::Builder::sync_interval()`
+    /// so that it can be paused and its period changed.
+    pub fn start(
+        rt: &Runtime,
+        db: Weak<SharedDatabase>,
+        period: Duration,
+        status: Arc<SyncStatus>,
+        events: Arc<SyncEvents>,
+    ) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let syncing = Arc::new(AtomicBool::new(false));
+        let task = rt.spawn({
+            let stopped = stopped.clone();
+            let syncing = syncing.clone();
+            async move {
+                let mut interval = tokio::time::interval(period);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    let Some(db) = db.upgrade() else {
+                        break;
+                    };
+                    let db = db.lock().await;
+                    // Checked after announcing the sync, so that `stop()`
+                    // either sees it running or the task sees the stop.
+                    syncing.store(true, Ordering::SeqCst);
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let result = status.track(db.sync()).await;
+                    syncing.store(false, Ordering::SeqCst);
+                    if let Err(err) = &result {
+                        trace!("Periodic sync failed: {}", err);
+                    }
+                    events.emit(result);
+                }
+            }
+        });
+        PeriodicSync {
+            task,
+            stopped,
+            syncing,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.stopped.load(Ordering::SeqCst) && !self.task.is_finished()
+    }
+
+    /// Stops the task. A sync that is in progress runs to completion, so that
+    /// its outcome is still recorded, but no further syncs are started. An
+    /// idle task is aborted right away, so that it doesn't hold on to the
+    /// runtime until its next tick.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if !self.syncing.load(Ordering::SeqCst) {
+            self.task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("libsql-js-{}-{}.db", name, std::process::id()))
+    }
+
+    fn write_wal(status: &SyncStatus, frames: usize) {
+        let page_size = 4096u32;
+        let mut wal = vec![0u8; WAL_HEADER_SIZE as usize];
+        wal[8..12].copy_from_slice(&page_size.to_be_bytes());
+        wal.resize(
+            wal.len() + frames * (WAL_FRAME_HEADER_SIZE as usize + 4096),
+            0,
+        );
+        std::fs::write(status.wal_path(), wal).unwrap();
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    async fn failing_sync() -> libsql::Result<Replicated> {
+        Err(libsql::Error::ConnectionFailed(
+            "primary unreachable".to_string(),
+        ))
+    }
+
+    async fn panicking_sync() -> libsql::Result<Replicated> {
+        panic!("sync panicked")
+    }
+
+    #[test]
+    fn track_records_errors() {
+        let status = SyncStatus::new("track-errors.db");
+        let result = runtime().block_on(status.track(failing_sync()));
+        assert!(result.is_err());
+        let state = status.inner.lock().unwrap();
+        assert_eq!(state.running, 0);
+        assert!(state
+            .last_error
+            .as_ref()
+            .unwrap()
+            .contains("primary unreachable"));
+        assert!(state.last_sync_time.is_none());
+        assert!(state.frame_no.is_none());
+    }
+
+    #[test]
+    fn track_resets_running_after_panic() {
+        let status = SyncStatus::new("track-panic.db");
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            runtime().block_on(status.track(panicking_sync()))
+        }));
+        assert!(result.is_err());
+        assert_eq!(status.inner.lock().unwrap().running, 0);
+    }
+
+    /// Spawns a sync that takes `duration` once the previous syncs have
+    /// finished, and returns the number of syncs that finished.
+    fn spawn_sync(
+        syncs: Arc<tokio::sync::Mutex<u32>>,
+        duration: Duration,
+    ) -> JoinHandle<libsql::Result<u32>> {
+        tokio::spawn(async move {
+            let mut syncs = syncs.lock().await;
+            tokio::time::sleep(duration).await;
+            *syncs += 1;
+            Ok(*syncs)
+        })
+    }
+
+    #[test]
+    fn timeout_leaves_the_sync_running() {
+        let status = SyncStatus::new("sync-timeout.db");
+        let syncs = Arc::new(tokio::sync::Mutex::new(0));
+        runtime().block_on(async {
+            let timeout = Some(Duration::from_millis(10));
+            let sync = spawn_sync(syncs.clone(), Duration::from_millis(100));
+            let result = with_timeout(&status, 7, timeout, sync).await;
+            let Err(SyncError::Timeout(msg)) = result else {
+                panic!("expected a timeout");
+            };
+            assert_eq!(msg, "Timed out after 10 ms waiting for replication index 7");
+            assert_eq!(
+                status.inner.lock().unwrap().last_error.as_deref(),
+                Some(msg.as_str())
+            );
+
+            // A later sync waits for the one that timed out to finish.
+            let timeout = Some(Duration::from_secs(10));
+            let sync = spawn_sync(syncs.clone(), Duration::ZERO);
+            let result = with_timeout(&status, 7, timeout, sync).await;
+            assert!(matches!(result, Ok(2)));
+        });
+    }
+
+    #[test]
+    fn sync_without_timeout() {
+        let status = SyncStatus::new("sync-no-timeout.db");
+        let syncs = Arc::new(tokio::sync::Mutex::new(0));
+        let result = runtime().block_on(async {
+            let sync = spawn_sync(syncs, Duration::from_millis(10));
+            with_timeout(&status, 7, None, sync).await
+        });
+        assert!(matches!(result, Ok(1)));
+        assert!(status.inner.lock().unwrap().last_error.is_none());
+    }
+
+    #[test]
+    fn write_replication_index_of_the_write() {
+        assert_eq!(write_replication_index(None, Some(5)), Some(5));
+        assert_eq!(write_replication_index(Some(5), Some(9)), Some(9));
+        // A write that didn't change the primary has no index of its own.
+        assert_eq!(write_replication_index(Some(9), Some(9)), None);
+        assert_eq!(write_replication_index(None, None), None);
+    }
+
+    #[test]
+    fn replication_index_is_read_during_a_sync() {
+        let rt = runtime();
+        let db = rt.block_on(async {
+            let db = libsql::Builder::new_local(":memory:")
+                .build()
+                .await
+                .unwrap();
+            SharedDatabase::new(db)
+        });
+        rt.block_on(async {
+            let _syncing = db.lock().await;
+            assert_eq!(db.max_write_replication_index(), None);
+        });
+    }
+
+    #[test]
+    fn timeout_cancels_waiting_for_another_sync() {
+        let status = SyncStatus::new("sync-timeout-lock.db");
+        let db = tokio::sync::Mutex::new(());
+        let result = runtime().block_on(async {
+            let _syncing = db.lock().await;
+            let sync = async {
+                let _db = db.lock().await;
+                status.track(failing_sync()).await
+            };
+            with_timeout(&status, 7, Some(Duration::from_millis(10)), sync).await
+        });
+        assert!(matches!(result, Err(SyncError::Timeout(_))));
+        assert_eq!(status.inner.lock().unwrap().running, 0);
+    }
+
+    #[test]
+    fn progress_counts_wal_frames() {
+        let path = temp_path("progress");
+        let status = Arc::new(SyncStatus::new(path.to_str().unwrap()));
+        write_wal(&status, 2);
+        status.inner.lock().unwrap().frame_no = Some(10);
+
+        let mut meter = ProgressMeter::new(status.clone());
+        let progress = meter.sample();
+        assert_eq!(progress.frames_applied, 0);
+        assert_eq!(progress.replication_index, Some(10));
+
+        write_wal(&status, 5);
+        let progress = meter.sample();
+        assert_eq!(progress.frames_applied, 3);
+        assert_eq!(progress.replication_index, Some(13));
+        assert_eq!(progress.bytes_written, 3 * (WAL_FRAME_HEADER_SIZE + 4096));
+
+        // A checkpoint restarted the WAL, which then got one more frame.
+        write_wal(&status, 1);
+        let progress = meter.sample();
+        assert_eq!(progress.frames_applied, 4);
+        assert_eq!(progress.replication_index, Some(14));
+
+        std::fs::remove_file(status.wal_path()).unwrap();
+    }
+
+    #[test]
+    fn progress_without_known_index() {
+        let path = temp_path("progress-unknown");
+        let status = Arc::new(SyncStatus::new(path.to_str().unwrap()));
+        let mut meter = ProgressMeter::new(status.clone());
+        write_wal(&status, 2);
+        let progress = meter.sample();
+        assert_eq!(progress.frames_applied, 2);
+        assert_eq!(progress.replication_index, None);
+
+        std::fs::remove_file(status.wal_path()).unwrap();
+    }
+}
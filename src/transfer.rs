@@ -0,0 +1,762 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Bytes, Lines, Read, Write};
+use std::iter::Peekable;
+
+use neon::prelude::*;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::trace;
+
+use crate::errors::throw_libsql_error;
+
+/// File formats supported by query export and file import.
+#[derive(Clone, Copy)]
+pub(crate) enum Format {
+    Csv,
+    Ndjson,
+}
+
+pub(crate) fn parse_format<'a, C: Context<'a>>(cx: &mut C, format: &str) -> NeonResult<Format> {
+    match format {
+        "csv" => Ok(Format::Csv),
+        "ndjson" => Ok(Format::Ndjson),
+        _ => cx.throw_type_error(format!("Unsupported file format: {}", format)),
+    }
+}
+
+pub(crate) enum TransferError {
+    Libsql(libsql::Error),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl From<libsql::Error> for TransferError {
+    fn from(err: libsql::Error) -> Self {
+        TransferError::Libsql(err)
+    }
+}
+
+impl From<std::io::Error> for TransferError {
+    fn from(err: std::io::Error) -> Self {
+        TransferError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for TransferError {
+    fn from(err: serde_json::Error) -> Self {
+        TransferError::Json(err)
+    }
+}
+
+pub(crate) fn throw_transfer_error<'a, C: Context<'a>, T>(
+    cx: &mut C,
+    err: TransferError,
+) -> NeonResult<T> {
+    match err {
+        TransferError::Libsql(err) => throw_libsql_error(cx, err),
+        TransferError::Io(err) => cx.throw_error(err.to_string()),
+        TransferError::Json(err) => cx.throw_error(err.to_string()),
+        TransferError::Invalid(msg) => cx.throw_error(msg),
+    }
+}
+
+/// Writes all rows to the file at `path`, returning the number of rows written.
+///
+/// CSV output starts with a header row. NULLs are written as empty fields in
+/// CSV, and empty strings as `""` so that they can be told apart, and as
+/// `null` in NDJSON. Reals are written with a decimal point or an exponent, so
+/// that they are imported as reals. Blobs are written as hex strings.
+pub(crate) async fn export_rows(
+    rows: &mut libsql::Rows,
+    format: Format,
+    path: &str,
+) -> Result<u64, TransferError> {
+    let mut writer = FileWriter::create(path).await?;
+    let result = write_rows(rows, format, &mut writer).await;
+    // A failed write stops the writer, whose error is the one to report.
+    writer.finish().await?;
+    result
+}
+
+async fn write_rows(
+    rows: &mut libsql::Rows,
+    format: Format,
+    writer: &mut FileWriter,
+) -> Result<u64, TransferError> {
+    let columns = (0..rows.column_count())
+        .map(|idx| rows.column_name(idx).unwrap_or_default().to_string())
+        .collect::<Vec<_>>();
+    let mut count = 0;
+    match format {
+        Format::Csv => {
+            let header = columns.iter().map(|column| Some(column.as_str()));
+            write_csv_record(&mut writer.buf, header)?;
+            while let Some(row) = rows.next().await? {
+                let mut fields = Vec::with_capacity(columns.len());
+                for idx in 0..rows.column_count() {
+                    fields.push(match row.get_value(idx)? {
+                        libsql::Value::Null => None,
+                        libsql::Value::Integer(v) => Some(v.to_string()),
+                        libsql::Value::Real(v) => Some(format!("{:?}", v)),
+                        libsql::Value::Text(v) => Some(v),
+                        libsql::Value::Blob(v) => Some(to_hex(&v)),
+                    });
+                }
+                write_csv_record(&mut writer.buf, fields.iter().map(|field| field.as_deref()))?;
+                writer.write_if_full().await?;
+                count += 1;
+            }
+        }
+        Format::Ndjson => {
+            while let Some(row) = rows.next().await? {
+                let mut obj = serde_json::Map::with_capacity(columns.len());
+                for (idx, column) in columns.iter().enumerate() {
+                    let v = match row.get_value(idx as i32)? {
+                        libsql::Value::Null => serde_json::Value::Null,
+                        libsql::Value::Integer(v) => serde_json::Value::from(v),
+                        libsql::Value::Real(v) => serde_json::Value::from(v),
+                        libsql::Value::Text(v) => serde_json::Value::from(v),
+                        libsql::Value::Blob(v) => serde_json::Value::from(to_hex(&v)),
+                    };
+                    obj.insert(column.clone(), v);
+                }
+                serde_json::to_writer(&mut writer.buf, &obj)?;
+                writer.buf.push(b'\n');
+                writer.write_if_full().await?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// The size of the chunks of output that are handed to the writer thread.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Writes a file on a blocking thread, so that a large export doesn't stall
+/// the runtime, which may have a single thread, while the file is written.
+struct FileWriter {
+    /// Output that has not been handed to the writer thread yet.
+    buf: Vec<u8>,
+    chunks: mpsc::Sender<Vec<u8>>,
+    task: JoinHandle<std::io::Result<()>>,
+}
+
+impl FileWriter {
+    async fn create(path: &str) -> Result<Self, TransferError> {
+        let path = path.to_string();
+        let file = blocking(move || Ok(File::create(path)?)).await?;
+        let (chunks, mut rx) = mpsc::channel::<Vec<u8>>(4);
+        let task = tokio::task::spawn_blocking(move || {
+            let mut file = file;
+            while let Some(chunk) = rx.blocking_recv() {
+                file.write_all(&chunk)?;
+            }
+            Ok(())
+        });
+        Ok(FileWriter {
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            chunks,
+            task,
+        })
+    }
+
+    async fn write_if_full(&mut self) -> Result<(), TransferError> {
+        if self.buf.len() < CHUNK_SIZE {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE));
+        self.chunks.send(chunk).await.map_err(|_| {
+            // The writer thread stopped on an error, which `finish()` returns.
+            TransferError::Invalid("The export file could not be written".to_string())
+        })
+    }
+
+    /// Writes the rest of the output and waits for the file to be written.
+    async fn finish(self) -> Result<(), TransferError> {
+        let FileWriter { buf, chunks, task } = self;
+        if !buf.is_empty() {
+            // An error is returned by the writer thread.
+            let _ = chunks.send(buf).await;
+        }
+        drop(chunks);
+        Ok(task.await.map_err(join_error)??)
+    }
+}
+
+/// Runs blocking file I/O on a blocking thread.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, TransferError> + Send + 'static,
+) -> Result<T, TransferError> {
+    tokio::task::spawn_blocking(f).await.map_err(join_error)?
+}
+
+fn join_error(err: tokio::task::JoinError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
+
+/// Inserts the records of the file at `path` into `table`, returning the
+/// number of rows inserted.
+///
+/// The rows are inserted in a savepoint, so that either all of them or none
+/// are inserted, with `batch_size` rows per INSERT statement. If `columns` is
+/// empty, the column names are taken from the CSV header or from the keys of
+/// the first NDJSON object. Text values for columns declared as BLOB are
+/// decoded from hex, as written by `export_rows()`. CSV fields for columns
+/// declared without a type, which don't convert text to numbers, are
+/// inserted as integers or reals if they are written like one.
+pub(crate) async fn import_file(
+    conn: &libsql::Connection,
+    table: &str,
+    path: &str,
+    format: Format,
+    columns: Vec<String>,
+    batch_size: usize,
+) -> Result<u64, TransferError> {
+    let path = path.to_string();
+    let (reader, columns) = blocking(move || {
+        let mut reader = RecordReader::open(&path, format)?;
+        let columns = reader.select_columns(columns)?;
+        Ok((reader, columns))
+    })
+    .await?;
+    if columns.is_empty() {
+        return Err(TransferError::Invalid(format!(
+            "No columns to import into {}",
+            table
+        )));
+    }
+    let mut column_types = column_types(conn, table, &columns).await?;
+    if matches!(format, Format::Ndjson) {
+        // JSON values already have a type.
+        for column_type in column_types.iter_mut() {
+            if matches!(column_type, ColumnType::Untyped) {
+                *column_type = ColumnType::Other;
+            }
+        }
+    }
+    let mut records = RecordStream::start(reader, columns.clone());
+    conn.execute("SAVEPOINT libsql_import", ()).await?;
+    let result = import_records(
+        conn,
+        table,
+        &mut records,
+        &columns,
+        &column_types,
+        batch_size,
+    )
+    .await;
+    match result {
+        Ok(count) => {
+            conn.execute("RELEASE libsql_import", ()).await?;
+            Ok(count)
+        }
+        Err(err) => {
+            if let Err(err) = conn.execute("ROLLBACK TO libsql_import", ()).await {
+                trace!("Failed to roll back the import: {}", err);
+            }
+            if let Err(err) = conn.execute("RELEASE libsql_import", ()).await {
+                trace!("Failed to release the import savepoint: {}", err);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// SQLite's default limit on the number of bind parameters in a statement.
+const MAX_VARIABLES: usize = 32766;
+
+async fn import_records(
+    conn: &libsql::Connection,
+    table: &str,
+    records: &mut RecordStream,
+    columns: &[String],
+    column_types: &[ColumnType],
+    batch_size: usize,
+) -> Result<u64, TransferError> {
+    let rows_per_insert = batch_size.min(MAX_VARIABLES / columns.len()).max(1);
+    let mut stmt = conn
+        .prepare(&insert_sql(table, columns, rows_per_insert))
+        .await?;
+    let mut count = 0;
+    let mut batch = Vec::with_capacity(rows_per_insert * columns.len());
+    let mut pending = 0;
+    while let Some(values) = records.next().await? {
+        if values.len() != columns.len() {
+            return Err(TransferError::Invalid(format!(
+                "Record {} has {} values, expected {}",
+                count + pending as u64 + 1,
+                values.len(),
+                columns.len()
+            )));
+        }
+        for (value, column_type) in values.into_iter().zip(column_types) {
+            batch.push(match column_type {
+                ColumnType::Blob => decode_blob(value)?,
+                ColumnType::Untyped => infer_number(value),
+                ColumnType::Other => value,
+            });
+        }
+        pending += 1;
+        if pending == rows_per_insert {
+            stmt.reset();
+            let values = std::mem::take(&mut batch);
+            stmt.run(libsql::params::Params::Positional(values)).await?;
+            count += pending as u64;
+            pending = 0;
+        }
+    }
+    if pending > 0 {
+        let mut stmt = conn.prepare(&insert_sql(table, columns, pending)).await?;
+        stmt.run(libsql::params::Params::Positional(batch)).await?;
+        count += pending as u64;
+    }
+    Ok(count)
+}
+
+fn insert_sql(table: &str, columns: &[String], rows: usize) -> String {
+    let row = format!("({})", vec!["?"; columns.len()].join(", "));
+    format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        quote_identifier(table),
+        columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", "),
+        vec![row; rows].join(", ")
+    )
+}
+
+/// How the values of a column are converted before they are inserted.
+#[derive(Clone, Copy)]
+enum ColumnType {
+    /// A column declared as BLOB, whose values are hex strings.
+    Blob,
+    /// A column declared without a type, which stores text as text.
+    Untyped,
+    Other,
+}
+
+/// Returns the type of each of `columns` in `table`.
+async fn column_types(
+    conn: &libsql::Connection,
+    table: &str,
+    columns: &[String],
+) -> Result<Vec<ColumnType>, TransferError> {
+    let sql = format!("PRAGMA table_info({})", quote_identifier(table));
+    let mut rows = conn.query(&sql, ()).await?;
+    let mut decl_types = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let name = row.get::<String>(1)?;
+        let decl_type = row.get::<String>(2)?;
+        decl_types.insert(name.to_lowercase(), decl_type.to_uppercase());
+    }
+    Ok(columns
+        .iter()
+        .map(|column| match decl_types.get(&column.to_lowercase()) {
+            Some(decl_type) if decl_type.contains("BLOB") => ColumnType::Blob,
+            Some(decl_type) if decl_type.trim().is_empty() => ColumnType::Untyped,
+            _ => ColumnType::Other,
+        })
+        .collect())
+}
+
+/// Converts text written like an integer or a real, as `export_rows()` writes
+/// them, to a number.
+fn infer_number(value: libsql::Value) -> libsql::Value {
+    let libsql::Value::Text(text) = &value else {
+        return value;
+    };
+    if let Ok(v) = text.parse::<i64>() {
+        // Leading zeros or a plus sign would be lost.
+        if v.to_string() == *text {
+            return libsql::Value::Integer(v);
+        }
+    }
+    // Rust also parses words such as "inf" and "NaN" as reals, and integers
+    // that didn't convert are left as text.
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let real = digits.starts_with(|c: char| c.is_ascii_digit())
+        && !(digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit()))
+        && digits.contains(['.', 'e', 'E'])
+        && digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'));
+    match text.parse::<f64>() {
+        Ok(v) if real => libsql::Value::Real(v),
+        _ => value,
+    }
+}
+
+fn decode_blob(value: libsql::Value) -> Result<libsql::Value, TransferError> {
+    let libsql::Value::Text(hex) = value else {
+        return Ok(value);
+    };
+    from_hex(&hex)
+        .map(libsql::Value::Blob)
+        .ok_or_else(|| TransferError::Invalid(format!("Invalid hex string for a blob: {}", hex)))
+}
+
+/// The number of records that the reader thread hands over at a time.
+const RECORDS_PER_CHUNK: usize = 1024;
+
+type RecordChunk = Result<Vec<Vec<libsql::Value>>, TransferError>;
+
+/// Reads records on a blocking thread, so that a large import doesn't stall
+/// the runtime, which may have a single thread, while the file is read.
+struct RecordStream {
+    chunks: mpsc::Receiver<RecordChunk>,
+    chunk: std::vec::IntoIter<Vec<libsql::Value>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl RecordStream {
+    fn start(mut reader: RecordReader, columns: Vec<String>) -> Self {
+        let (tx, chunks) = mpsc::channel(4);
+        let task = tokio::task::spawn_blocking(move || loop {
+            let mut chunk = Vec::with_capacity(RECORDS_PER_CHUNK);
+            let result = loop {
+                if chunk.len() == RECORDS_PER_CHUNK {
+                    break Ok(false);
+                }
+                match reader.next_record(&columns) {
+                    Ok(Some(record)) => chunk.push(record),
+                    Ok(None) => break Ok(true),
+                    Err(err) => break Err(err),
+                }
+            };
+            let (chunk, done) = match result {
+                Ok(done) => (Ok(chunk), done),
+                Err(err) => (Err(err), true),
+            };
+            // The stream is dropped if the import fails.
+            if tx.blocking_send(chunk).is_err() || done {
+                return;
+            }
+        });
+        RecordStream {
+            chunks,
+            chunk: Vec::new().into_iter(),
+            task: Some(task),
+        }
+    }
+
+    async fn next(&mut self) -> Result<Option<Vec<libsql::Value>>, TransferError> {
+        loop {
+            if let Some(record) = self.chunk.next() {
+                return Ok(Some(record));
+            }
+            match self.chunks.recv().await {
+                Some(chunk) => self.chunk = chunk?.into_iter(),
+                None => {
+                    // The reader thread only stops early if it panics.
+                    if let Some(task) = self.task.take() {
+                        task.await.map_err(join_error)?;
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+enum RecordReader {
+    Csv {
+        reader: CsvReader,
+        /// Index of the CSV field of each imported column.
+        fields: Vec<usize>,
+    },
+    Ndjson {
+        lines: Lines<BufReader<File>>,
+        peeked: Option<serde_json::Value>,
+    },
+}
+
+impl RecordReader {
+    fn open(path: &str, format: Format) -> Result<Self, TransferError> {
+        let file = File::open(path)?;
+        Ok(match format {
+            Format::Csv => RecordReader::Csv {
+                reader: CsvReader::new(file),
+                fields: vec![],
+            },
+            Format::Ndjson => RecordReader::Ndjson {
+                lines: BufReader::new(file).lines(),
+                peeked: None,
+            },
+        })
+    }
+
+    /// Returns the columns to import, which default to the columns of the
+    /// file, and matches them to the CSV fields by the header row.
+    fn select_columns(&mut self, columns: Vec<String>) -> Result<Vec<String>, TransferError> {
+        match self {
+            RecordReader::Csv { reader, fields } => {
+                let header = reader
+                    .read_record()?
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Option::unwrap_or_default)
+                    .collect::<Vec<_>>();
+                if columns.is_empty() {
+                    *fields = (0..header.len()).collect();
+                    return Ok(header);
+                }
+                *fields = columns
+                    .iter()
+                    .map(|column| {
+                        header
+                            .iter()
+                            .position(|name| name == column)
+                            .ok_or_else(|| {
+                                TransferError::Invalid(format!(
+                                    "Column {} not found in the CSV header",
+                                    column
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(columns)
+            }
+            RecordReader::Ndjson { .. } if !columns.is_empty() => Ok(columns),
+            RecordReader::Ndjson { .. } => match self.peek_json()? {
+                Some(serde_json::Value::Object(obj)) => Ok(obj.keys().cloned().collect()),
+                Some(_) => Err(TransferError::Invalid(
+                    "The columns option is required to import NDJSON arrays".to_string(),
+                )),
+                None => Ok(vec![]),
+            },
+        }
+    }
+
+    fn next_record(
+        &mut self,
+        columns: &[String],
+    ) -> Result<Option<Vec<libsql::Value>>, TransferError> {
+        match self {
+            RecordReader::Csv { reader, fields } => {
+                let Some(mut record) = reader.read_record()? else {
+                    return Ok(None);
+                };
+                let mut values = Vec::with_capacity(fields.len());
+                for idx in fields.iter() {
+                    let value = match record.get_mut(*idx).map(Option::take) {
+                        Some(Some(field)) => libsql::Value::Text(field),
+                        Some(None) => libsql::Value::Null,
+                        None => {
+                            return Err(TransferError::Invalid(format!(
+                                "CSV record {} has {} fields, expected at least {}",
+                                reader.records,
+                                record.len(),
+                                idx + 1
+                            )))
+                        }
+                    };
+                    values.push(value);
+                }
+                Ok(Some(values))
+            }
+            RecordReader::Ndjson { .. } => {
+                self.peek_json()?;
+                let RecordReader::Ndjson { peeked, .. } = self else {
+                    unreachable!()
+                };
+                match peeked.take() {
+                    Some(serde_json::Value::Object(mut obj)) => Ok(Some(
+                        columns
+                            .iter()
+                            .map(|column| {
+                                obj.remove(column)
+                                    .map(json_to_value)
+                                    .unwrap_or(libsql::Value::Null)
+                            })
+                            .collect(),
+                    )),
+                    Some(serde_json::Value::Array(values)) => {
+                        Ok(Some(values.into_iter().map(json_to_value).collect()))
+                    }
+                    Some(v) => Err(TransferError::Invalid(format!(
+                        "Expected a JSON object or array, got: {}",
+                        v
+                    ))),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Parses the next non-empty NDJSON line unless one is already peeked.
+    fn peek_json(&mut self) -> Result<Option<&serde_json::Value>, TransferError> {
+        let RecordReader::Ndjson { lines, peeked } = self else {
+            return Ok(None);
+        };
+        if peeked.is_none() {
+            for line in lines.by_ref() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    *peeked = Some(serde_json::from_str(&line)?);
+                    break;
+                }
+            }
+        }
+        Ok(peeked.as_ref())
+    }
+}
+
+/// Reads CSV records as defined by RFC 4180. Unlike a quoted empty string, an
+/// empty field is read as `None`.
+struct CsvReader {
+    bytes: Peekable<Bytes<BufReader<File>>>,
+    /// Number of records read so far, including the header row.
+    records: u64,
+}
+
+impl CsvReader {
+    fn new(file: File) -> Self {
+        CsvReader {
+            bytes: BufReader::new(file).bytes().peekable(),
+            records: 0,
+        }
+    }
+
+    fn next_byte_if(&mut self, expected: u8) -> bool {
+        let matches = matches!(self.bytes.peek(), Some(Ok(byte)) if *byte == expected);
+        if matches {
+            self.bytes.next();
+        }
+        matches
+    }
+
+    fn read_record(&mut self) -> Result<Option<Vec<Option<String>>>, TransferError> {
+        let mut fields = Vec::new();
+        let mut field = Vec::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+        let mut started = false;
+        loop {
+            let byte = match self.bytes.next().transpose()? {
+                Some(byte) => byte,
+                None if !started => return Ok(None),
+                None if in_quotes => {
+                    return Err(TransferError::Invalid(format!(
+                        "Unterminated quoted field in CSV record {}",
+                        self.records + 1
+                    )))
+                }
+                None => break,
+            };
+            if in_quotes {
+                if byte != b'"' {
+                    field.push(byte);
+                } else if self.next_byte_if(b'"') {
+                    field.push(b'"');
+                } else {
+                    in_quotes = false;
+                }
+                continue;
+            }
+            match byte {
+                // Blank lines are skipped.
+                b'\r' | b'\n' if !started => {}
+                b'\r' | b'\n' => {
+                    if byte == b'\r' {
+                        self.next_byte_if(b'\n');
+                    }
+                    break;
+                }
+                b'"' if field.is_empty() && !quoted => {
+                    started = true;
+                    quoted = true;
+                    in_quotes = true;
+                }
+                b',' => {
+                    started = true;
+                    fields.push(self.take_field(&mut field, quoted)?);
+                    quoted = false;
+                }
+                _ => {
+                    started = true;
+                    field.push(byte);
+                }
+            }
+        }
+        fields.push(self.take_field(&mut field, quoted)?);
+        self.records += 1;
+        Ok(Some(fields))
+    }
+
+    fn take_field(
+        &self,
+        field: &mut Vec<u8>,
+        quoted: bool,
+    ) -> Result<Option<String>, TransferError> {
+        if field.is_empty() && !quoted {
+            return Ok(None);
+        }
+        String::from_utf8(std::mem::take(field))
+            .map(Some)
+            .map_err(|_| {
+                TransferError::Invalid(format!("Invalid UTF-8 in CSV record {}", self.records + 1))
+            })
+    }
+}
+
+/// Writes a CSV record, quoting the fields that need it. `None` is written as
+/// an empty field and an empty string as `""`.
+fn write_csv_record<'a, W: Write>(
+    writer: &mut W,
+    fields: impl Iterator<Item = Option<&'a str>>,
+) -> std::io::Result<()> {
+    for (idx, field) in fields.enumerate() {
+        if idx > 0 {
+            writer.write_all(b",")?;
+        }
+        let Some(field) = field else {
+            continue;
+        };
+        if field.is_empty() || field.contains([',', '"', '\r', '\n']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\n")
+}
+
+fn json_to_value(v: serde_json::Value) -> libsql::Value {
+    match v {
+        serde_json::Value::Null => libsql::Value::Null,
+        serde_json::Value::Bool(v) => libsql::Value::Integer(v as i64),
+        serde_json::Value::Number(v) => match v.as_i64() {
+            Some(v) => libsql::Value::Integer(v),
+            None => libsql::Value::Real(v.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(v) => libsql::Value::Text(v),
+        v => libsql::Value::Text(v.to_string()),
+    }
+}
+
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|digits| match digits {
+            [high, low] => Some((hex_digit(*high)? << 4) | hex_digit(*low)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|digit| digit as u8)
+}
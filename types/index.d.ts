@@ -26,15 +26,23 @@ declare namespace Libsql {
         busy: boolean;
 
         run(...params: BindParameters): Database.RunResult;
+        runMany(rows: BindParameters[] | Record<string, ArrayLike<unknown>>, options?: Database.RunManyOptions): Database.RunResult;
         get(...params: BindParameters): unknown;
         all(...params: BindParameters): unknown[];
         iterate(...params: BindParameters): IterableIterator<unknown>;
+        columnar(...params: BindParameters): Database.ColumnarResult;
+        arrow(...params: BindParameters): Buffer;
+        arrowBatches(...params: BindParameters): IterableIterator<Buffer>;
         pluck(toggleState?: boolean): this;
         expand(toggleState?: boolean): this;
         raw(toggleState?: boolean): this;
+        consistency(options: Database.ConsistencyOptions): this;
         bind(...params: BindParameters): this;
         columns(): ColumnDefinition[];
         safeIntegers(toggleState?: boolean): this;
+        stats(): Database.StatementStats;
+        explain(options?: Database.ExplainOptions): Database.ExplainResult;
+        finalize(): void;
     }
 
     interface ColumnDefinition {
@@ -51,6 +59,7 @@ declare namespace Libsql {
         deferred(...params: ArgumentTypes<F>): ReturnType<F>;
         immediate(...params: ArgumentTypes<F>): ReturnType<F>;
         exclusive(...params: ArgumentTypes<F>): ReturnType<F>;
+        readonly replicationIndex: number | undefined;
     }
 
     interface VirtualTableOptions {
@@ -72,8 +81,22 @@ declare namespace Libsql {
             source: string,
         ): BindParameters extends unknown[] ? Statement<BindParameters> : Statement<[BindParameters]>;
         transaction<F extends VariableArgFunction>(fn: F): Transaction<F>;
-        sync(): any;
+        sync(options?: Database.SyncOptions): Database.SyncResult;
+        syncUntil(replicationIndex: number, options?: Database.SyncUntilOptions): Database.SyncResult;
+        syncStatus(): Database.SyncStatus;
+        maxWriteReplicationIndex(): number | undefined;
+        pendingChanges(): Database.PendingChanges;
+        push(options?: Database.PushOptions): Database.PushResult;
+        pull(): Database.PullResult;
+        pauseSync(): void;
+        resumeSync(): void;
+        setSyncPeriod(seconds: number): void;
+        on(event: "sync", listener: (result: Database.SyncResult) => void): this;
+        on(event: "syncError", listener: (err: Error) => void): this;
+        off(event: "sync" | "syncError", listener: (...args: any[]) => void): this;
         exec(source: string): this;
+        exportQuery(source: string, params: unknown[] | {}, options: Database.ExportOptions): number;
+        importFile(table: string, path: string, options?: Database.ImportOptions): number;
         pragma(source: string, options?: Database.PragmaOptions): unknown;
         function(name: string, cb: (...params: unknown[]) => unknown): this;
         function(name: string, options: Database.RegistrationOptions, cb: (...params: unknown[]) => unknown): this;
@@ -90,6 +113,10 @@ declare namespace Libsql {
         table(name: string, options: VirtualTableOptions): this;
         unsafeMode(unsafe?: boolean): this;
         serialize(options?: Database.SerializeOptions): Buffer;
+        statementCacheStats(): Database.StatementCacheStats;
+        schema(): Database.Schema[];
+        migrate(options: Database.MigrateOptions): Database.MigrationResult;
+        explain(source: string, options?: Database.ExplainOptions): Database.ExplainResult;
     }
 
     interface DatabaseConstructor {
@@ -98,6 +125,9 @@ declare namespace Libsql {
         prototype: Database;
 
         SqliteError: typeof SqliteError;
+        configure(options?: Database.RuntimeOptions): void;
+        runtimeMetrics(): Database.RuntimeMetrics | undefined;
+        setLogger(options: Database.LoggerOptions | null): void;
     }
 }
 
@@ -113,6 +143,28 @@ declare namespace Database {
     interface RunResult {
         changes: number;
         lastInsertRowid: number | bigint;
+        replicationIndex?: number | undefined;
+    }
+
+    interface ColumnarResult {
+        length: number;
+        columns: Record<string, Float64Array | BigInt64Array | unknown[]>;
+        nulls: Record<string, Uint8Array>;
+    }
+
+    interface ExportOptions {
+        path: string;
+        format?: "csv" | "ndjson" | undefined;
+    }
+
+    interface ImportOptions {
+        format?: "csv" | "ndjson" | undefined;
+        columns?: string[] | undefined;
+        batchSize?: number | undefined;
+    }
+
+    interface RunManyOptions {
+        transaction?: boolean | undefined;
     }
 
     interface Options {
@@ -122,6 +174,235 @@ declare namespace Database {
         verbose?: ((message?: unknown, ...additionalArgs: unknown[]) => void) | undefined;
         nativeBinding?: string | undefined;
         syncUrl?: string | undefined;
+        bootstrapFrom?: string | undefined;
+        statementCacheSize?: number | undefined;
+        slowQueryThresholdMs?: number | undefined;
+        onSlowQuery?: ((event: SlowQueryEvent) => void) | undefined;
+    }
+
+    interface SlowQueryEvent {
+        sql: string;
+        parameters: string;
+        durationMs: number;
+        mode: "local" | "remote" | "delegated";
+    }
+
+    interface RuntimeOptions {
+        workerThreads?: number | undefined;
+        maxBlockingThreads?: number | undefined;
+        threadName?: string | undefined;
+        currentThread?: boolean | undefined;
+    }
+
+    interface RuntimeMetrics {
+        workers: number;
+        aliveTasks: number;
+        globalQueueDepth: number;
+    }
+
+    interface LogRecord {
+        level: "trace" | "debug" | "info" | "warn" | "error";
+        target: string;
+        message: string;
+        fields: Record<string, string | number | boolean>;
+        time: number;
+    }
+
+    interface LoggerOptions {
+        level?: string | undefined;
+        callback: (record: LogRecord) => void;
+    }
+
+    interface StatementCacheStats {
+        hits: number;
+        misses: number;
+        size: number;
+        capacity: number;
+    }
+
+    interface StatementStats {
+        executions: number;
+        totalTime: number;
+        minTime: number;
+        maxTime: number;
+        rowsReturned: number;
+        rowsRead: number;
+        fullScanSteps: number;
+        sorts: number;
+        autoIndexes: number;
+        vmSteps: number;
+    }
+
+    interface ExplainOptions {
+        bytecode?: boolean | undefined;
+    }
+
+    interface QueryPlanNode {
+        id: number;
+        parent: number;
+        detail: string;
+        children: QueryPlanNode[];
+    }
+
+    interface BytecodeInstruction {
+        addr: number;
+        opcode: string;
+        p1: number;
+        p2: number;
+        p3: number;
+        p4: string | null;
+        p5: number;
+        comment: string | null;
+    }
+
+    interface ExplainResult {
+        plan: QueryPlanNode[];
+        bytecode?: BytecodeInstruction[];
+    }
+
+    interface SyncOptions {
+        onProgress?: ((progress: SyncProgress) => void) | undefined;
+        changes?: boolean | undefined;
+        onConflict?: "fail" | undefined;
+    }
+
+    interface SyncUntilOptions {
+        timeoutMs?: number | undefined;
+    }
+
+    interface ConsistencyOptions {
+        minReplicationIndex?: number | undefined;
+        timeoutMs?: number | undefined;
+    }
+
+    interface SyncResult {
+        frames_synced: number;
+        frame_no: number | undefined;
+        changes?: SyncChanges | null;
+        pushed?: number;
+        pulled?: number | null;
+    }
+
+    interface PendingChanges {
+        frames: number;
+        transactions: number;
+        bytes: number;
+        oldestUnpushedTime: number | null;
+    }
+
+    interface PushOptions {
+        onConflict?: "fail" | undefined;
+    }
+
+    interface PushResult {
+        pushed: number;
+        pendingFrames: number;
+        durableFrameNo: number;
+        generation: number;
+    }
+
+    interface PullResult {
+        pulled: number | null;
+        frameNo: number | null;
+        pendingFrames: number;
+    }
+
+    interface SyncConflict {
+        status: number | null;
+        generation: number;
+        durableFrameNo: number;
+        pendingFrames: number;
+    }
+
+    interface SyncChanges {
+        tables: Array<{ name: string; rowids: Array<number | bigint> | null }>;
+        complete: boolean;
+    }
+
+    interface SyncStatus {
+        syncing: boolean;
+        lastSyncTime: number | null;
+        lastError: string | null;
+        replicationIndex: number | null;
+        framesSynced: number;
+        periodicSync: boolean;
+    }
+
+    interface SyncProgress {
+        bytesWritten: number;
+        framesApplied: number;
+        replicationIndex: number | null;
+    }
+
+    interface Migration {
+        id: string;
+        up: string;
+        down?: string | undefined;
+    }
+
+    interface MigrateOptions {
+        dir?: string | undefined;
+        migrations?: Migration[] | undefined;
+        table?: string | undefined;
+        to?: string | undefined;
+    }
+
+    interface MigrationResult {
+        applied: string[];
+        rolledBack: string[];
+    }
+
+    interface Schema {
+        name: string;
+        tables: SchemaTable[];
+        views: SchemaView[];
+        triggers: SchemaTrigger[];
+    }
+
+    interface SchemaTable {
+        name: string;
+        columns: SchemaColumn[];
+        indexes: SchemaIndex[];
+        foreignKeys: SchemaForeignKey[];
+        sql: string | null;
+    }
+
+    interface SchemaView {
+        name: string;
+        columns: SchemaColumn[];
+        sql: string | null;
+    }
+
+    interface SchemaTrigger {
+        name: string;
+        table: string;
+        sql: string | null;
+    }
+
+    interface SchemaColumn {
+        name: string;
+        type: string;
+        notNull: boolean;
+        default: string | null;
+        primaryKey: number;
+        hidden: boolean;
+        generated: "virtual" | "stored" | null;
+    }
+
+    interface SchemaIndex {
+        name: string;
+        unique: boolean;
+        origin: "c" | "u" | "pk";
+        partial: boolean;
+        columns: (string | null)[];
+    }
+
+    interface SchemaForeignKey {
+        table: string;
+        from: string[];
+        to: (string | null)[];
+        onUpdate: string;
+        onDelete: string;
     }
 
     interface SerializeOptions {